@@ -198,13 +198,15 @@ impl NoiseProxyConnection {
             direct_downstream_peer_addr, proxy_info
         );
         let upstream_framed = self
-            .connect_upstream::<v1::Codec, v1::Frame>(proxy_info, local_addr)
+            .connect_upstream::<v1::Codec, v1::Frame>(&proxy_info, local_addr)
             .await
             .map_err(|e| {
                 self.metrics.account_tcp_close_in_stage("upstream_noise");
                 e
             })?;
 
+        // Both directions only need to log the proxy information
+        let proxy_info = &proxy_info;
         let (mut downstream_sink, downstream_stream) = downstream_framed.split();
         let (mut upstream_sink, upstream_stream) = upstream_framed.split();
         let tripwire_clone = self.tripwire.clone();
@@ -269,7 +271,7 @@ impl NoiseProxyConnection {
     /// any useful information.
     async fn connect_upstream<C, F>(
         &self,
-        proxy_info: ProxyInfo,
+        proxy_info: &ProxyInfo,
         local_addr: SocketAddr,
     ) -> Result<Framed<TcpStream, C>>
     where
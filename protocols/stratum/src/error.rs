@@ -53,6 +53,10 @@ pub enum Error {
     #[error("Noise handshake error: {0}")]
     Noise(String),
 
+    /// The noise handshake didn't complete in time, the peer is most likely stalling
+    #[error("Noise handshake timed out after {0:?}")]
+    NoiseHandshakeTimeout(std::time::Duration),
+
     #[error("Noise protocol error: {0}")]
     NoiseProtocol(#[from] snow::error::Error),
 
@@ -62,6 +66,10 @@ pub enum Error {
     #[error("Noise base58 error: {0}")]
     NoiseEncoding(#[from] bs58::decode::Error),
 
+    /// The certificate is in a format this implementation doesn't understand
+    #[error("Unsupported certificate version: {version}, supported: {supported}")]
+    UnsupportedCertificateVersion { version: u16, supported: u16 },
+
     /// Stratum version 1 error
     #[error("V1 error: {0}")]
     V1(#[from] super::v1::error::Error),
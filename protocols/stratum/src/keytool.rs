@@ -31,8 +31,8 @@ use ii_stratum::v2::noise::auth::{ServerSecurityBundle, StaticPublicKeyFormat};
 use std::convert::{TryFrom, TryInto};
 use std::fs::{File, OpenOptions};
 use std::io::{Read, Write};
-use std::path::PathBuf;
-use std::time::Duration;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 use structopt::StructOpt;
 
 /// All commands recognized by the keytool
@@ -50,8 +50,18 @@ enum Command {
     GenNoiseKey(GenNoiseKeyCommand),
     /// Sign a specified public key and output a certificate
     SignKey(SignKeyCommand),
+    /// Sign multiple public keys and output a certificate for each of them
+    SignBatch(SignBatchCommand),
     /// Sign a specified secret key and output a server security bundle
     SignBundle(SignBundleCommand),
+    /// Renew certificate of an existing server security bundle, the bundle is replaced in place
+    RenewBundle(RenewBundleCommand),
+    /// Convert a raw secret key (hex or base64) into the keytool format or vice versa
+    Convert(ConvertCommand),
+    /// Print SHA-256 fingerprints of public keys in a key, certificate or bundle file for pinning
+    Fingerprint(FingerprintCommand),
+    /// Print decoded fields of a certificate or security bundle without validating it
+    Inspect(InspectCommand),
 }
 
 /// Generates keypair suitable for certification authority and stores secret and public key into
@@ -76,7 +86,8 @@ struct GenCAKeyCommand {
 
 impl GenCAKeyCommand {
     fn execute(self) -> Result<()> {
-        print!("Generating ED25519 keypair...");
+        // Progress goes to stderr so that it doesn't mix with a key written to stdout
+        eprint!("Generating ED25519 keypair...");
 
         use rand::rngs::OsRng;
         use ed25519_dalek::Keypair;
@@ -84,16 +95,18 @@ impl GenCAKeyCommand {
         let keypair: Keypair = Keypair::generate(&mut csprng);
 
         write_to_file(
+            OpenOptions::new(),
             &self.public_key_file,
             noise::auth::Ed25519PublicKeyFormat::new(keypair.public),
             "public key",
         )?;
         write_to_file(
+            secret_file_options(),
             &self.secret_key_file,
             noise::auth::Ed25519SecretKeyFormat::new(keypair.secret),
             "secret key",
         )?;
-        println!("DONE");
+        eprintln!("DONE");
 
         Ok(())
     }
@@ -117,26 +130,78 @@ struct GenNoiseKeyCommand {
         default_value = "server-noise-static-secret.key"
     )]
     secret_key_file: PathBuf,
+    /// Number of keypairs to generate. Key files are named after the base file names with an
+    /// index appended, e.g. `server-noise-static-public-0.key`
+    #[structopt(long)]
+    count: Option<usize>,
 }
 
 impl GenNoiseKeyCommand {
-    fn execute(self) -> Result<()> {
-        print!("Generating static ('s') keypair for Noise handshake ...");
+    /// Provides pairs of public and secret key files to generate
+    fn key_files(&self) -> Result<Vec<(PathBuf, PathBuf)>> {
+        let count = match self.count {
+            None => {
+                return Ok(vec![(
+                    self.public_key_file.clone(),
+                    self.secret_key_file.clone(),
+                )])
+            }
+            Some(count) => count,
+        };
+        if count == 0 {
+            return Err(anyhow!("Number of keypairs to generate must be positive"));
+        }
+        if is_stdio(&self.public_key_file) || is_stdio(&self.secret_key_file) {
+            return Err(anyhow!("Multiple keypairs cannot be written to stdout"));
+        }
+        let key_files: Vec<_> = (0..count)
+            .map(|index| {
+                (
+                    indexed_path(&self.public_key_file, index),
+                    indexed_path(&self.secret_key_file, index),
+                )
+            })
+            .collect();
+        // Check all files upfront so that a conflict doesn't leave the batch partially written
+        for (public_key_file, secret_key_file) in key_files.iter() {
+            for file_path_buf in &[public_key_file, secret_key_file] {
+                if file_path_buf.exists() {
+                    return Err(anyhow!("File {:?} already exists", file_path_buf));
+                }
+            }
+        }
+        Ok(key_files)
+    }
 
+    fn generate(public_key_file: &PathBuf, secret_key_file: &PathBuf) -> Result<()> {
         let keypair = noise::generate_keypair()
             .map_err(|e| anyhow!("Cannot generate noise keypair {:?}", e))?;
 
         write_to_file(
-            &self.public_key_file,
+            OpenOptions::new(),
+            public_key_file,
             noise::auth::StaticPublicKeyFormat::new(keypair.public),
             "noise static public key",
         )?;
         write_to_file(
-            &self.secret_key_file,
+            secret_file_options(),
+            secret_key_file,
             noise::auth::StaticSecretKeyFormat::new(keypair.private),
             "noise static secret key",
-        )?;
-        println!("DONE");
+        )
+    }
+
+    fn execute(self) -> Result<()> {
+        let key_files = self.key_files()?;
+        eprint!(
+            "Generating {} static ('s') keypair(s) for Noise handshake ...",
+            key_files.len()
+        );
+
+        for (public_key_file, secret_key_file) in key_files.iter() {
+            Self::generate(public_key_file, secret_key_file)?;
+        }
+        eprintln!("DONE");
 
         Ok(())
     }
@@ -147,15 +212,32 @@ impl GenNoiseKeyCommand {
 /// specified `secret_key_to_sign`, signing the certificate with `signing_key`.
 #[derive(Debug, StructOpt)]
 struct SignBundleCommand {
-    /// File that contains the secret key that we want to sign
+    /// File that contains the secret key that we want to sign, `-` for stdin
+    #[structopt(long, parse(from_os_str), required_unless = "secret-key-to-sign-env")]
+    secret_key_to_sign: Option<PathBuf>,
+    /// Environment variable that contains the base58 encoded secret key that we want to sign
+    #[structopt(long, conflicts_with = "secret-key-to-sign", requires = "bundle-file")]
+    secret_key_to_sign_env: Option<String>,
+    /// File to store the bundle into, `-` for stdout. Defaults to `secret_key_to_sign` with
+    /// `.cert` extension or to stdout when the secret key is read from stdin
     #[structopt(long, parse(from_os_str))]
-    secret_key_to_sign: PathBuf,
-    /// Actual signing key
-    #[structopt(short, long, parse(from_os_str))]
-    signing_key: PathBuf,
+    bundle_file: Option<PathBuf>,
+    /// Actual signing key, `-` for stdin
+    #[structopt(short, long, parse(from_os_str), required_unless = "signing-key-env")]
+    signing_key: Option<PathBuf>,
+    /// Environment variable that contains the base58 encoded signing key
+    #[structopt(long, conflicts_with = "signing-key")]
+    signing_key_env: Option<String>,
     /// How many days the generated certificate should be valid for
     #[structopt(short, long, default_value = "90")]
     valid_for_days: usize,
+    /// Start of the certificate validity as RFC 3339 date and time or unix timestamp, defaults
+    /// to now
+    #[structopt(long, parse(try_from_str = parse_valid_from))]
+    valid_from: Option<SystemTime>,
+    /// Allow `valid_from` to be in the past
+    #[structopt(long)]
+    allow_backdating: bool,
 }
 
 impl SignBundleCommand {
@@ -175,9 +257,13 @@ impl SignBundleCommand {
         T: TryFrom<String>,
         <T as std::convert::TryFrom<std::string::String>>::Error: std::fmt::Display,
     {
-        let mut file = Self::open_file(file_path_buf, error_context_descr)?;
         let mut file_content = String::new();
-        file.read_to_string(&mut file_content).context(format!(
+        if is_stdio(file_path_buf) {
+            std::io::stdin().read_to_string(&mut file_content)
+        } else {
+            Self::open_file(file_path_buf, error_context_descr)?.read_to_string(&mut file_content)
+        }
+        .context(format!(
             "Cannot read {} ({:?})",
             error_context_descr, file_path_buf
         ))?;
@@ -194,37 +280,36 @@ impl SignBundleCommand {
         Ok(parsed_file_content)
     }
 
+    fn read_secret_key_to_sign(&self) -> Result<noise::auth::StaticSecretKeyFormat> {
+        match (&self.secret_key_to_sign, &self.secret_key_to_sign_env) {
+            (_, Some(var)) => Ok(noise::auth::StaticSecretKeyFormat::new(
+                read_from_env::<noise::auth::EncodedStaticSecretKey>(
+                    var,
+                    "static secret key to sign",
+                )?
+                .into_inner(),
+            )),
+            (Some(file), None) => Self::read_from_file::<noise::auth::StaticSecretKeyFormat>(
+                file,
+                "static secret key to sign",
+            ),
+            (None, None) => Err(anyhow!("Missing static secret key to sign")),
+        }
+    }
+
     fn execute(self) -> Result<()> {
-        let secret_key = Self::read_from_file::<noise::auth::StaticSecretKeyFormat>(
-            &self.secret_key_to_sign,
-            "static secret key to sign",
-        )?;
+        check_single_stdin(&[self.secret_key_to_sign.as_ref(), self.signing_key.as_ref()])?;
+        let secret_key = self.read_secret_key_to_sign()?;
 
-        // FIXME: this breaks layers of abstraction of noise protocol. Certificate should be generated
-        // from existing public key.
-        let mut raw_secret_key = [0_u8; 32];
-        raw_secret_key.copy_from_slice(&secret_key.clone().into_inner());
-        let inner_public_key =
-            x25519_dalek::x25519(raw_secret_key, x25519_dalek::X25519_BASEPOINT_BYTES).to_vec();
-        let public_key = StaticPublicKeyFormat::new(inner_public_key);
+        let public_key = StaticPublicKeyFormat::new(noise::public_key_from_secret(
+            &secret_key.clone().into_inner(),
+        )?);
 
-        let authority_secret_key = Self::read_from_file::<noise::auth::Ed25519SecretKeyFormat>(
-            &self.signing_key,
-            "signing key",
-        )?
-        .into_inner();
+        let authority_keypair =
+            read_signing_key(self.signing_key.as_ref(), self.signing_key_env.as_deref())?;
 
-        // Dalek crate requires the full Keypair for signing
-        let authority_keypair = ed25519_dalek::Keypair {
-            // Derive the public key from the secret key
-            public: (&authority_secret_key).into(),
-            secret: authority_secret_key,
-        };
-
-        let header = noise::auth::SignedPartHeader::with_duration(Duration::from_secs(
-            (self.valid_for_days * 24 * 60 * 60) as u64,
-        ))
-        .map_err(|e| anyhow!("{:?}", e))?;
+        let header =
+            signed_part_header(self.valid_from, self.valid_for_days, self.allow_backdating)?;
 
         let signed_part =
             noise::auth::SignedPart::new(header, public_key.into_inner(), authority_keypair.public);
@@ -240,11 +325,24 @@ impl SignBundleCommand {
             .expect("BUG: Inconsistent server security bundle has been generated");
         let bundle_string =
             serde_json::to_string_pretty(&bundle).context("Couldn't serialize security bundle")?;
-        // Derive the certificate file name from the public key filename
-        let mut bundle_file = self.secret_key_to_sign;
-        bundle_file.set_extension("cert");
+        // Unless specified, derive the certificate file name from the secret key filename
+        let bundle_file = match (self.bundle_file, self.secret_key_to_sign) {
+            (Some(bundle_file), _) => bundle_file,
+            (None, Some(mut bundle_file)) => {
+                if !is_stdio(&bundle_file) {
+                    bundle_file.set_extension("cert");
+                }
+                bundle_file
+            }
+            (None, None) => return Err(anyhow!("Missing bundle file")),
+        };
 
-        write_to_file(&bundle_file, bundle_string, "security bundle")
+        write_to_file(
+            secret_file_options(),
+            &bundle_file,
+            bundle_string,
+            "security bundle",
+        )
     }
 }
 
@@ -252,15 +350,26 @@ impl SignBundleCommand {
 /// certificate with `signing_key`.
 #[derive(Debug, StructOpt)]
 struct SignKeyCommand {
-    /// File that contains the public key that we want to sign
+    /// File that contains the public key that we want to sign, `-` for stdin. The certificate
+    /// is stored next to it or written to stdout when reading from stdin.
     #[structopt(short, long, parse(from_os_str))]
     public_key_to_sign: PathBuf,
-    /// Actual signing key
-    #[structopt(short, long, parse(from_os_str))]
-    signing_key: PathBuf,
+    /// Actual signing key, `-` for stdin
+    #[structopt(short, long, parse(from_os_str), required_unless = "signing-key-env")]
+    signing_key: Option<PathBuf>,
+    /// Environment variable that contains the base58 encoded signing key
+    #[structopt(long, conflicts_with = "signing-key")]
+    signing_key_env: Option<String>,
     /// How many days the generated certificate should be valid for
     #[structopt(short, long, default_value = "90")]
     valid_for_days: usize,
+    /// Start of the certificate validity as RFC 3339 date and time or unix timestamp, defaults
+    /// to now
+    #[structopt(long, parse(try_from_str = parse_valid_from))]
+    valid_from: Option<SystemTime>,
+    /// Allow `valid_from` to be in the past
+    #[structopt(long)]
+    allow_backdating: bool,
 }
 
 impl SignKeyCommand {
@@ -280,9 +389,13 @@ impl SignKeyCommand {
         T: TryFrom<String>,
         <T as std::convert::TryFrom<std::string::String>>::Error: std::fmt::Display,
     {
-        let mut file = Self::open_file(file_path_buf, error_context_descr)?;
         let mut file_content = String::new();
-        file.read_to_string(&mut file_content).context(format!(
+        if is_stdio(file_path_buf) {
+            std::io::stdin().read_to_string(&mut file_content)
+        } else {
+            Self::open_file(file_path_buf, error_context_descr)?.read_to_string(&mut file_content)
+        }
+        .context(format!(
             "Cannot read {} ({:?})",
             error_context_descr, file_path_buf
         ))?;
@@ -299,52 +412,639 @@ impl SignKeyCommand {
         Ok(parsed_file_content)
     }
 
-    fn execute(self) -> Result<()> {
-        let public_key = Self::read_from_file::<noise::auth::StaticPublicKeyFormat>(
-            &self.public_key_to_sign,
-            "static public key to sign",
-        )?;
-
+    /// Reads the signing key and builds the full keypair that is needed for signing
+    fn read_authority_keypair(signing_key: &PathBuf) -> Result<ed25519_dalek::Keypair> {
         let authority_secret_key = Self::read_from_file::<noise::auth::Ed25519SecretKeyFormat>(
-            &self.signing_key,
+            signing_key,
             "signing key",
         )?
         .into_inner();
 
-        // Dalek crate requires the full Keypair for signing
-        let authority_keypair = ed25519_dalek::Keypair {
-            // Derive the public key from the secret key
-            public: (&authority_secret_key).into(),
-            secret: authority_secret_key,
-        };
+        Ok(authority_keypair(authority_secret_key))
+    }
 
-        let header = noise::auth::SignedPartHeader::with_duration(Duration::from_secs(
-            (self.valid_for_days * 24 * 60 * 60) as u64,
-        ))
-        .map_err(|e| anyhow!("{:?}", e))?;
+    /// Signs public key stored in `public_key_to_sign` and stores the certificate next to it.
+    /// Returns path of the certificate. An existing certificate is only replaced if `overwrite`
+    /// is set and it has been signed by the same authority.
+    fn sign(
+        public_key_to_sign: &PathBuf,
+        authority_keypair: &ed25519_dalek::Keypair,
+        header: noise::auth::SignedPartHeader,
+        overwrite: bool,
+    ) -> Result<PathBuf> {
+        // Derive the certificate file name from the public key filename
+        let mut cert_file = public_key_to_sign.clone();
+        if !is_stdio(&cert_file) {
+            cert_file.set_extension("cert");
+        }
+        if overwrite {
+            if let Ok(existing_certificate) = noise::auth::Certificate::read_from_file(&cert_file) {
+                existing_certificate
+                    .check_signing_key(&authority_keypair.secret)
+                    .map_err(|e| anyhow!("{}", e))
+                    .context(format!("Cannot re-sign certificate ({:?})", cert_file))?;
+            }
+        }
+
+        let public_key = Self::read_from_file::<noise::auth::StaticPublicKeyFormat>(
+            public_key_to_sign,
+            "static public key to sign",
+        )?;
 
         let signed_part =
             noise::auth::SignedPart::new(header, public_key.into_inner(), authority_keypair.public);
 
         let signature = signed_part
-            .sign_with(&authority_keypair)
+            .sign_with(authority_keypair)
             .map_err(|e| anyhow!("{:?}", e))
             .context("Signing certificate")?;
 
         // Final step is to compose the certificate from all components and serialize it into a file
         let certificate = noise::auth::Certificate::new(signed_part, signature);
-        // Derive the certificate file name from the public key filename
-        let mut cert_file = self.public_key_to_sign;
-        cert_file.set_extension("cert");
 
-        write_to_file(&cert_file, certificate, "certificate")
+        if overwrite {
+            write_atomic(OpenOptions::new(), &cert_file, certificate, "certificate")?;
+        } else {
+            write_to_file(OpenOptions::new(), &cert_file, certificate, "certificate")?;
+        }
+        Ok(cert_file)
     }
+
+    fn execute(self) -> Result<()> {
+        check_single_stdin(&[Some(&self.public_key_to_sign), self.signing_key.as_ref()])?;
+        let authority_keypair =
+            read_signing_key(self.signing_key.as_ref(), self.signing_key_env.as_deref())?;
+        let header =
+            signed_part_header(self.valid_from, self.valid_for_days, self.allow_backdating)?;
+        Self::sign(&self.public_key_to_sign, &authority_keypair, header, false)?;
+        Ok(())
+    }
+}
+
+/// Command that creates signed certificates for multiple public keys, signing the certificates
+/// with `signing_key`. Each certificate is stored next to its public key.
+#[derive(Debug, StructOpt)]
+struct SignBatchCommand {
+    /// Files that contain the public keys that we want to sign. All public keys in files with
+    /// `.key` extension are taken from a specified directory.
+    #[structopt(parse(from_os_str), required = true)]
+    public_keys_to_sign: Vec<PathBuf>,
+    /// Actual signing key, `-` for stdin
+    #[structopt(short, long, parse(from_os_str), required_unless = "signing-key-env")]
+    signing_key: Option<PathBuf>,
+    /// Environment variable that contains the base58 encoded signing key
+    #[structopt(long, conflicts_with = "signing-key")]
+    signing_key_env: Option<String>,
+    /// How many days the generated certificates should be valid for
+    #[structopt(short, long, default_value = "90")]
+    valid_for_days: usize,
+    /// Start of the certificate validity as RFC 3339 date and time or unix timestamp, defaults
+    /// to now
+    #[structopt(long, parse(try_from_str = parse_valid_from))]
+    valid_from: Option<SystemTime>,
+    /// Allow `valid_from` to be in the past
+    #[structopt(long)]
+    allow_backdating: bool,
+    /// Replace already existing certificates
+    #[structopt(long)]
+    overwrite: bool,
+}
+
+impl SignBatchCommand {
+    /// Expands directories in `public_keys_to_sign` into the key files they contain
+    fn collect_public_keys(&self) -> Result<Vec<PathBuf>> {
+        let mut public_keys = Vec::new();
+        for path in self.public_keys_to_sign.iter() {
+            if path.is_dir() {
+                let mut dir_keys = std::fs::read_dir(path)
+                    .context(format!("cannot read directory ({:?})", path))?
+                    .map(|entry| entry.map(|entry| entry.path()))
+                    .collect::<std::io::Result<Vec<_>>>()
+                    .context(format!("cannot read directory ({:?})", path))?;
+                // Other key files, e.g. the secret keys generated along with the public ones,
+                // are skipped
+                dir_keys.retain(|key| {
+                    key.is_file()
+                        && key.extension() == Some("key".as_ref())
+                        && SignKeyCommand::read_from_file::<noise::auth::StaticPublicKeyFormat>(
+                            key,
+                            "static public key to sign",
+                        )
+                        .is_ok()
+                });
+                dir_keys.sort();
+                public_keys.extend(dir_keys);
+            } else {
+                public_keys.push(path.clone());
+            }
+        }
+        Ok(public_keys)
+    }
+
+    fn execute(self) -> Result<()> {
+        let public_keys = self.collect_public_keys()?;
+        let mut inputs: Vec<_> = public_keys.iter().map(Some).collect();
+        inputs.push(self.signing_key.as_ref());
+        check_single_stdin(&inputs)?;
+        let authority_keypair =
+            read_signing_key(self.signing_key.as_ref(), self.signing_key_env.as_deref())?;
+        let header =
+            signed_part_header(self.valid_from, self.valid_for_days, self.allow_backdating)?;
+
+        // Keep going on errors so that a single broken key doesn't block the whole batch
+        let mut failed = 0;
+        for public_key in public_keys.iter() {
+            match SignKeyCommand::sign(
+                public_key,
+                &authority_keypair,
+                header.clone(),
+                self.overwrite,
+            ) {
+                Ok(cert_file) => println!("Signed {:?} -> {:?}", public_key, cert_file),
+                Err(e) => {
+                    eprintln!("Failed to sign {:?}: {:#}", public_key, e);
+                    failed += 1;
+                }
+            }
+        }
+        println!(
+            "Signed {} of {} public keys",
+            public_keys.len() - failed,
+            public_keys.len()
+        );
+
+        if failed > 0 {
+            Err(anyhow!("Failed to sign {} public keys", failed))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Command that renews the certificate of an existing server security `bundle`, signing the
+/// certificate with `signing_key`. The bundle file is atomically replaced so that a running server
+/// never reads a partially written bundle.
+#[derive(Debug, StructOpt)]
+struct RenewBundleCommand {
+    /// File that contains the security bundle to be renewed
+    #[structopt(short, long, parse(from_os_str))]
+    bundle: PathBuf,
+    /// Actual signing key, it has to be the key that signed the current certificate, `-` for
+    /// stdin
+    #[structopt(short, long, parse(from_os_str), required_unless = "signing-key-env")]
+    signing_key: Option<PathBuf>,
+    /// Environment variable that contains the base58 encoded signing key
+    #[structopt(long, conflicts_with = "signing-key")]
+    signing_key_env: Option<String>,
+    /// How many days the renewed certificate should be valid for
+    #[structopt(short, long, default_value = "90")]
+    valid_for_days: usize,
+    /// Start of the certificate validity as RFC 3339 date and time or unix timestamp, defaults
+    /// to now
+    #[structopt(long, parse(try_from_str = parse_valid_from))]
+    valid_from: Option<SystemTime>,
+    /// Allow `valid_from` to be in the past
+    #[structopt(long)]
+    allow_backdating: bool,
+}
+
+impl RenewBundleCommand {
+    fn execute(self) -> Result<()> {
+        check_single_stdin(&[Some(&self.bundle), self.signing_key.as_ref()])?;
+        let bundle_string =
+            SignBundleCommand::read_from_file::<String>(&self.bundle, "security bundle")?;
+        let bundle = ServerSecurityBundle::read_from_string(&bundle_string)
+            .map_err(|e| anyhow!("Cannot parse security bundle ({:?}) {}", self.bundle, e))?;
+
+        let authority_keypair =
+            read_signing_key(self.signing_key.as_ref(), self.signing_key_env.as_deref())?;
+
+        let header =
+            signed_part_header(self.valid_from, self.valid_for_days, self.allow_backdating)?;
+
+        let renewed_bundle = bundle
+            .renew(header, &authority_keypair)
+            .map_err(|e| anyhow!("{}", e))
+            .context("Renewing certificate")?;
+        if !renewed_bundle
+            .certificate
+            .has_overlap_with(&bundle.certificate)
+        {
+            eprintln!(
+                "WARNING: Renewed certificate doesn't overlap the current one, there is a gap in \
+                validity ({:?})",
+                self.bundle
+            );
+        }
+        let renewed_bundle_string = serde_json::to_string_pretty(&renewed_bundle)
+            .context("Couldn't serialize security bundle")?;
+
+        write_atomic(
+            secret_file_options(),
+            &self.bundle,
+            renewed_bundle_string,
+            "security bundle",
+        )
+    }
+}
+
+/// Kind of the secret key being converted by `ConvertCommand`
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SecretKeyType {
+    /// Ed25519 key of the certification authority
+    Ed25519,
+    /// Static key for the noise handshake
+    Noise,
+}
+
+impl std::str::FromStr for SecretKeyType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "ed25519" => Ok(Self::Ed25519),
+            "noise" => Ok(Self::Noise),
+            _ => Err(anyhow!(
+                "Unknown key type: {}, expected: ed25519 or noise",
+                s
+            )),
+        }
+    }
+}
+
+/// Command that converts a raw 32-byte secret key as used by other tools into the keytool format
+/// or, with `to_raw`, the other way around. The output file is created with private permissions.
+#[derive(Debug, StructOpt)]
+struct ConvertCommand {
+    /// File that contains the secret key to be converted
+    #[structopt(short, long, parse(from_os_str))]
+    input: PathBuf,
+    /// File that the converted secret key is written to
+    #[structopt(short, long, parse(from_os_str))]
+    output: PathBuf,
+    /// Type of the secret key: ed25519 or noise
+    #[structopt(short = "t", long, default_value = "ed25519")]
+    key_type: SecretKeyType,
+    /// Encoding of the raw secret key: hex or base64
+    #[structopt(short, long, default_value = "hex")]
+    encoding: noise::auth::RawKeyEncoding,
+    /// Convert a secret key in the keytool format into the raw secret key
+    #[structopt(long)]
+    to_raw: bool,
+}
+
+impl ConvertCommand {
+    fn execute(self) -> Result<()> {
+        if self.to_raw {
+            self.convert_to_raw()
+        } else {
+            self.convert_from_raw()
+        }
+    }
+
+    fn convert_from_raw(&self) -> Result<()> {
+        let raw_key = SignKeyCommand::read_from_file::<String>(&self.input, "raw secret key")?;
+        let raw_key = self
+            .encoding
+            .decode(&raw_key)
+            .map_err(|e| anyhow!("Cannot decode raw secret key ({:?}) {}", self.input, e))?;
+
+        match self.key_type {
+            SecretKeyType::Ed25519 => write_to_file(
+                secret_file_options(),
+                &self.output,
+                noise::auth::Ed25519SecretKeyFormat::from_raw(&raw_key)
+                    .map_err(|e| anyhow!("{}", e))?,
+                "secret key",
+            ),
+            SecretKeyType::Noise => write_to_file(
+                secret_file_options(),
+                &self.output,
+                noise::auth::StaticSecretKeyFormat::from_raw(&raw_key)
+                    .map_err(|e| anyhow!("{}", e))?,
+                "noise static secret key",
+            ),
+        }
+    }
+
+    fn convert_to_raw(&self) -> Result<()> {
+        let raw_key = match self.key_type {
+            SecretKeyType::Ed25519 => SignKeyCommand::read_from_file::<
+                noise::auth::Ed25519SecretKeyFormat,
+            >(&self.input, "secret key")?
+            .into_inner()
+            .to_bytes()
+            .to_vec(),
+            SecretKeyType::Noise => SignKeyCommand::read_from_file::<
+                noise::auth::StaticSecretKeyFormat,
+            >(&self.input, "noise static secret key")?
+            .into_inner(),
+        };
+
+        write_to_file(
+            secret_file_options(),
+            &self.output,
+            self.encoding.encode(&raw_key),
+            "raw secret key",
+        )
+    }
+}
+
+/// Command that prints fingerprints of the public keys in a public key file (authority or noise
+/// static key), a certificate or a server security bundle. Each fingerprint is labeled with the
+/// type of the key.
+#[derive(Debug, StructOpt)]
+struct FingerprintCommand {
+    /// Public key, certificate or security bundle file
+    #[structopt(parse(from_os_str))]
+    file: PathBuf,
+}
+
+impl FingerprintCommand {
+    fn execute(self) -> Result<()> {
+        for (label, fingerprint) in self.fingerprints()? {
+            println!("{}: {}", label, fingerprint);
+        }
+        Ok(())
+    }
+
+    /// Detects the type of the file by trying all supported formats in turn
+    fn fingerprints(&self) -> Result<Vec<(&'static str, String)>> {
+        use noise::auth::{EncodedEd25519PublicKey, EncodedStaticPublicKey, RawBytes};
+
+        let content = SignKeyCommand::read_from_file::<String>(&self.file, "input file")?;
+        let authority_fingerprint = |key: noise::auth::Ed25519PublicKeyFormat| {
+            (
+                "Ed25519 authority public key",
+                EncodedEd25519PublicKey::new(key.into_inner()).fingerprint(),
+            )
+        };
+        let noise_fingerprint = |key: StaticPublicKeyFormat| {
+            (
+                "Noise static public key",
+                EncodedStaticPublicKey::new(key.into_inner()).fingerprint(),
+            )
+        };
+
+        if let Ok(key) = noise::auth::Ed25519PublicKeyFormat::try_from(content.clone()) {
+            return Ok(vec![authority_fingerprint(key)]);
+        }
+        if let Ok(key) = StaticPublicKeyFormat::try_from(content.clone()) {
+            return Ok(vec![noise_fingerprint(key)]);
+        }
+        let certificate = match noise::auth::Certificate::try_from(content.clone()) {
+            Ok(certificate) => certificate,
+            Err(_) => {
+                ServerSecurityBundle::read_from_string(&content)
+                    .map_err(|_| {
+                        anyhow!(
+                            "Cannot parse {:?}, expected public key, certificate or security \
+                             bundle",
+                            self.file
+                        )
+                    })?
+                    .certificate
+            }
+        };
+        Ok(vec![
+            authority_fingerprint(certificate.authority_public_key),
+            noise_fingerprint(certificate.public_key),
+        ])
+    }
+}
+
+/// Command that prints decoded fields of a certificate or a server security bundle for
+/// diagnostics. The certificate is printed even if it's expired or otherwise invalid, the result
+/// of its validation is reported along with the fields. The secret key of a bundle isn't parsed.
+#[derive(Debug, StructOpt)]
+struct InspectCommand {
+    /// Certificate or security bundle file, `-` for stdin
+    #[structopt(parse(from_os_str))]
+    file: PathBuf,
+}
+
+impl InspectCommand {
+    fn execute(self) -> Result<()> {
+        for (label, value) in self.fields(SystemTime::now())? {
+            println!("{}: {}", label, value);
+        }
+        Ok(())
+    }
+
+    /// Decodes the certificate fields, `now` is used for reporting the remaining validity
+    fn fields(&self, now: SystemTime) -> Result<Vec<(&'static str, String)>> {
+        use noise::auth::{
+            Certificate, EncodedEd25519PublicKey, EncodedEd25519Signature, EncodedStaticPublicKey,
+        };
+
+        /// Only the certificate of a security bundle is parsed
+        #[derive(serde::Deserialize)]
+        struct BundleCertificate {
+            certificate: Certificate,
+        }
+
+        let content = SignKeyCommand::read_from_file::<String>(&self.file, "input file")?;
+        let (kind, certificate) = match Certificate::try_from(content.clone()) {
+            Ok(certificate) => ("certificate", certificate),
+            Err(_) => (
+                "server security bundle",
+                serde_json::from_str::<BundleCertificate>(&content)
+                    .map_err(|_| {
+                        anyhow!(
+                            "Cannot parse {:?}, expected certificate or security bundle",
+                            self.file
+                        )
+                    })?
+                    .certificate,
+            ),
+        };
+
+        let header = &certificate.signed_part_header;
+        let remaining_validity = match certificate.time_to_expiry(now) {
+            Some(remaining) => format_duration(remaining),
+            None => "expired".to_string(),
+        };
+        let status = match certificate.validate(|| now) {
+            Ok(_) => "valid".to_string(),
+            Err(e) => format!("invalid ({})", e),
+        };
+        Ok(vec![
+            ("Type", kind.to_string()),
+            ("Version", header.version.to_string()),
+            ("Valid from", format_unix_time(header.valid_from)),
+            ("Not valid after", format_unix_time(header.not_valid_after)),
+            ("Remaining validity", remaining_validity),
+            (
+                "Ed25519 authority public key",
+                EncodedEd25519PublicKey::new(certificate.authority_public_key.into_inner())
+                    .to_string(),
+            ),
+            (
+                "Noise static public key",
+                EncodedStaticPublicKey::new(certificate.public_key.into_inner()).to_string(),
+            ),
+            (
+                "Signature",
+                EncodedEd25519Signature::new(certificate.signature.into_inner()).to_string(),
+            ),
+            ("Status", status),
+        ])
+    }
+}
+
+/// Formats unix `timestamp` as RFC 3339 date and time in UTC along with the timestamp itself
+fn format_unix_time(timestamp: u32) -> String {
+    let date_time = chrono::DateTime::<chrono::Utc>::from(
+        SystemTime::UNIX_EPOCH + Duration::from_secs(timestamp.into()),
+    );
+    format!(
+        "{} ({})",
+        date_time.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+        timestamp
+    )
+}
+
+/// Formats `duration` as days, hours, minutes and seconds
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!(
+        "{}d {:02}h {:02}m {:02}s",
+        secs / 86400,
+        secs % 86400 / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+/// Options for creating files that contain secret keys. On unix, the files are only accessible
+/// by the owner so that the keys are not world-readable.
+fn secret_file_options() -> OpenOptions {
+    let mut options = OpenOptions::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+}
+
+/// Builds the full keypair from `secret_key` as Dalek crate requires it for signing
+fn authority_keypair(secret_key: ed25519_dalek::SecretKey) -> ed25519_dalek::Keypair {
+    ed25519_dalek::Keypair {
+        // Derive the public key from the secret key
+        public: (&secret_key).into(),
+        secret: secret_key,
+    }
+}
+
+/// Reads the signing key either from `file` or from environment variable `env_var` and builds the
+/// full keypair. Command line parsing ensures that exactly one of them is specified.
+fn read_signing_key(
+    file: Option<&PathBuf>,
+    env_var: Option<&str>,
+) -> Result<ed25519_dalek::Keypair> {
+    let secret_key = match (file, env_var) {
+        (_, Some(var)) => {
+            read_from_env::<noise::auth::EncodedEd25519SecretKey>(var, "signing key")?.into_inner()
+        }
+        (Some(file), None) => return SignKeyCommand::read_authority_keypair(file),
+        (None, None) => return Err(anyhow!("Missing signing key")),
+    };
+    Ok(authority_keypair(secret_key))
+}
+
+/// Helper that parses a key stored in environment variable `var` in its base58 encoding. This
+/// avoids storing the key on disk, e.g. in containerized environments.
+fn read_from_env<T>(var: &str, error_context_descr: &str) -> Result<T>
+where
+    T: TryFrom<String>,
+    <T as std::convert::TryFrom<std::string::String>>::Error: std::fmt::Display,
+{
+    let value = std::env::var(var).context(format!(
+        "Cannot read {} from environment variable {}",
+        error_context_descr, var
+    ))?;
+    T::try_from(value.trim().to_string()).map_err(|e| {
+        anyhow!(
+            "Cannot parse {} (environment variable {}) {}",
+            error_context_descr,
+            var,
+            e
+        )
+    })
+}
+
+/// Parses the start of certificate validity given either as RFC 3339 date and time or as unix
+/// timestamp
+fn parse_valid_from(value: &str) -> Result<SystemTime> {
+    if let Ok(timestamp) = value.parse::<u32>() {
+        return Ok(SystemTime::UNIX_EPOCH + Duration::from_secs(timestamp.into()));
+    }
+    chrono::DateTime::parse_from_rfc3339(value)
+        .map(SystemTime::from)
+        .context(format!(
+            "Invalid valid from: {}, expected RFC 3339 date and time or unix timestamp",
+            value
+        ))
+}
+
+/// How far in the past a certificate may start without `allow_backdating`, absorbs the delay
+/// between choosing the start time and running the command
+const BACKDATING_TOLERANCE: Duration = Duration::from_secs(5 * 60);
+
+/// Builds header of a certificate valid for `valid_for_days` starting at `valid_from` (now if not
+/// specified). A start that is in the past by more than `BACKDATING_TOLERANCE` is refused unless
+/// `allow_backdating` is set.
+fn signed_part_header(
+    valid_from: Option<SystemTime>,
+    valid_for_days: usize,
+    allow_backdating: bool,
+) -> Result<noise::auth::SignedPartHeader> {
+    let now = SystemTime::now();
+    let valid_from = valid_from.unwrap_or(now);
+    if !allow_backdating && valid_from + BACKDATING_TOLERANCE < now {
+        return Err(anyhow!(
+            "Certificate validity would start in the past, use --allow-backdating to permit it"
+        ));
+    }
+    noise::auth::SignedPartHeader::with_validity(
+        valid_from,
+        Duration::from_secs((valid_for_days * 24 * 60 * 60) as u64),
+    )
+    .map_err(|e| anyhow!("{:?}", e))
+}
+
+/// Appends `index` to the file name of `file_path` while keeping its extension, e.g.
+/// `server.key` becomes `server-0.key`
+fn indexed_path(file_path: &Path, index: usize) -> PathBuf {
+    let mut file_name = file_path.file_stem().unwrap_or_default().to_os_string();
+    file_name.push(format!("-{}", index));
+    if let Some(extension) = file_path.extension() {
+        file_name.push(".");
+        file_name.push(extension);
+    }
+    file_path.with_file_name(file_name)
+}
+
+/// Path that stands for stdin when reading and for stdout when writing
+const STDIO_PATH: &str = "-";
+
+fn is_stdio(file: &Path) -> bool {
+    file == Path::new(STDIO_PATH)
+}
+
+/// Stdin can only provide one of the inputs of a command
+fn check_single_stdin(files: &[Option<&PathBuf>]) -> Result<()> {
+    if files.iter().flatten().filter(|file| is_stdio(file)).count() > 1 {
+        return Err(anyhow!("Only one input can be read from stdin"));
+    }
+    Ok(())
 }
 
 /// Helper that opens a new file for writing or emits an error with specified context description
 /// if the file already exists. This is important to prevent overwriting already generated files.
-fn open_new_file(file: &PathBuf, descr: &str) -> Result<File> {
-    OpenOptions::new()
+fn open_new_file(mut options: OpenOptions, file: &PathBuf, descr: &str) -> Result<File> {
+    options
         .write(true)
         .create_new(true)
         .open(file)
@@ -356,8 +1056,10 @@ fn open_new_file(file: &PathBuf, descr: &str) -> Result<File> {
 }
 
 /// Helper that allows writing any String serializable type `payload` to be written into a
-/// specified path
+/// specified path. The file is created with `options`, see `secret_file_options`. The payload is
+/// written to stdout if the path is `-`.
 fn write_to_file<T: TryInto<String>>(
+    options: OpenOptions,
     file_path_buf: &PathBuf,
     payload: T,
     error_context_descr: &str,
@@ -366,7 +1068,12 @@ where
     T: TryInto<String>,
     <T as std::convert::TryInto<std::string::String>>::Error: std::fmt::Display,
 {
-    let mut file = open_new_file(file_path_buf, error_context_descr)?;
+    // There is nothing to protect from overwriting when writing to stdout
+    let mut file: Box<dyn Write> = if is_stdio(file_path_buf) {
+        Box::new(std::io::stdout())
+    } else {
+        Box::new(open_new_file(options, file_path_buf, error_context_descr)?)
+    };
 
     let serialized_str: String = payload.try_into().map_err(|e| {
         anyhow!(
@@ -382,6 +1089,85 @@ where
     Ok(())
 }
 
+/// Helper that replaces content of a specified path with any String serializable type `payload`.
+/// See `write_atomic_with` for details.
+fn write_atomic<T>(
+    options: OpenOptions,
+    file_path_buf: &PathBuf,
+    payload: T,
+    error_context_descr: &str,
+) -> Result<()>
+where
+    T: TryInto<String>,
+    <T as std::convert::TryInto<std::string::String>>::Error: std::fmt::Display,
+{
+    let serialized_str: String = payload.try_into().map_err(|e| {
+        anyhow!(
+            "Cannot serialize {} ({:?}) {}",
+            error_context_descr,
+            file_path_buf,
+            e
+        )
+    })?;
+
+    write_atomic_with(options, file_path_buf, error_context_descr, |file| {
+        file.write_all((serialized_str + "\n").as_bytes())
+            .map_err(Into::into)
+    })
+}
+
+/// Helper that atomically replaces a specified file (or creates it if it doesn't exist). The
+/// content is written by `write` into a temporary file in the same directory that is then renamed
+/// over the original file. The rename is atomic when both files are on the same filesystem,
+/// therefore readers see either the original or the complete new content. The original file is
+/// left untouched if `write` fails. The temporary file is created with `options` under a unique
+/// name and never reuses an existing file (or symlink), so that `options` (e.g. the mode of
+/// `secret_file_options`) always apply to the written content.
+fn write_atomic_with<F>(
+    mut options: OpenOptions,
+    file_path_buf: &PathBuf,
+    error_context_descr: &str,
+    write: F,
+) -> Result<()>
+where
+    F: FnOnce(&mut File) -> Result<()>,
+{
+    let file_name = file_path_buf
+        .file_name()
+        .ok_or_else(|| anyhow!("Invalid {} path ({:?})", error_context_descr, file_path_buf))?;
+    options.write(true).create_new(true);
+    let (mut file, tmp_file_path_buf) = loop {
+        let mut tmp_file_name = std::ffi::OsString::from(".");
+        tmp_file_name.push(file_name);
+        tmp_file_name.push(format!(".{:016x}.tmp", rand::random::<u64>()));
+        let tmp_file_path_buf = file_path_buf.with_file_name(tmp_file_name);
+        match options.open(&tmp_file_path_buf) {
+            Ok(file) => break (file, tmp_file_path_buf),
+            // Name collision, try again with another name
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => {
+                return Err(e).context(format!(
+                    "cannot create temporary file for {} ({:?})",
+                    error_context_descr, tmp_file_path_buf
+                ))
+            }
+        }
+    };
+
+    let result = write(&mut file)
+        .and_then(|_| file.sync_all().map_err(Into::into))
+        .and_then(|_| {
+            std::fs::rename(&tmp_file_path_buf, file_path_buf).context(format!(
+                "cannot replace {} ({:?})",
+                error_context_descr, file_path_buf
+            ))
+        });
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp_file_path_buf);
+    }
+    result
+}
+
 fn main() -> Result<()> {
     let command = Command::from_args();
 
@@ -389,6 +1175,665 @@ fn main() -> Result<()> {
         Command::GenCAKey(gen_key_cmd) => gen_key_cmd.execute(),
         Command::GenNoiseKey(gen_key_cmd) => gen_key_cmd.execute(),
         Command::SignKey(sign_key_cmd) => sign_key_cmd.execute(),
+        Command::SignBatch(sign_batch_cmd) => sign_batch_cmd.execute(),
         Command::SignBundle(sign_bundle_cmd) => sign_bundle_cmd.execute(),
+        Command::RenewBundle(renew_bundle_cmd) => renew_bundle_cmd.execute(),
+        Command::Convert(convert_cmd) => convert_cmd.execute(),
+        Command::Fingerprint(fingerprint_cmd) => fingerprint_cmd.execute(),
+        Command::Inspect(inspect_cmd) => inspect_cmd.execute(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sign_batch() {
+        let dir = tempfile::tempdir().expect("BUG: Cannot create temporary directory");
+        let signing_key = dir.path().join("ca-ed25519-secret.key");
+        let authority_keypair = ed25519_dalek::Keypair::generate(&mut rand::rngs::OsRng {});
+        write_to_file(
+            secret_file_options(),
+            &signing_key,
+            noise::auth::Ed25519SecretKeyFormat::new(authority_keypair.secret),
+            "secret key",
+        )
+        .expect("BUG: Cannot write signing key");
+
+        let keys_dir = dir.path().join("keys");
+        std::fs::create_dir(&keys_dir).expect("BUG: Cannot create directory");
+        for name in &["server1.key", "server2.key"] {
+            let keypair = noise::generate_keypair().expect("BUG: Cannot generate keypair");
+            write_to_file(
+                OpenOptions::new(),
+                &keys_dir.join(name),
+                StaticPublicKeyFormat::new(keypair.public),
+                "public key",
+            )
+            .expect("BUG: Cannot write public key");
+        }
+        let keypair = noise::generate_keypair().expect("BUG: Cannot generate keypair");
+        write_to_file(
+            secret_file_options(),
+            &keys_dir.join("server-secret.key"),
+            noise::auth::StaticSecretKeyFormat::new(keypair.private),
+            "secret key",
+        )
+        .expect("BUG: Cannot write secret key");
+
+        let command = SignBatchCommand {
+            public_keys_to_sign: vec![keys_dir.clone()],
+            signing_key: Some(signing_key.clone()),
+            signing_key_env: None,
+            valid_for_days: 1,
+            valid_from: None,
+            allow_backdating: false,
+            overwrite: false,
+        };
+        command.execute().expect("BUG: Cannot sign batch");
+
+        for name in &["server1.cert", "server2.cert"] {
+            let certificate = noise::auth::Certificate::read_from_file(&keys_dir.join(name))
+                .expect("BUG: Cannot read certificate");
+            assert_eq!(
+                certificate.authority_public_key.into_inner(),
+                authority_keypair.public
+            );
+        }
+        // Secret keys in the directory are not signed
+        assert!(!keys_dir.join("server-secret.cert").exists());
+
+        // Existing certificates are only replaced when requested
+        let command = SignBatchCommand {
+            public_keys_to_sign: vec![keys_dir.clone()],
+            signing_key: Some(signing_key.clone()),
+            signing_key_env: None,
+            valid_for_days: 1,
+            valid_from: None,
+            allow_backdating: false,
+            overwrite: false,
+        };
+        command
+            .execute()
+            .expect_err("BUG: Existing certificates overwritten");
+        let command = SignBatchCommand {
+            public_keys_to_sign: vec![keys_dir],
+            signing_key: Some(signing_key),
+            signing_key_env: None,
+            valid_for_days: 1,
+            valid_from: None,
+            allow_backdating: false,
+            overwrite: true,
+        };
+        command
+            .execute()
+            .expect("BUG: Cannot overwrite certificates");
+    }
+
+    #[test]
+    fn renew_bundle_with_wrong_signing_key() {
+        let dir = tempfile::tempdir().expect("BUG: Cannot create temporary directory");
+        let path = |name: &str| dir.path().join(name);
+        for (public_key_file, secret_key_file) in &[
+            ("ca-public.key", "ca-secret.key"),
+            ("other-public.key", "other-secret.key"),
+        ] {
+            GenCAKeyCommand {
+                public_key_file: path(public_key_file),
+                secret_key_file: path(secret_key_file),
+            }
+            .execute()
+            .expect("BUG: Cannot generate CA keypair");
+        }
+        GenNoiseKeyCommand {
+            public_key_file: path("noise-public.key"),
+            secret_key_file: path("noise-secret.key"),
+            count: None,
+        }
+        .execute()
+        .expect("BUG: Cannot generate noise keypair");
+        SignBundleCommand {
+            secret_key_to_sign: Some(path("noise-secret.key")),
+            secret_key_to_sign_env: None,
+            bundle_file: None,
+            signing_key: Some(path("ca-secret.key")),
+            signing_key_env: None,
+            valid_for_days: 1,
+            valid_from: None,
+            allow_backdating: false,
+        }
+        .execute()
+        .expect("BUG: Cannot sign bundle");
+        let original_bundle =
+            std::fs::read_to_string(path("noise-secret.cert")).expect("BUG: Cannot read bundle");
+
+        let err = RenewBundleCommand {
+            bundle: path("noise-secret.cert"),
+            signing_key: Some(path("other-secret.key")),
+            signing_key_env: None,
+            valid_for_days: 2,
+            valid_from: None,
+            allow_backdating: false,
+        }
+        .execute()
+        .expect_err("BUG: Bundle renewed with a wrong signing key");
+        assert!(
+            format!("{:#}", err)
+                .contains("Signing key doesn't belong to the certificate authority"),
+            "BUG: Unclear error: {:#}",
+            err
+        );
+        assert_eq!(
+            std::fs::read_to_string(path("noise-secret.cert")).expect("BUG: Cannot read bundle"),
+            original_bundle
+        );
+
+        RenewBundleCommand {
+            bundle: path("noise-secret.cert"),
+            signing_key: Some(path("ca-secret.key")),
+            signing_key_env: None,
+            valid_for_days: 2,
+            valid_from: None,
+            allow_backdating: false,
+        }
+        .execute()
+        .expect("BUG: Cannot renew bundle");
+
+        // The signing key can be passed in an environment variable, too
+        let signing_key = SignKeyCommand::read_from_file::<noise::auth::Ed25519SecretKeyFormat>(
+            &path("ca-secret.key"),
+            "signing key",
+        )
+        .expect("BUG: Cannot read signing key");
+        std::env::set_var(
+            "KEYTOOL_TEST_RENEW_SIGNING_KEY",
+            String::from(noise::auth::EncodedEd25519SecretKey::new(
+                signing_key.into_inner(),
+            )),
+        );
+        RenewBundleCommand {
+            bundle: path("noise-secret.cert"),
+            signing_key: None,
+            signing_key_env: Some("KEYTOOL_TEST_RENEW_SIGNING_KEY".to_string()),
+            valid_for_days: 2,
+            valid_from: None,
+            allow_backdating: false,
+        }
+        .execute()
+        .expect("BUG: Cannot renew bundle with signing key from environment");
+
+        // The renewed certificate can start later, starting in the past has to be allowed
+        let renew = |valid_from, allow_backdating| RenewBundleCommand {
+            bundle: path("noise-secret.cert"),
+            signing_key: Some(path("ca-secret.key")),
+            signing_key_env: None,
+            valid_for_days: 2,
+            valid_from: Some(valid_from),
+            allow_backdating,
+        };
+        let valid_from = SystemTime::UNIX_EPOCH + Duration::from_secs(4_000_000_000);
+        renew(valid_from, false)
+            .execute()
+            .expect("BUG: Cannot renew bundle with later start");
+        let bundle = ServerSecurityBundle::read_from_string(
+            &std::fs::read_to_string(path("noise-secret.cert")).expect("BUG: Cannot read bundle"),
+        )
+        .expect("BUG: Cannot parse bundle");
+        assert_eq!(
+            bundle.certificate.signed_part_header.valid_from(),
+            valid_from
+        );
+        let yesterday = SystemTime::now() - Duration::from_secs(24 * 60 * 60);
+        renew(yesterday, false)
+            .execute()
+            .expect_err("BUG: Bundle backdated without permission");
+        renew(yesterday, true)
+            .execute()
+            .expect("BUG: Cannot renew backdated bundle");
+    }
+
+    #[test]
+    fn write_atomic_keeps_original_on_failure() {
+        let dir = tempfile::tempdir().expect("BUG: Cannot create temporary directory");
+        let file_path_buf = dir.path().join("server.cert");
+        std::fs::write(&file_path_buf, "original").expect("BUG: Cannot write file");
+
+        write_atomic_with(OpenOptions::new(), &file_path_buf, "test file", |file| {
+            file.write_all(b"partial")?;
+            Err(anyhow!("Simulated failure"))
+        })
+        .expect_err("BUG: Failed write reported as success");
+        assert_eq!(
+            std::fs::read_to_string(&file_path_buf).expect("BUG: Cannot read file"),
+            "original"
+        );
+        // No temporary file is left behind
+        assert_eq!(
+            std::fs::read_dir(dir.path())
+                .expect("BUG: Cannot read directory")
+                .count(),
+            1
+        );
+
+        // A file planted at the former fixed temporary path is neither reused nor replaced
+        let planted_path_buf = dir.path().join(".server.cert.tmp");
+        std::fs::write(&planted_path_buf, "planted").expect("BUG: Cannot write file");
+
+        write_atomic(
+            OpenOptions::new(),
+            &file_path_buf,
+            "replaced".to_string(),
+            "test file",
+        )
+        .expect("BUG: Cannot replace file");
+        assert_eq!(
+            std::fs::read_to_string(&file_path_buf).expect("BUG: Cannot read file"),
+            "replaced\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(&planted_path_buf).expect("BUG: Cannot read file"),
+            "planted"
+        );
+    }
+
+    #[test]
+    fn stdio_paths() {
+        let dir = tempfile::tempdir().expect("BUG: Cannot create temporary directory");
+        let file_path_buf = dir.path().join("server.cert");
+        std::fs::write(&file_path_buf, "original").expect("BUG: Cannot write file");
+
+        // Real files are still protected from overwriting
+        write_to_file(
+            OpenOptions::new(),
+            &file_path_buf,
+            "replaced".to_string(),
+            "test file",
+        )
+        .expect_err("BUG: Existing file overwritten");
+
+        let stdin = PathBuf::from("-");
+        assert!(is_stdio(&stdin));
+        check_single_stdin(&[Some(&stdin), Some(&file_path_buf)])
+            .expect("BUG: Single stdin input refused");
+        check_single_stdin(&[Some(&stdin), None]).expect("BUG: Single stdin input refused");
+        check_single_stdin(&[Some(&stdin), Some(&stdin)])
+            .expect_err("BUG: Multiple stdin inputs accepted");
+        assert!(!is_stdio(&dir.path().join("-")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn secret_keys_are_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().expect("BUG: Cannot create temporary directory");
+        let file_mode = |name: &str| {
+            std::fs::metadata(dir.path().join(name))
+                .expect("BUG: Cannot read file metadata")
+                .permissions()
+                .mode()
+                & 0o777
+        };
+
+        GenCAKeyCommand {
+            public_key_file: dir.path().join("ca-public.key"),
+            secret_key_file: dir.path().join("ca-secret.key"),
+        }
+        .execute()
+        .expect("BUG: Cannot generate CA keypair");
+        GenNoiseKeyCommand {
+            public_key_file: dir.path().join("noise-public.key"),
+            secret_key_file: dir.path().join("noise-secret.key"),
+            count: None,
+        }
+        .execute()
+        .expect("BUG: Cannot generate noise keypair");
+        SignBundleCommand {
+            secret_key_to_sign: Some(dir.path().join("noise-secret.key")),
+            secret_key_to_sign_env: None,
+            bundle_file: None,
+            signing_key: Some(dir.path().join("ca-secret.key")),
+            signing_key_env: None,
+            valid_for_days: 1,
+            valid_from: None,
+            allow_backdating: false,
+        }
+        .execute()
+        .expect("BUG: Cannot sign bundle");
+
+        assert_eq!(file_mode("ca-secret.key"), 0o600);
+        assert_eq!(file_mode("noise-secret.key"), 0o600);
+        assert_eq!(file_mode("noise-secret.cert"), 0o600);
+    }
+
+    #[test]
+    fn convert_raw_secret_key() {
+        const RAW_KEY_HEX: &str =
+            "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60";
+        let dir = tempfile::tempdir().expect("BUG: Cannot create temporary directory");
+        std::fs::write(dir.path().join("raw.key"), format!("{}\n", RAW_KEY_HEX))
+            .expect("BUG: Cannot write raw key");
+
+        ConvertCommand {
+            input: dir.path().join("raw.key"),
+            output: dir.path().join("secret.key"),
+            key_type: SecretKeyType::Ed25519,
+            encoding: noise::auth::RawKeyEncoding::Hex,
+            to_raw: false,
+        }
+        .execute()
+        .expect("BUG: Cannot convert raw key");
+        let secret_key = SignKeyCommand::read_from_file::<noise::auth::Ed25519SecretKeyFormat>(
+            &dir.path().join("secret.key"),
+            "secret key",
+        )
+        .expect("BUG: Cannot read converted key");
+        assert_eq!(hex::encode(secret_key.into_inner().to_bytes()), RAW_KEY_HEX);
+
+        ConvertCommand {
+            input: dir.path().join("secret.key"),
+            output: dir.path().join("raw-again.key"),
+            key_type: SecretKeyType::Ed25519,
+            encoding: noise::auth::RawKeyEncoding::Hex,
+            to_raw: true,
+        }
+        .execute()
+        .expect("BUG: Cannot convert key to raw");
+        let raw_key = std::fs::read_to_string(dir.path().join("raw-again.key"))
+            .expect("BUG: Cannot read raw key");
+        assert_eq!(raw_key.trim(), RAW_KEY_HEX);
+    }
+
+    #[test]
+    fn sign_bundle_with_keys_from_env() {
+        let dir = tempfile::tempdir().expect("BUG: Cannot create temporary directory");
+        let authority_keypair = ed25519_dalek::Keypair::generate(&mut rand::rngs::OsRng {});
+        let keypair = noise::generate_keypair().expect("BUG: Cannot generate keypair");
+        std::env::set_var(
+            "KEYTOOL_TEST_SIGNING_KEY",
+            String::from(noise::auth::EncodedEd25519SecretKey::new(
+                authority_keypair.secret,
+            )),
+        );
+        std::env::set_var(
+            "KEYTOOL_TEST_SECRET_KEY_TO_SIGN",
+            String::from(noise::auth::EncodedStaticSecretKey::new(keypair.private)),
+        );
+
+        let bundle_file = dir.path().join("bundle.cert");
+        let command = Command::from_iter_safe(&[
+            "ii-stratum-keytool",
+            "sign-bundle",
+            "--secret-key-to-sign-env",
+            "KEYTOOL_TEST_SECRET_KEY_TO_SIGN",
+            "--signing-key-env",
+            "KEYTOOL_TEST_SIGNING_KEY",
+            "--bundle-file",
+            bundle_file.to_str().expect("BUG: Invalid path"),
+        ])
+        .expect("BUG: Cannot parse command line");
+        match command {
+            Command::SignBundle(command) => command.execute().expect("BUG: Cannot sign bundle"),
+            _ => panic!("BUG: Unexpected command {:?}", command),
+        }
+
+        let bundle = ServerSecurityBundle::read_from_string(
+            &std::fs::read_to_string(&bundle_file).expect("BUG: Cannot read bundle"),
+        )
+        .expect("BUG: Cannot parse bundle");
+        assert_eq!(
+            bundle.certificate.authority_public_key.into_inner(),
+            authority_keypair.public
+        );
+        assert_eq!(bundle.certificate.public_key.into_inner(), keypair.public);
+
+        // The file and the environment variable cannot be combined
+        Command::from_iter_safe(&[
+            "ii-stratum-keytool",
+            "sign-key",
+            "--public-key-to-sign",
+            "server.key",
+            "--signing-key",
+            "ca-secret.key",
+            "--signing-key-env",
+            "KEYTOOL_TEST_SIGNING_KEY",
+        ])
+        .expect_err("BUG: Both signing key options accepted");
+    }
+
+    #[test]
+    fn fingerprint() {
+        use noise::auth::{EncodedEd25519PublicKey, EncodedStaticPublicKey, RawBytes};
+
+        let dir = tempfile::tempdir().expect("BUG: Cannot create temporary directory");
+        let authority_keypair = ed25519_dalek::Keypair::generate(&mut rand::rngs::OsRng {});
+        let keypair = noise::generate_keypair().expect("BUG: Cannot generate keypair");
+        let authority_fingerprint = (
+            "Ed25519 authority public key",
+            EncodedEd25519PublicKey::new(authority_keypair.public).fingerprint(),
+        );
+        let noise_fingerprint = (
+            "Noise static public key",
+            EncodedStaticPublicKey::new(keypair.public.clone()).fingerprint(),
+        );
+
+        let authority_public_key = dir.path().join("ca-ed25519-public.key");
+        write_to_file(
+            OpenOptions::new(),
+            &authority_public_key,
+            noise::auth::Ed25519PublicKeyFormat::new(authority_keypair.public),
+            "public key",
+        )
+        .expect("BUG: Cannot write public key");
+        let public_key = dir.path().join("server-noise-static-public.key");
+        write_to_file(
+            OpenOptions::new(),
+            &public_key,
+            StaticPublicKeyFormat::new(keypair.public),
+            "noise static public key",
+        )
+        .expect("BUG: Cannot write public key");
+        let signing_key = dir.path().join("ca-ed25519-secret.key");
+        write_to_file(
+            secret_file_options(),
+            &signing_key,
+            noise::auth::Ed25519SecretKeyFormat::new(authority_keypair.secret),
+            "secret key",
+        )
+        .expect("BUG: Cannot write signing key");
+        let secret_key = dir.path().join("server-noise-static-secret.key");
+        write_to_file(
+            secret_file_options(),
+            &secret_key,
+            noise::auth::StaticSecretKeyFormat::new(keypair.private),
+            "noise static secret key",
+        )
+        .expect("BUG: Cannot write secret key");
+        let bundle = dir.path().join("bundle.cert");
+        SignBundleCommand::from_iter_safe(&[
+            "sign-bundle",
+            "--secret-key-to-sign",
+            secret_key.to_str().expect("BUG: Invalid path"),
+            "--signing-key",
+            signing_key.to_str().expect("BUG: Invalid path"),
+            "--bundle-file",
+            bundle.to_str().expect("BUG: Invalid path"),
+        ])
+        .expect("BUG: Cannot parse command line")
+        .execute()
+        .expect("BUG: Cannot sign bundle");
+
+        let fingerprints = |file: &PathBuf| {
+            let command = Command::from_iter_safe(&[
+                "ii-stratum-keytool",
+                "fingerprint",
+                file.to_str().expect("BUG: Invalid path"),
+            ])
+            .expect("BUG: Cannot parse command line");
+            match command {
+                Command::Fingerprint(command) => command.fingerprints(),
+                _ => panic!("BUG: Unexpected command {:?}", command),
+            }
+        };
+        assert_eq!(
+            vec![authority_fingerprint.clone()],
+            fingerprints(&authority_public_key).expect("BUG: Cannot fingerprint public key")
+        );
+        assert_eq!(
+            vec![noise_fingerprint.clone()],
+            fingerprints(&public_key).expect("BUG: Cannot fingerprint public key")
+        );
+        assert_eq!(
+            vec![authority_fingerprint, noise_fingerprint],
+            fingerprints(&bundle).expect("BUG: Cannot fingerprint bundle")
+        );
+        fingerprints(&signing_key).expect_err("BUG: Secret key fingerprinted");
+    }
+
+    #[test]
+    fn inspect() {
+        let dir = tempfile::tempdir().expect("BUG: Cannot create temporary directory");
+        let authority_keypair = ed25519_dalek::Keypair::generate(&mut rand::rngs::OsRng {});
+        let keypair = noise::generate_keypair().expect("BUG: Cannot generate keypair");
+        let signed_part = noise::auth::SignedPart::new(
+            noise::auth::SignedPartHeader::new(1612897727, 1612954827),
+            keypair.public.clone(),
+            authority_keypair.public,
+        );
+        let signature = signed_part
+            .sign_with(&authority_keypair)
+            .expect("BUG: Cannot sign certificate");
+        let certificate = noise::auth::Certificate::new(signed_part, signature);
+        let bundle = ServerSecurityBundle::new(
+            certificate.clone(),
+            noise::auth::StaticSecretKeyFormat::new(keypair.private),
+        )
+        .expect("BUG: Cannot create bundle");
+
+        let certificate_file = dir.path().join("server.cert");
+        write_to_file(
+            OpenOptions::new(),
+            &certificate_file,
+            certificate,
+            "certificate",
+        )
+        .expect("BUG: Cannot write certificate");
+        let bundle_file = dir.path().join("server.bundle");
+        write_to_file(
+            secret_file_options(),
+            &bundle_file,
+            serde_json::to_string_pretty(&bundle).expect("BUG: Cannot serialize bundle"),
+            "security bundle",
+        )
+        .expect("BUG: Cannot write bundle");
+
+        let inspect = |file: &PathBuf, now: u64| {
+            InspectCommand { file: file.clone() }
+                .fields(SystemTime::UNIX_EPOCH + Duration::from_secs(now))
+                .expect("BUG: Cannot inspect certificate")
+        };
+        let fields = inspect(&certificate_file, 1612897727 + 86400 / 2);
+        let field = |label: &str| {
+            fields
+                .iter()
+                .find(|(field_label, _)| *field_label == label)
+                .map(|(_, value)| value.clone())
+                .expect("BUG: Missing field")
+        };
+        assert_eq!(field("Type"), "certificate");
+        assert_eq!(field("Valid from"), "2021-02-09T19:08:47Z (1612897727)");
+        assert_eq!(
+            field("Not valid after"),
+            "2021-02-10T11:00:27Z (1612954827)"
+        );
+        assert_eq!(field("Remaining validity"), "0d 03h 51m 40s");
+        assert_eq!(
+            field("Noise static public key"),
+            noise::auth::EncodedStaticPublicKey::new(keypair.public).to_string()
+        );
+        assert_eq!(field("Status"), "valid");
+
+        // Expired certificate in a bundle is still printed
+        let fields = inspect(&bundle_file, 1612954827 + 1);
+        assert_eq!(fields[0], ("Type", "server security bundle".to_string()));
+        assert!(fields.contains(&("Remaining validity", "expired".to_string())));
+        assert!(fields
+            .iter()
+            .any(|(label, value)| *label == "Status" && value.starts_with("invalid")));
+    }
+
+    #[test]
+    fn valid_from() {
+        assert_eq!(
+            parse_valid_from("1612897727").expect("BUG: Cannot parse timestamp"),
+            SystemTime::UNIX_EPOCH + Duration::from_secs(1612897727)
+        );
+        assert_eq!(
+            parse_valid_from("2021-02-09T20:08:47+01:00").expect("BUG: Cannot parse RFC 3339"),
+            SystemTime::UNIX_EPOCH + Duration::from_secs(1612897727)
+        );
+        assert!(parse_valid_from("yesterday").is_err());
+
+        let now = SystemTime::now();
+        let tomorrow = now + Duration::from_secs(24 * 60 * 60);
+        let header =
+            signed_part_header(Some(tomorrow), 2, false).expect("BUG: Cannot build header");
+        assert!(header.verify_expiration(now).is_err());
+        assert!(header.verify_expiration(tomorrow).is_ok());
+
+        // Backdating needs to be enabled explicitly
+        let yesterday = now - Duration::from_secs(24 * 60 * 60);
+        assert!(signed_part_header(Some(yesterday), 2, false).is_err());
+        let header =
+            signed_part_header(Some(yesterday), 2, true).expect("BUG: Cannot build header");
+        assert!(header.verify_expiration(now).is_ok());
+    }
+
+    #[test]
+    fn gen_noise_key_count() {
+        let dir = tempfile::tempdir().expect("BUG: Cannot create temporary directory");
+        let path = |name: &str| dir.path().join(name);
+        assert_eq!(
+            indexed_path(&path("server-public.key"), 1),
+            path("server-public-1.key")
+        );
+        assert_eq!(indexed_path(&path("server"), 0), path("server-0"));
+
+        let command = |count| GenNoiseKeyCommand {
+            public_key_file: path("server-public.key"),
+            secret_key_file: path("server-secret.key"),
+            count,
+        };
+        command(Some(3))
+            .execute()
+            .expect("BUG: Cannot generate noise keypairs");
+        let mut public_keys = Vec::new();
+        for index in 0..3 {
+            let public_key = SignKeyCommand::read_from_file::<noise::auth::StaticPublicKeyFormat>(
+                &path(&format!("server-public-{}.key", index)),
+                "noise static public key",
+            )
+            .expect("BUG: Cannot read public key")
+            .into_inner();
+            SignKeyCommand::read_from_file::<noise::auth::StaticSecretKeyFormat>(
+                &path(&format!("server-secret-{}.key", index)),
+                "noise static secret key",
+            )
+            .expect("BUG: Cannot read secret key");
+            assert!(!public_keys.contains(&public_key));
+            public_keys.push(public_key);
+        }
+        command(Some(0))
+            .execute()
+            .expect_err("BUG: Zero keypairs generated");
+
+        // A single existing file fails the whole batch before anything is written
+        std::fs::remove_file(path("server-public-0.key")).expect("BUG: Cannot remove file");
+        command(Some(4))
+            .execute()
+            .expect_err("BUG: Existing files overwritten");
+        assert!(!path("server-public-0.key").exists());
+        assert!(!path("server-public-3.key").exists());
     }
 }
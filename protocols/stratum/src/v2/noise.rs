@@ -40,6 +40,7 @@ pub use codec::{Codec, CompoundCodec};
 
 pub mod auth;
 mod handshake;
+pub use handshake::HandshakeTimeout;
 
 #[macro_use]
 pub mod negotiation;
@@ -85,6 +86,22 @@ pub fn generate_keypair() -> Result<StaticKeypair> {
     let builder = NoiseParamsBuilder::new(EncryptionAlgorithm::AESGCM).get_builder();
     builder.generate_keypair().map_err(Into::into)
 }
+
+/// Derives the static public key from the static `secret` key. Snow doesn't provide this
+/// functionality, therefore the derivation is done directly by the Curve25519 implementation
+/// that matches the DH function of our noise params (see `NoiseParamsBuilder`).
+pub fn public_key_from_secret(secret: &StaticSecretKey) -> Result<StaticPublicKey> {
+    let mut raw_secret_key = [0_u8; 32];
+    if secret.len() != raw_secret_key.len() {
+        return Err(Error::Noise(format!(
+            "Invalid static secret key length: {}, expected: {}",
+            secret.len(),
+            raw_secret_key.len()
+        )));
+    }
+    raw_secret_key.copy_from_slice(&secret[..]);
+    Ok(x25519_dalek::x25519(raw_secret_key, x25519_dalek::X25519_BASEPOINT_BYTES).to_vec())
+}
 #[derive(Debug)]
 pub struct Initiator {
     stage: usize,
@@ -94,6 +111,7 @@ pub struct Initiator {
     /// the SignatureNoiseMessage and of the static public key of the `Responder` and will verify
     /// the authenticity of the static public key of the Responder
     authority_public_key: ed25519_dalek::PublicKey,
+    handshake_timeout: HandshakeTimeout,
 }
 
 impl Initiator {
@@ -106,9 +124,16 @@ impl Initiator {
             handshake_state: None,
             algorithms,
             authority_public_key,
+            handshake_timeout: HandshakeTimeout::default(),
         }
     }
 
+    /// Overrides the default time limits of the handshake
+    pub fn with_handshake_timeout(mut self, handshake_timeout: HandshakeTimeout) -> Self {
+        self.handshake_timeout = handshake_timeout;
+        self
+    }
+
     pub async fn connect(self, connection: TcpStream) -> Result<v2::Framed> {
         self.connect_with_codec(connection, |noise_codec| {
             <v2::framing::Framing as ii_wire::Framing>::Codec::new(Some(noise_codec))
@@ -207,6 +232,10 @@ impl handshake::Step for Initiator {
             .expect("BUG: into_handshake_state shouldn't be called before negotiation")
     }
 
+    fn handshake_timeout(&self) -> HandshakeTimeout {
+        self.handshake_timeout
+    }
+
     fn step(
         &mut self,
         in_msg: Option<handshake::Message>,
@@ -299,6 +328,7 @@ pub struct Responder<'a> {
     /// Serialized signature noise message that can be directly provided as part of the
     /// handshake - see `step()`
     signature_noise_message: Bytes,
+    handshake_timeout: HandshakeTimeout,
 }
 
 impl<'a> Responder<'a> {
@@ -313,9 +343,16 @@ impl<'a> Responder<'a> {
             algorithms,
             handshake_state: None,
             signature_noise_message,
+            handshake_timeout: HandshakeTimeout::default(),
         }
     }
 
+    /// Overrides the default time limits of the handshake
+    pub fn with_handshake_timeout(mut self, handshake_timeout: HandshakeTimeout) -> Self {
+        self.handshake_timeout = handshake_timeout;
+        self
+    }
+
     /// Executes noise protocol handshake on provided connection
     pub async fn accept(self, connection: TcpStream) -> Result<v2::Framed> {
         self.accept_with_codec(connection, |noise_codec| {
@@ -397,6 +434,10 @@ impl<'a> handshake::Step for Responder<'a> {
             .expect("BUG: into_handshake_state shouldn't be called before negotiation")
     }
 
+    fn handshake_timeout(&self) -> HandshakeTimeout {
+        self.handshake_timeout
+    }
+
     fn step(
         &mut self,
         in_msg: Option<handshake::Message>,
@@ -564,6 +605,8 @@ pub(crate) mod test {
     use handshake::Step as _;
     use std::net::Ipv4Addr;
     use std::sync::Arc;
+    use std::time::{Duration, Instant};
+    use tokio::io::AsyncReadExt;
     use tokio::net::{TcpListener, TcpStream};
     use tokio::sync::Notify;
 
@@ -698,6 +741,17 @@ pub(crate) mod test {
         assert_eq!(&message[..], &decrypted_msg, "Messages don't match");
     }
 
+    #[test]
+    fn test_public_key_from_secret() {
+        let keypair = generate_keypair().expect("BUG: Failed to generate keypair");
+        let public_key =
+            public_key_from_secret(&keypair.private).expect("BUG: Failed to derive public key");
+        assert_eq!(keypair.public, public_key);
+
+        public_key_from_secret(&keypair.private[1..].to_vec())
+            .expect_err("BUG: Public key derived from a short secret key");
+    }
+
     /// Legacy version of the initiator. Useful for testing that handshake still works even with
     /// legacy clients.
     #[derive(Debug)]
@@ -968,6 +1022,60 @@ pub(crate) mod test {
         jh1.await.expect("BUG: Initiator failed");
     }
 
+    #[tokio::test]
+    async fn handshake_timeout_stalling_peer() {
+        let (signature_noise_message, _, static_keypair) =
+            build_deterministic_serialized_signature_noise_message_and_keypairs();
+        let tcp_listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .await
+            .expect("BUG: Failed to bind the test server");
+        let addr = tcp_listener
+            .local_addr()
+            .expect("BUG: Failed to get local address");
+        // The peer connects but never sends anything
+        let mut stalling_peer = TcpStream::connect(addr)
+            .await
+            .expect("BUG: Failed to connect to the test server");
+        let (downstream, _) = tcp_listener
+            .accept()
+            .await
+            .expect("BUG: Failed to accept tcp connection");
+
+        let timeout = HandshakeTimeout {
+            message: Duration::from_secs(5),
+            total: Duration::from_millis(100),
+        };
+        let responder = Responder::new(
+            &static_keypair,
+            signature_noise_message,
+            vec![EncryptionAlgorithm::ChaChaPoly, EncryptionAlgorithm::AESGCM],
+        )
+        .with_handshake_timeout(timeout);
+
+        let started = Instant::now();
+        let result = responder.accept(downstream).await;
+        assert!(
+            started.elapsed() < Duration::from_secs(1),
+            "BUG: Handshake timeout not respected"
+        );
+        match result {
+            Err(Error::NoiseHandshakeTimeout(duration)) => assert_eq!(duration, timeout.total),
+            Err(e) => panic!("BUG: Unexpected error: {}", e),
+            Ok(_) => panic!("BUG: Handshake with stalling peer succeeded"),
+        }
+
+        // The connection has been closed by the responder
+        let mut buf = [0_u8; 16];
+        let read = stalling_peer
+            .read(&mut buf)
+            .await
+            .expect("BUG: Failed to read from closed connection");
+        assert_eq!(
+            read, 0,
+            "BUG: Connection not closed after handshake timeout"
+        );
+    }
+
     /// This is usefull if the counterparts are run on separate machines.
     #[tokio::test]
     #[ignore]
@@ -34,6 +34,23 @@ use crate::v2::{self, noise::StaticPublicKey};
 mod formats;
 pub use formats::*;
 
+/// Source of the current time used for certificate validation. Devices with unreliable clocks
+/// (e.g. embedded miners without RTC) can provide an implementation backed by a trusted time
+/// source (NTP etc.) instead of relying on the local system clock.
+pub trait TimeSource {
+    fn now(&self) -> SystemTime;
+}
+
+/// Default `TimeSource` that uses the local system clock
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl TimeSource for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
 /// Header of the `SignedPart` that will also be part of the `Certificate`
 #[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
 pub struct SignedPartHeader {
@@ -46,6 +63,10 @@ pub struct SignedPartHeader {
 
 impl SignedPartHeader {
     const VERSION: u16 = 0;
+    /// Certificates that become valid further in the future than this are reported as a
+    /// possible clock skew rather than just not yet valid. This only affects the error, a
+    /// certificate that isn't valid yet is refused either way.
+    pub const SKEW_HINT_THRESHOLD: Duration = Duration::from_secs(5 * 60);
 
     pub fn new(valid_from: u32, not_valid_after: u32) -> Self {
         Self {
@@ -56,7 +77,12 @@ impl SignedPartHeader {
     }
 
     pub fn with_duration(valid_for: Duration) -> Result<Self> {
-        let valid_from = SystemTime::now();
+        Self::with_validity(SystemTime::now(), valid_for)
+    }
+
+    /// Same as `with_duration` but the validity starts at `valid_from` instead of now, e.g. for
+    /// certificates that are generated ahead of deployment
+    pub fn with_validity(valid_from: SystemTime, valid_for: Duration) -> Result<Self> {
         let not_valid_after = valid_from + valid_for;
         Ok(Self::new(
             Self::system_time_to_unix_time_u32(&valid_from)?,
@@ -74,15 +100,64 @@ impl SignedPartHeader {
             .expect("BUG: cannot provide 'not_valid_after' time")
     }
 
+    /// Refuses header versions other than the one this implementation understands so that a
+    /// certificate in a future format is never validated under the current format assumptions
+    pub fn verify_version(&self) -> Result<()> {
+        if self.version != Self::VERSION {
+            return Err(Error::UnsupportedCertificateVersion {
+                version: self.version,
+                supported: Self::VERSION,
+            });
+        }
+        Ok(())
+    }
+
     pub fn verify_expiration(&self, now: SystemTime) -> Result<SystemTime> {
+        self.verify_expiration_with_skew_hint(now, Self::SKEW_HINT_THRESHOLD)
+    }
+
+    /// Same as `verify_expiration` but the error for a certificate that becomes valid more than
+    /// `skew_hint_threshold` after `now` hints at a clock skew
+    pub fn verify_expiration_with_skew_hint(
+        &self,
+        now: SystemTime,
+        skew_hint_threshold: Duration,
+    ) -> Result<SystemTime> {
+        self.verify_validity_period(now, Duration::from_secs(0), skew_hint_threshold)
+    }
+
+    /// Same as `verify_expiration` but both bounds of the validity period are widened by
+    /// `tolerance` so that minor clock differences between the peers are tolerated
+    pub fn verify_expiration_with_tolerance(
+        &self,
+        now: SystemTime,
+        tolerance: Duration,
+    ) -> Result<SystemTime> {
+        self.verify_validity_period(now, tolerance, Self::SKEW_HINT_THRESHOLD)
+    }
+
+    fn verify_validity_period(
+        &self,
+        now: SystemTime,
+        tolerance: Duration,
+        skew_hint_threshold: Duration,
+    ) -> Result<SystemTime> {
         let now_timestamp = Self::system_time_to_unix_time_u32(&now)?;
-        if now_timestamp < self.valid_from {
+        let tolerance = tolerance.as_secs();
+        if u64::from(now_timestamp) + tolerance < u64::from(self.valid_from) {
+            let ahead = Duration::from_secs(u64::from(self.valid_from - now_timestamp));
+            if ahead > skew_hint_threshold {
+                return Err(Error::Noise(format!(
+                    "Certificate valid from: {:?} is {:?} ahead of now: {:?}, possible clock skew",
+                    self.valid_from, ahead, now
+                )));
+            }
             return Err(Error::Noise(format!(
                 "Certificate not yet valid, valid from: {:?}, now: {:?}",
                 self.valid_from, now
             )));
         }
-        if now_timestamp > self.not_valid_after {
+        if u64::from(now_timestamp) > u64::from(self.not_valid_after) + tolerance {
             return Err(Error::Noise(format!(
                 "Certificate expired, not valid after: {:?}, now: {:?}",
                 self.valid_from, now
@@ -135,10 +210,36 @@ impl SignedPart {
         }
     }
 
+    /// Serializes the signed part into the canonical byte layout that the signature covers. The
+    /// layout is fixed and independent of the format the certificate is stored in (e.g. order of
+    /// fields in JSON). All integers are little endian:
+    ///
+    /// | field                                 | size |
+    /// |---------------------------------------|------|
+    /// | `header.version`                      | 2    |
+    /// | `header.valid_from`                   | 4    |
+    /// | `header.not_valid_after`              | 4    |
+    /// | length of `pubkey` (N)                | 2    |
+    /// | `pubkey`                              | N    |
+    /// | length of `authority_public_key` (M)  | 2    |
+    /// | `authority_public_key`                | M    |
+    ///
+    /// The layout matches the Stratum V2 binary serialization of `SignedPart` that has been used
+    /// for signing before, so existing signatures remain valid.
     fn serialize_to_buf(&self) -> Result<BytesMut> {
-        let mut signed_part_writer = BytesMut::new().writer();
-        v2::serialization::to_writer(&mut signed_part_writer, self)?;
-        Ok(signed_part_writer.into_inner())
+        let authority_public_key = self.authority_public_key.as_bytes();
+        let mut buf = BytesMut::with_capacity(14 + self.pubkey.len() + authority_public_key.len());
+        buf.put_u16_le(self.header.version);
+        buf.put_u32_le(self.header.valid_from);
+        buf.put_u32_le(self.header.not_valid_after);
+        for key in [&self.pubkey[..], &authority_public_key[..]].iter() {
+            let len = u16::try_from(key.len()).map_err(|_| {
+                Error::Noise(format!("Key too long for signed part: {}", key.len()))
+            })?;
+            buf.put_u16_le(len);
+            buf.put_slice(key);
+        }
+        Ok(buf)
     }
 
     /// Generates the actual ed25519_dalek::Signature that is ready to be embedded into the certificate
@@ -164,8 +265,23 @@ impl SignedPart {
         Ok(())
     }
 
-    fn verify_expiration(&self, now: SystemTime) -> Result<SystemTime> {
-        self.header.verify_expiration(now)
+    fn verify_expiration(
+        &self,
+        now: SystemTime,
+        skew_hint_threshold: Duration,
+    ) -> Result<SystemTime> {
+        self.header
+            .verify_expiration_with_skew_hint(now, skew_hint_threshold)
+    }
+
+    /// Verifies the validity period with both bounds widened by `tolerance`, see
+    /// `SignedPartHeader::verify_expiration_with_tolerance()`
+    pub fn verify_expiration_with_tolerance(
+        &self,
+        now: SystemTime,
+        tolerance: Duration,
+    ) -> Result<SystemTime> {
+        self.header.verify_expiration_with_tolerance(now, tolerance)
     }
 }
 
@@ -323,6 +439,50 @@ pub(crate) mod test {
         (signed_part, ca_keypair, static_server_keypair, signature)
     }
 
+    #[test]
+    fn signed_part_canonical_layout() {
+        let (signed_part, ca_keypair, static_keypair, signature) =
+            build_test_signed_part_and_auth();
+        let mut expected = vec![0, 0, 0, 0, 0, 0, 255, 255, 255, 255, 32, 0];
+        expected.extend_from_slice(&static_keypair.public);
+        expected.extend_from_slice(&[32, 0]);
+        expected.extend_from_slice(ca_keypair.public.as_bytes());
+        assert_eq!(
+            &signed_part
+                .serialize_to_buf()
+                .expect("BUG: Cannot serialize signed part")[..],
+            &expected[..]
+        );
+
+        // Fields of a stored certificate in a different order don't affect the signed bytes
+        let certificate = Certificate::new(signed_part, signature);
+        let reordered = format!(
+            "{{\"signature\": {}, \"authority_public_key\": {}, \"public_key\": {}, \
+             \"signed_part_header\": {{\"not_valid_after\": {}, \"valid_from\": {}, \
+             \"version\": {}}}}}",
+            serde_json::to_string(&certificate.signature).expect("BUG: Cannot serialize"),
+            serde_json::to_string(&certificate.authority_public_key)
+                .expect("BUG: Cannot serialize"),
+            serde_json::to_string(&certificate.public_key).expect("BUG: Cannot serialize"),
+            certificate.signed_part_header.not_valid_after,
+            certificate.signed_part_header.valid_from,
+            certificate.signed_part_header.version,
+        );
+        let reordered_certificate =
+            Certificate::try_from(reordered.clone()).expect("BUG: Cannot parse certificate");
+        reordered_certificate
+            .validate(SystemTime::now)
+            .expect("BUG: Reordered certificate not valid");
+
+        // A tampered field is still detected
+        let tampered = reordered.replace("\"valid_from\": 0", "\"valid_from\": 1");
+        assert_ne!(tampered, reordered);
+        Certificate::try_from(tampered)
+            .expect("BUG: Cannot parse certificate")
+            .validate(SystemTime::now)
+            .expect_err("BUG: Tampered certificate accepted");
+    }
+
     #[test]
     fn header_time_validity_is_valid() {
         let header = SignedPartHeader::with_duration(TEST_CERT_VALIDITY)
@@ -357,6 +517,52 @@ pub(crate) mod test {
         );
     }
 
+    #[test]
+    fn header_with_validity() {
+        let valid_from = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let header = SignedPartHeader::with_validity(valid_from, TEST_CERT_VALIDITY)
+            .expect("BUG: cannot build certificate header");
+        assert_eq!(header.valid_from(), valid_from);
+        assert_eq!(header.not_valid_after(), valid_from + TEST_CERT_VALIDITY);
+
+        SignedPartHeader::with_validity(
+            SystemTime::UNIX_EPOCH - Duration::from_secs(1),
+            TEST_CERT_VALIDITY,
+        )
+        .expect_err("BUG: header with time before unix epoch built");
+    }
+
+    #[test]
+    fn signed_part_time_validity_with_tolerance() {
+        let (signed_part, _authority_keypair, _static_keypair, _signature) =
+            build_test_signed_part_and_auth();
+        let now = SystemTime::now();
+        let valid_from = SignedPartHeader::system_time_to_unix_time_u32(&now)
+            .expect("BUG: cannot convert time")
+            + 5;
+        let signed_part = SignedPart::new(
+            SignedPartHeader::new(valid_from, valid_from + 60),
+            signed_part.pubkey,
+            signed_part.authority_public_key,
+        );
+
+        signed_part
+            .verify_expiration_with_tolerance(now, Duration::from_secs(0))
+            .expect_err("BUG: Certificate not valid yet evaluated as valid");
+        signed_part
+            .verify_expiration_with_tolerance(now, Duration::from_secs(10))
+            .expect("BUG: Certificate within tolerance evaluated as not valid");
+
+        // The tolerance applies to expiration, too
+        let after_expiration = now + Duration::from_secs(5 + 60 + 5);
+        signed_part
+            .verify_expiration_with_tolerance(after_expiration, Duration::from_secs(0))
+            .expect_err("BUG: Expired certificate evaluated as valid");
+        signed_part
+            .verify_expiration_with_tolerance(after_expiration, Duration::from_secs(10))
+            .expect("BUG: Certificate within tolerance evaluated as expired");
+    }
+
     #[test]
     fn signature_noise_message_serialization() {
         let (signed_part, authority_keypair, _static_keypair, _signature) =
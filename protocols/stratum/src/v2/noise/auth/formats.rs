@@ -23,15 +23,23 @@
 //! All formats that need to be persisted as physical files, too
 
 // use ed25519_dalek::ed25519::signature::Signature;
-use serde::{Deserialize, Serialize};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 use std::convert::TryFrom;
+use std::ffi::OsStr;
 use std::fmt;
-use std::time::SystemTime;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
+use futures::stream::{FuturesUnordered, StreamExt};
+use ii_async_utils::Tripwire;
 use tokio::net::TcpStream;
+use tokio::sync::Semaphore;
 use tokio_util::codec::{Decoder, Encoder, Framed};
 
-use super::{SignatureNoiseMessage, SignedPart, SignedPartHeader};
+use super::{SignatureNoiseMessage, SignedPart, SignedPartHeader, TimeSource};
 use crate::error::{Error, Result};
 use crate::v2::noise::{
     self, negotiation::EncryptionAlgorithm::*, StaticPublicKey, StaticSecretKey,
@@ -52,10 +60,6 @@ macro_rules! impl_basic_type {
             pub fn new(inner: $inner_encoded_struct_type) -> Self {
                 Self { inner }
             }
-
-            pub fn into_inner(self) -> $inner_encoded_struct_type {
-                self.inner
-            }
         }
         impl fmt::Display for $encoded_struct_type {
             fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -115,13 +119,23 @@ macro_rules! generate_ed25519_structs {
             $($tr), *
         );
 
+        impl $encoded_struct_type {
+            pub fn into_inner(self) -> $inner_encoded_struct_type {
+                self.inner
+            }
+        }
+
         impl TryFrom<String> for $encoded_struct_type {
             type Error = Error;
 
             fn try_from(value: String) -> Result<Self> {
                 // Decode with checksum, don't verify version
-                let bytes = bs58::decode(value).with_check(None).into_vec()?;
-                Ok(Self::new(<$inner_encoded_struct_type>::from_bytes(&bytes)?))
+                let bytes = bs58::decode(&value).with_check(None).into_vec();
+                wipe(value);
+                let bytes = bytes?;
+                let inner = <$inner_encoded_struct_type>::from_bytes(&bytes);
+                wipe(bytes);
+                Ok(Self::new(inner?))
             }
         }
 
@@ -130,6 +144,16 @@ macro_rules! generate_ed25519_structs {
                 bs58::encode(&value.into_inner().to_bytes()[..]).with_check().into_string()
             }
         }
+
+        impl RawBytes for $encoded_struct_type {
+            fn to_raw_bytes(&self) -> Vec<u8> {
+                self.inner.to_bytes().to_vec()
+            }
+
+            fn from_raw_bytes(bytes: &[u8]) -> Result<Self> {
+                Ok(Self::new(<$inner_encoded_struct_type>::from_bytes(bytes)?))
+            }
+        }
     };
 }
 
@@ -145,12 +169,21 @@ macro_rules! generate_noise_keypair_structs {
             Clone
         );
 
+        impl $encoded_struct_type {
+            /// The bytes are taken out instead of moving them so that the type can implement
+            /// `Drop`, see `EncodedStaticSecretKey`
+            pub fn into_inner(mut self) -> $inner_encoded_struct_type {
+                std::mem::take(&mut self.inner)
+            }
+        }
+
         impl TryFrom<String> for $encoded_struct_type {
             type Error = Error;
 
             fn try_from(value: String) -> Result<Self> {
-                let bytes = bs58::decode(value).with_check(None).into_vec()?;
-                Ok(Self::new(bytes))
+                let bytes = bs58::decode(&value).with_check(None).into_vec();
+                wipe(value);
+                Ok(Self::new(bytes?))
             }
         }
 
@@ -159,9 +192,111 @@ macro_rules! generate_noise_keypair_structs {
                 bs58::encode(&value.into_inner()).with_check().into_string()
             }
         }
+
+        impl RawBytes for $encoded_struct_type {
+            fn to_raw_bytes(&self) -> Vec<u8> {
+                self.inner.clone()
+            }
+
+            fn from_raw_bytes(bytes: &[u8]) -> Result<Self> {
+                if bytes.len() != RAW_KEY_LEN {
+                    return Err(Error::Noise(format!(
+                        "Invalid noise key length: {}, expected: {}",
+                        bytes.len(),
+                        RAW_KEY_LEN
+                    )));
+                }
+                Ok(Self::new(bytes.to_vec()))
+            }
+        }
     };
 }
 
+/// Wipes `value` from memory when the `zeroize` feature is enabled, used for buffers that
+/// temporarily hold (possibly secret) key material
+#[cfg(feature = "zeroize")]
+fn wipe<T: zeroize::Zeroize>(mut value: T) {
+    value.zeroize();
+}
+
+#[cfg(not(feature = "zeroize"))]
+fn wipe<T>(_value: T) {}
+
+/// Access to the raw bytes of the encoded key and signature types so that they can be
+/// represented in an alternative encoding, see `Base64Url`. The serde and `String` conversions
+/// of the types always use base58 with checksum.
+pub trait RawBytes: Sized {
+    fn to_raw_bytes(&self) -> Vec<u8>;
+    fn from_raw_bytes(bytes: &[u8]) -> Result<Self>;
+
+    /// Encodes the raw bytes with `encoding`, e.g. lowercase hex as used by other SV2
+    /// implementations
+    fn to_encoded(&self, encoding: RawKeyEncoding) -> String {
+        encoding.encode(&self.to_raw_bytes())
+    }
+
+    /// Decodes raw bytes produced by `to_encoded()` with the same `encoding`, the bytes have to
+    /// represent a valid key or signature of the type
+    fn from_encoded(encoded: &str, encoding: RawKeyEncoding) -> Result<Self> {
+        Self::from_raw_bytes(&encoding.decode(encoded)?)
+    }
+
+    /// Hex encoded SHA-256 of the raw bytes. This is a stable fingerprint for pinning a public
+    /// key that doesn't depend on the file encoding.
+    fn fingerprint(&self) -> String {
+        use bitcoin_hashes::{hex::ToHex, sha256, Hash};
+        sha256::Hash::hash(&self.to_raw_bytes())[..].to_hex()
+    }
+}
+
+/// Wrapper that represents the encoded key or signature `T` (e.g. `EncodedEd25519PublicKey`)
+/// as unpadded base64url instead of the default base58 with checksum. This is meant for
+/// interoperability with web tooling, the keytool formats stay in base58.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Base64Url<T>(pub T);
+
+impl<T> Base64Url<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T: RawBytes> TryFrom<String> for Base64Url<T> {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        let bytes = base64::decode_config(value, base64::URL_SAFE_NO_PAD)
+            .map_err(|e| Error::Noise(format!("Invalid base64url encoding: {}", e)))?;
+        Ok(Self(T::from_raw_bytes(&bytes)?))
+    }
+}
+
+impl<T: RawBytes> From<Base64Url<T>> for String {
+    fn from(value: Base64Url<T>) -> Self {
+        value.to_string()
+    }
+}
+
+impl<T: RawBytes> fmt::Display for Base64Url<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let encoded = base64::encode_config(self.0.to_raw_bytes(), base64::URL_SAFE_NO_PAD);
+        write!(f, "{}", encoded)
+    }
+}
+
+impl<T: RawBytes> Serialize for Base64Url<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de, T: RawBytes> Deserialize<'de> for Base64Url<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        Self::try_from(value).map_err(de::Error::custom)
+    }
+}
+
 generate_ed25519_structs!(
     EncodedEd25519PublicKey,
     Ed25519PublicKeyFormat,
@@ -222,6 +357,81 @@ generate_noise_keypair_structs!(
     "noise_secret_key"
 );
 
+/// Wipes the secret key from memory, this also covers `StaticSecretKeyFormat` and
+/// `ServerSecurityBundle`. `EncodedEd25519SecretKey` needs no such treatment as
+/// `ed25519_dalek::SecretKey` wipes itself.
+#[cfg(feature = "zeroize")]
+impl Drop for EncodedStaticSecretKey {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(&mut self.inner);
+    }
+}
+
+/// Length of raw Ed25519 and X25519 keys
+const RAW_KEY_LEN: usize = 32;
+
+/// Encoding of raw keys and signatures as used by other tools. Secret keys are converted from/to
+/// `Ed25519SecretKeyFormat` or `StaticSecretKeyFormat` with `from_raw()` and `into_inner()`,
+/// the encoded types use `RawBytes::to_encoded()` and `RawBytes::from_encoded()`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RawKeyEncoding {
+    Hex,
+    Base64,
+}
+
+impl RawKeyEncoding {
+    /// Decodes raw bytes, surrounding whitespace is ignored. The length is checked by the
+    /// conversion of the bytes into the actual key or signature.
+    pub fn decode(self, encoded: &str) -> Result<Vec<u8>> {
+        let encoded = encoded.trim();
+        match self {
+            Self::Hex => hex::decode(encoded).map_err(Into::into),
+            Self::Base64 => base64::decode(encoded)
+                .map_err(|e| Error::Noise(format!("Invalid base64 encoding: {}", e))),
+        }
+    }
+
+    pub fn encode(self, raw: &[u8]) -> String {
+        match self {
+            Self::Hex => hex::encode(raw),
+            Self::Base64 => base64::encode(raw),
+        }
+    }
+}
+
+impl FromStr for RawKeyEncoding {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "hex" => Ok(Self::Hex),
+            "base64" => Ok(Self::Base64),
+            _ => Err(Error::Noise(format!(
+                "Unknown raw key encoding: {}, expected: hex or base64",
+                s
+            ))),
+        }
+    }
+}
+
+impl Ed25519SecretKeyFormat {
+    /// Builds the secret key from its raw 32-byte representation, see `RawKeyEncoding`
+    pub fn from_raw(raw: &[u8]) -> Result<Self> {
+        Ok(Self {
+            inner: EncodedEd25519SecretKey::from_raw_bytes(raw)?,
+        })
+    }
+}
+
+impl StaticSecretKeyFormat {
+    /// Builds the secret key from its raw 32-byte representation, see `RawKeyEncoding`
+    pub fn from_raw(raw: &[u8]) -> Result<Self> {
+        Ok(Self {
+            inner: EncodedStaticSecretKey::from_raw_bytes(raw)?,
+        })
+    }
+}
+
 /// Certificate is intended to be serialized and deserialized from/into a file and loaded on the
 /// stratum server.
 /// Second use of the certificate is to build it from `SignatureNoiseMessage` and check its
@@ -244,6 +454,17 @@ impl Certificate {
         }
     }
 
+    /// Same as `new` but verifies that `signature` is a valid signature of `signed_part` first.
+    /// Use this when the inputs come from an untrusted source. Note, that certificate expiration
+    /// is not checked here, use `validate` for that.
+    pub fn new_verified(
+        signed_part: SignedPart,
+        signature: ed25519_dalek::Signature,
+    ) -> Result<Self> {
+        signed_part.verify(&signature)?;
+        Ok(Self::new(signed_part, signature))
+    }
+
     /// See  https://docs.rs/ed25519-dalek/1.0.1/ed25519_dalek/struct.PublicKey.html on
     /// details for the strict verification.
     /// Returns expiration timestamp stated in certificate represented as SystemTime
@@ -251,13 +472,135 @@ impl Certificate {
     where
         FN: FnOnce() -> SystemTime,
     {
-        let signed_part = SignedPart::new(
+        self.validate_with_skew_hint(get_current_time, SignedPartHeader::SKEW_HINT_THRESHOLD)
+    }
+
+    /// Same as `validate`, a certificate that becomes valid more than `skew_hint_threshold` in
+    /// the future is reported as a possible clock skew
+    pub fn validate_with_skew_hint<FN>(
+        &self,
+        get_current_time: FN,
+        skew_hint_threshold: Duration,
+    ) -> Result<SystemTime>
+    where
+        FN: FnOnce() -> SystemTime,
+    {
+        let signed_part = self.verify_self_consistency_inner()?;
+        signed_part.verify_expiration(get_current_time(), skew_hint_threshold)
+    }
+
+    /// Performs all checks of `validate` except for the validity period, i.e. the certificate is
+    /// well-formed and its signature is valid. No clock is needed, which allows checking issued
+    /// certificates offline, e.g. in CI before deployment.
+    pub fn verify_self_consistency(&self) -> Result<()> {
+        self.verify_self_consistency_inner().map(|_| ())
+    }
+
+    /// Returns the time remaining until the certificate expires, `None` when the certificate
+    /// has already expired. The certificate is still valid at its `not_valid_after` time, zero
+    /// is returned then. Validity of the certificate isn't verified.
+    pub fn time_to_expiry(&self, now: SystemTime) -> Option<Duration> {
+        self.signed_part_header
+            .not_valid_after()
+            .duration_since(now)
+            .ok()
+    }
+
+    /// Returns whether the certificate expires in less than `threshold` after `now` or has
+    /// already expired, e.g. to warn about a certificate that has to be renewed soon
+    pub fn is_expiring_within(&self, now: SystemTime, threshold: Duration) -> bool {
+        match self.time_to_expiry(now) {
+            Some(remaining) => remaining < threshold,
+            None => true,
+        }
+    }
+
+    /// Verifies that `secret_key` corresponds to the public key of the certificate, e.g. before
+    /// shipping a generated security bundle
+    pub fn validate_secret_key(&self, secret_key: &StaticSecretKeyFormat) -> Result<()> {
+        let calculated_public_key =
+            StaticPublicKeyFormat::new(noise::public_key_from_secret(&secret_key.inner.inner)?);
+        if calculated_public_key == self.public_key {
+            Ok(())
+        } else {
+            Err(Error::Noise(
+                "Secret key doesn't match the public key of the certificate".to_owned(),
+            ))
+        }
+    }
+
+    fn signed_part(&self) -> SignedPart {
+        SignedPart::new(
             self.signed_part_header.clone(),
             self.public_key.clone().into_inner(),
             self.authority_public_key.clone().into_inner(),
-        );
+        )
+    }
+
+    fn verify_self_consistency_inner(&self) -> Result<SignedPart> {
+        let signed_part = self.signed_part();
+        self.signed_part_header.verify_version()?;
+        self.validate_keys()?;
         signed_part.verify(&self.signature.clone().into_inner())?;
-        signed_part.verify_expiration(get_current_time())
+        Ok(signed_part)
+    }
+
+    /// Verifies that the noise public key is a well-formed X25519 public key so that a corrupted
+    /// certificate is refused when loaded rather than during the noise handshake
+    pub fn validate_keys(&self) -> Result<()> {
+        let public_key = &self.public_key.inner.inner;
+        let mut raw_public_key = [0_u8; 32];
+        if public_key.len() != raw_public_key.len() {
+            return Err(Error::Noise(format!(
+                "Invalid noise public key length in certificate: {}, expected: {}",
+                public_key.len(),
+                raw_public_key.len()
+            )));
+        }
+        raw_public_key.copy_from_slice(&public_key[..]);
+        // Any clamped scalar eliminates the low order component, only a low order point
+        // results in the all-zero shared secret
+        if x25519_dalek::x25519([1_u8; 32], raw_public_key) == [0_u8; 32] {
+            return Err(Error::Noise(
+                "Noise public key in certificate is a low order point".to_owned(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Verifies that `signing_key` is the secret key of the authority that signed this
+    /// certificate. This catches a wrong signing key before re-signing, otherwise the new
+    /// certificate couldn't be verified with the already distributed authority public key.
+    pub fn check_signing_key(&self, signing_key: &ed25519_dalek::SecretKey) -> Result<()> {
+        let authority_public_key = self.authority_public_key.clone().into_inner();
+        let signing_public_key: ed25519_dalek::PublicKey = signing_key.into();
+        if signing_public_key != authority_public_key {
+            return Err(Error::Noise(format!(
+                "Signing key doesn't belong to the certificate authority {}, its public key is {}",
+                EncodedEd25519PublicKey::new(authority_public_key),
+                EncodedEd25519PublicKey::new(signing_public_key)
+            )));
+        }
+        Ok(())
+    }
+
+    /// Returns true if validity periods of both certificates have at least one moment in common.
+    /// A replacement certificate should overlap the one being replaced, otherwise there is a gap
+    /// when neither of them is valid.
+    pub fn has_overlap_with(&self, other: &Certificate) -> bool {
+        let this = &self.signed_part_header;
+        let other = &other.signed_part_header;
+        this.valid_from <= other.not_valid_after && other.valid_from <= this.not_valid_after
+    }
+
+    /// Same as `validate` but takes the current time from the specified `time_source`
+    pub fn validate_with(&self, time_source: &dyn TimeSource) -> Result<SystemTime> {
+        self.validate(|| time_source.now())
+    }
+
+    /// Loads a certificate in the JSON format as generated by the keytool
+    pub fn read_from_file(path: &Path) -> Result<Self> {
+        Self::try_from(fs::read_to_string(path)?)
     }
 
     pub fn from_noise_message(
@@ -277,6 +620,21 @@ impl Certificate {
             signature: self.signature.clone().into_inner(),
         }
     }
+
+    /// Serializes the certificate into a PEM container labeled `SV2 CERTIFICATE` that wraps
+    /// the JSON representation, e.g. for storing it along with other certificates in a PKI
+    /// directory
+    pub fn to_pem(&self) -> Result<String> {
+        Ok(pem_encode(
+            CERTIFICATE_PEM_LABEL,
+            &serde_json::to_vec(self)?,
+        ))
+    }
+
+    /// Parses a certificate serialized by `to_pem()`
+    pub fn from_pem(pem: &str) -> Result<Self> {
+        serde_json::from_slice(&pem_decode(CERTIFICATE_PEM_LABEL, pem)?).map_err(Into::into)
+    }
 }
 
 impl TryFrom<String> for Certificate {
@@ -294,6 +652,212 @@ impl TryFrom<Certificate> for String {
     }
 }
 
+/// Certificates ordered from the server certificate up to the one issued by a root authority.
+/// Each certificate following the server certificate certifies (as its public key) the authority
+/// that has signed the preceding certificate. The chain is serialized as a JSON array so that it
+/// can be stored in a single file.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CertificateChain(Vec<Certificate>);
+
+impl CertificateChain {
+    pub fn new(certificates: Vec<Certificate>) -> Self {
+        Self(certificates)
+    }
+
+    pub fn certificates(&self) -> &[Certificate] {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> Vec<Certificate> {
+        self.0
+    }
+
+    /// Walks the chain from the server certificate up, validating each certificate the same way
+    /// as `Certificate::validate` and verifying that it has been signed by the authority
+    /// certified by the next certificate. The last certificate has to be signed by one of
+    /// `trusted_roots`.
+    /// Returns the earliest expiration timestamp of the certificates in the chain
+    pub fn validate_chain<FN>(
+        &self,
+        trusted_roots: &[Ed25519PublicKeyFormat],
+        get_current_time: FN,
+    ) -> Result<SystemTime>
+    where
+        FN: FnOnce() -> SystemTime,
+    {
+        let now = get_current_time();
+        let mut certificates = self.0.iter();
+        let certificate = certificates
+            .next()
+            .ok_or_else(|| Error::Noise("Empty certificate chain".to_owned()))?;
+        let mut expiration = certificate.validate(|| now)?;
+        let mut authority_public_key = &certificate.authority_public_key;
+        for (position, certificate) in certificates.enumerate() {
+            if certificate.public_key.inner.inner[..]
+                != authority_public_key.inner.inner.as_bytes()[..]
+            {
+                return Err(Error::Noise(format!(
+                    "Certificate {} of the chain doesn't certify the authority of the preceding \
+                     certificate",
+                    position + 1
+                )));
+            }
+            expiration = expiration.min(certificate.validate(|| now)?);
+            authority_public_key = &certificate.authority_public_key;
+        }
+        if !trusted_roots.contains(authority_public_key) {
+            return Err(Error::Noise(format!(
+                "Certificate chain is signed by untrusted authority: {}",
+                authority_public_key.inner
+            )));
+        }
+        Ok(expiration)
+    }
+}
+
+impl TryFrom<String> for CertificateChain {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        serde_json::from_str(value.as_str()).map_err(Into::into)
+    }
+}
+
+impl TryFrom<CertificateChain> for String {
+    type Error = Error;
+    fn try_from(value: CertificateChain) -> Result<String> {
+        serde_json::to_string_pretty(&value).map_err(Into::into)
+    }
+}
+
+/// Label of the PEM container of a `Certificate`, see `Certificate::to_pem()`
+const CERTIFICATE_PEM_LABEL: &str = "SV2 CERTIFICATE";
+/// Label of the PEM container of a `ServerSecurityBundle`, see `ServerSecurityBundle::to_pem()`
+const SECURITY_BUNDLE_PEM_LABEL: &str = "SV2 SERVER SECURITY BUNDLE";
+/// Length of the lines of base64 encoded PEM body
+const PEM_LINE_LEN: usize = 64;
+
+/// Wraps `data` into a PEM container labeled `label`
+fn pem_encode(label: &str, data: &[u8]) -> String {
+    let encoded = base64::encode(data);
+    let mut pem = format!("-----BEGIN {}-----\n", label);
+    for line in encoded.as_bytes().chunks(PEM_LINE_LEN) {
+        pem.push_str(std::str::from_utf8(line).expect("BUG: base64 is not ASCII"));
+        pem.push('\n');
+    }
+    pem.push_str(&format!("-----END {}-----\n", label));
+    pem
+}
+
+/// Extracts data from a PEM container labeled `label`, surrounding whitespace is ignored
+fn pem_decode(label: &str, pem: &str) -> Result<Vec<u8>> {
+    let begin = format!("-----BEGIN {}-----", label);
+    let end = format!("-----END {}-----", label);
+    let lines: Vec<_> = pem.trim().lines().map(str::trim).collect();
+    if lines.len() < 2 || lines[0] != begin || lines[lines.len() - 1] != end {
+        return Err(Error::Noise(format!(
+            "Expected PEM container labeled '{}'",
+            label
+        )));
+    }
+    base64::decode(lines[1..lines.len() - 1].concat())
+        .map_err(|e| Error::Noise(format!("Invalid PEM body: {}", e)))
+}
+
+/// Loads and validates all certificates (files with `.cert` extension) in `dir`. Returns the path
+/// of each certificate along with the result of its validation at time `now`, sorted by path.
+/// Only failure to read the directory itself is reported as an error.
+pub fn scan_certificates(
+    dir: &Path,
+    now: SystemTime,
+) -> Result<Vec<(PathBuf, Result<SystemTime>)>> {
+    Ok(certificate_paths(dir)?
+        .into_iter()
+        .map(|path| {
+            let result = scan_certificate(&path, now);
+            (path, result)
+        })
+        .collect())
+}
+
+/// Same as `scan_certificates` but at most `concurrency` certificates are loaded and validated
+/// at a time on the blocking thread pool, which speeds up scanning of large directories.
+/// Scanning is aborted with an error when `tripwire` fires before all certificates are done.
+pub async fn scan_certificates_concurrent(
+    dir: &Path,
+    now: SystemTime,
+    concurrency: usize,
+    tripwire: Tripwire,
+) -> Result<Vec<(PathBuf, Result<SystemTime>)>> {
+    let paths = certificate_paths(dir)?;
+    scan_paths_concurrent(paths, concurrency, tripwire, move |path| {
+        scan_certificate(path, now)
+    })
+    .await
+}
+
+/// Lists files with `.cert` extension in `dir` sorted by path
+fn certificate_paths(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() && path.extension() == Some(OsStr::new("cert")) {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+fn scan_certificate(path: &Path, now: SystemTime) -> Result<SystemTime> {
+    Certificate::read_from_file(path).and_then(|cert| cert.validate(|| now))
+}
+
+/// Runs `scan` for each of `paths` with bounded concurrency, results are sorted by path
+async fn scan_paths_concurrent<F>(
+    paths: Vec<PathBuf>,
+    concurrency: usize,
+    tripwire: Tripwire,
+    scan: F,
+) -> Result<Vec<(PathBuf, Result<SystemTime>)>>
+where
+    F: Fn(&Path) -> Result<SystemTime> + Send + Sync + 'static,
+{
+    let scan = Arc::new(scan);
+    let semaphore = Arc::new(Semaphore::new(concurrency.max(1)));
+    let mut results = Vec::with_capacity(paths.len());
+
+    let mut scans = paths
+        .into_iter()
+        .map(|path| {
+            let scan = scan.clone();
+            let semaphore = semaphore.clone();
+            async move {
+                let _permit = semaphore
+                    .acquire_owned()
+                    .await
+                    .expect("BUG: Semaphore closed");
+                tokio::task::spawn_blocking(move || {
+                    let result = scan(&path);
+                    (path, result)
+                })
+                .await
+                .map_err(|e| Error::General(format!("Certificate scan failed: {}", e)))
+            }
+        })
+        .collect::<FuturesUnordered<_>>()
+        .take_until(tripwire);
+    while let Some(result) = scans.next().await {
+        results.push(result?);
+    }
+    if scans.take_result().is_some() {
+        return Err(Error::General("Certificate scan cancelled".to_owned()));
+    }
+
+    results.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(results)
+}
+
 /// Server security bundle is held by the server and provided to each (noise secured) connection so
 /// that it can successfully perform the noise handshake and authenticate itself to the client
 /// NOTE: this struct intentionally implements Debug manually to prevent leakage of the secure key
@@ -314,26 +878,13 @@ impl ServerSecurityBundle {
         Ok(bundle)
     }
 
-    // FIXME: This breaks layers of abstraction. We are using external library to validate
-    // keys for noise protocol internal structures. Unfortunately snow is unlikely to implement
-    // mechanisms for secret key validation.
-    // TODO: Consider moving it onto Certificate structure
     fn validate_secret_key(&self) -> Result<()> {
-        let mut raw_secret_key = [0_u8; 32];
-        raw_secret_key.copy_from_slice(&self.secret_key.inner.inner);
-        let raw_public_key =
-            x25519_dalek::x25519(raw_secret_key, x25519_dalek::X25519_BASEPOINT_BYTES);
-        let calculated_public_key = StaticPublicKeyFormat::new(raw_public_key.to_vec());
-        if calculated_public_key == self.certificate.public_key {
-            Ok(())
-        } else {
-            Err(Error::Noise(
-                "Inconsistent secret and public key in security bundle".to_owned(),
-            ))
-        }
+        self.certificate.validate_secret_key(&self.secret_key)
     }
 
-    fn authority_pubkey(&self) -> EncodedEd25519PublicKey {
+    /// Public key of the authority that signed the certificate, its `Display` implementation
+    /// provides the base58check encoding used in the certificate files
+    pub fn authority_pubkey(&self) -> EncodedEd25519PublicKey {
         EncodedEd25519PublicKey::new(self.certificate.authority_public_key.clone().into_inner())
     }
 
@@ -343,6 +894,23 @@ impl ServerSecurityBundle {
         Ok(bundle)
     }
 
+    /// Serializes the bundle into a PEM container labeled `SV2 SERVER SECURITY BUNDLE`, see
+    /// `Certificate::to_pem()`
+    pub fn to_pem(&self) -> Result<String> {
+        Ok(pem_encode(
+            SECURITY_BUNDLE_PEM_LABEL,
+            &serde_json::to_vec(self)?,
+        ))
+    }
+
+    /// Parses a bundle serialized by `to_pem()`, the secret key is validated the same way as
+    /// in `read_from_string()`
+    pub fn from_pem(pem: &str) -> Result<Self> {
+        let bundle = serde_json::from_slice::<Self>(&pem_decode(SECURITY_BUNDLE_PEM_LABEL, pem)?)?;
+        bundle.validate_secret_key()?;
+        Ok(bundle)
+    }
+
     pub fn read_from_strings(certificate: &str, secret_key: &str) -> Result<Self> {
         let bundle = serde_json::from_str::<Certificate>(certificate).and_then(|cert| {
             serde_json::from_str::<StaticSecretKeyFormat>(secret_key)
@@ -399,6 +967,55 @@ impl ServerSecurityBundle {
             .map_err(|_| Error::Noise("Time validation failed".into()))
     }
 
+    /// See `Certificate::time_to_expiry()`
+    pub fn time_to_expiry(&self, now: SystemTime) -> Option<Duration> {
+        self.certificate.time_to_expiry(now)
+    }
+
+    /// See `Certificate::is_expiring_within()`
+    pub fn is_expiring_within(&self, now: SystemTime, threshold: Duration) -> bool {
+        self.certificate.is_expiring_within(now, threshold)
+    }
+
+    /// Builds a bundle for the same static key with a new certificate that has the validity
+    /// period of `header` and is signed by `authority_keypair`. The authority has to be the same
+    /// as the one that signed the current certificate.
+    pub fn renew(
+        &self,
+        header: SignedPartHeader,
+        authority_keypair: &ed25519_dalek::Keypair,
+    ) -> Result<Self> {
+        self.certificate
+            .check_signing_key(&authority_keypair.secret)?;
+        let signed_part = SignedPart::new(
+            header,
+            self.certificate.public_key.clone().into_inner(),
+            authority_keypair.public,
+        );
+        let signature = signed_part.sign_with(authority_keypair)?;
+        Self::new(
+            Certificate::new(signed_part, signature),
+            self.secret_key.clone(),
+        )
+    }
+
+    /// Refuses a certificate that is valid at `now` but has less than `min` of its validity left.
+    /// This is intended to be used at server startup to enforce certificate renewal before the
+    /// certificate expires in the middle of client handshakes.
+    pub fn ensure_min_validity(&self, now: SystemTime, min: Duration) -> Result<()> {
+        let not_valid_after = self.certificate.validate(|| now)?;
+        let remaining = not_valid_after
+            .duration_since(now)
+            .unwrap_or_else(|_| Duration::from_secs(0));
+        if remaining < min {
+            return Err(Error::Noise(format!(
+                "Certificate expires too soon, remaining validity: {:?}, required: {:?}",
+                remaining, min
+            )));
+        }
+        Ok(())
+    }
+
     pub async fn build_framed_tcp<C, F>(
         &self,
         tcp_stream: TcpStream,
@@ -531,6 +1148,514 @@ pub mod test {
             .expect("BUG: Certificate not valid!");
     }
 
+    #[test]
+    fn certificate_new_verified() {
+        let (signed_part, authority_keypair, static_keypair, signature) =
+            build_test_signed_part_and_auth();
+        // Signature of a signed part that differs only in the validity period
+        let other_signature = SignedPart::new(
+            SignedPartHeader::new(1_000, 2_000),
+            static_keypair.public,
+            signed_part.authority_public_key,
+        )
+        .sign_with(&authority_keypair)
+        .expect("BUG: Failed to sign certificate");
+
+        Certificate::new_verified(signed_part.clone(), other_signature)
+            .expect_err("BUG: Certificate with mismatched signature constructed!");
+        Certificate::new_verified(signed_part, signature)
+            .expect("BUG: Certificate with matching signature not constructed!");
+    }
+
+    #[test]
+    fn certificate_validate_keys() {
+        let (signed_part, authority_keypair, static_keypair, _signature) =
+            build_test_signed_part_and_auth();
+        let build_certificate = |public_key: StaticPublicKey| {
+            let signed_part = SignedPart::new(
+                signed_part.header.clone(),
+                public_key,
+                signed_part.authority_public_key,
+            );
+            let signature = signed_part
+                .sign_with(&authority_keypair)
+                .expect("BUG: Failed to sign certificate");
+            Certificate::new(signed_part, signature)
+        };
+
+        build_certificate(static_keypair.public.clone())
+            .validate_keys()
+            .expect("BUG: Valid noise public key refused");
+
+        let certificate = build_certificate(static_keypair.public[1..].to_vec());
+        let err = certificate
+            .validate(SystemTime::now)
+            .expect_err("BUG: Certificate with short noise public key accepted");
+        assert!(
+            err.to_string()
+                .contains("Invalid noise public key length in certificate: 31"),
+            "BUG: Unexpected error: {}",
+            err
+        );
+
+        build_certificate(vec![0; 32])
+            .validate_keys()
+            .expect_err("BUG: Low order noise public key accepted");
+    }
+
+    #[test]
+    fn certificate_validate_version() {
+        let (signed_part, authority_keypair, static_keypair, _signature) =
+            build_test_signed_part_and_auth();
+        let mut header = signed_part.header.clone();
+        header.version = 1;
+        let signed_part = SignedPart::new(
+            header,
+            static_keypair.public,
+            signed_part.authority_public_key,
+        );
+        let signature = signed_part
+            .sign_with(&authority_keypair)
+            .expect("BUG: Failed to sign certificate");
+        let err = Certificate::new(signed_part, signature)
+            .validate(SystemTime::now)
+            .expect_err("BUG: Certificate with unknown version accepted");
+        assert!(
+            matches!(
+                err,
+                Error::UnsupportedCertificateVersion {
+                    version: 1,
+                    supported: 0
+                }
+            ),
+            "BUG: Unexpected error: {}",
+            err
+        );
+    }
+
+    #[test]
+    fn certificate_verify_self_consistency() {
+        // Expired long ago, which doesn't matter for the offline check
+        let certificate = build_test_bundle(1_000, 2_000).certificate;
+        certificate
+            .verify_self_consistency()
+            .expect("BUG: Valid certificate refused");
+        certificate
+            .validate(SystemTime::now)
+            .expect_err("BUG: Expired certificate accepted");
+
+        let mut tampered = certificate;
+        tampered.signature = build_test_bundle(1_000, 3_000).certificate.signature;
+        tampered
+            .verify_self_consistency()
+            .expect_err("BUG: Tampered signature accepted");
+    }
+
+    #[test]
+    fn certificate_validate_clock_skew() {
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .expect("BUG: Invalid system time")
+            .as_secs() as u32;
+        let certificate = build_test_bundle(now + 3_600, now + 7_200).certificate;
+
+        let err = certificate
+            .validate(SystemTime::now)
+            .expect_err("BUG: Certificate valid an hour ahead accepted");
+        assert!(
+            err.to_string().contains("possible clock skew"),
+            "BUG: Unexpected error: {}",
+            err
+        );
+
+        // Within the tolerance the certificate is just not yet valid
+        let err = certificate
+            .validate_with_skew_hint(SystemTime::now, Duration::from_secs(7_200))
+            .expect_err("BUG: Certificate valid an hour ahead accepted");
+        assert!(
+            err.to_string().contains("Certificate not yet valid"),
+            "BUG: Unexpected error: {}",
+            err
+        );
+    }
+
+    #[test]
+    fn fingerprint() {
+        let public_key = EncodedStaticPublicKey::new(vec![0; 32]);
+        assert_eq!(
+            "66687aadf862bd776c8fc18b8e9f8e20089714856ee233b3902a591d0d5f2925",
+            public_key.fingerprint()
+        );
+
+        let (signed_part, _authority_keypair, _static_keypair, _signature) =
+            build_test_signed_part_and_auth();
+        let authority_public_key = EncodedEd25519PublicKey::new(signed_part.authority_public_key);
+        assert_eq!(
+            EncodedStaticPublicKey::new(authority_public_key.to_raw_bytes()).fingerprint(),
+            authority_public_key.fingerprint()
+        );
+    }
+
+    #[test]
+    fn base64url_encoding() {
+        let (signed_part, _authority_keypair, static_keypair, signature) =
+            build_test_signed_part_and_auth();
+
+        let public_key = EncodedEd25519PublicKey::new(signed_part.authority_public_key);
+        let base58 = String::from(public_key.clone());
+        let base64url = String::from(Base64Url(public_key.clone()));
+        assert_ne!(base58, base64url);
+        let from_base58 =
+            EncodedEd25519PublicKey::try_from(base58).expect("BUG: Cannot decode base58");
+        let from_base64url = Base64Url::<EncodedEd25519PublicKey>::try_from(base64url)
+            .expect("BUG: Cannot decode base64url")
+            .into_inner();
+        assert_eq!(from_base58, from_base64url);
+        assert_eq!(public_key, from_base64url);
+        // Noise keys are checked for length the same way as the Ed25519 ones
+        Base64Url::<EncodedStaticPublicKey>::try_from("AAAA".to_string())
+            .expect_err("BUG: Short static public key decoded");
+
+        // Serde representation is a plain base64url string
+        let signature = Base64Url(EncodedEd25519Signature::new(signature));
+        let json = serde_json::to_string(&signature).expect("BUG: Cannot serialize signature");
+        assert!(!json.contains('+') && !json.contains('/') && !json.contains('='));
+        let deserialized: Base64Url<EncodedEd25519Signature> =
+            serde_json::from_str(&json).expect("BUG: Cannot deserialize signature");
+        assert_eq!(signature, deserialized);
+
+        let static_public_key = Base64Url(EncodedStaticPublicKey::new(static_keypair.public));
+        assert_eq!(
+            static_public_key,
+            Base64Url::try_from(String::from(static_public_key.clone()))
+                .expect("BUG: Cannot decode static public key")
+        );
+    }
+
+    #[test]
+    fn hex_and_base64_encoding() {
+        let (signed_part, _authority_keypair, static_keypair, signature) =
+            build_test_signed_part_and_auth();
+
+        let public_key = EncodedEd25519PublicKey::new(signed_part.authority_public_key);
+        let encoded_hex = public_key.to_encoded(RawKeyEncoding::Hex);
+        assert_eq!(encoded_hex, hex::encode(public_key.to_raw_bytes()));
+        assert_eq!(
+            public_key,
+            EncodedEd25519PublicKey::from_encoded(&encoded_hex, RawKeyEncoding::Hex)
+                .expect("BUG: Cannot decode hex")
+        );
+        let encoded_base64 = public_key.to_encoded(RawKeyEncoding::Base64);
+        assert_eq!(
+            public_key,
+            EncodedEd25519PublicKey::from_encoded(&encoded_base64, RawKeyEncoding::Base64)
+                .expect("BUG: Cannot decode base64")
+        );
+
+        let signature = EncodedEd25519Signature::new(signature);
+        assert_eq!(
+            signature,
+            EncodedEd25519Signature::from_encoded(
+                &signature.to_encoded(RawKeyEncoding::Hex),
+                RawKeyEncoding::Hex
+            )
+            .expect("BUG: Cannot decode hex signature")
+        );
+        assert_eq!(
+            signature,
+            EncodedEd25519Signature::from_encoded(
+                &signature.to_encoded(RawKeyEncoding::Base64),
+                RawKeyEncoding::Base64
+            )
+            .expect("BUG: Cannot decode base64 signature")
+        );
+
+        let static_public_key = EncodedStaticPublicKey::new(static_keypair.public);
+        assert_eq!(
+            static_public_key,
+            EncodedStaticPublicKey::from_encoded(
+                &static_public_key.to_encoded(RawKeyEncoding::Hex),
+                RawKeyEncoding::Hex
+            )
+            .expect("BUG: Cannot decode hex static public key")
+        );
+        assert_eq!(
+            static_public_key,
+            EncodedStaticPublicKey::from_encoded(
+                &static_public_key.to_encoded(RawKeyEncoding::Base64),
+                RawKeyEncoding::Base64
+            )
+            .expect("BUG: Cannot decode base64 static public key")
+        );
+
+        // The String conversion stays base58
+        let base58 = String::from(public_key.clone());
+        assert_ne!(base58, encoded_hex);
+        assert_ne!(base58, encoded_base64);
+        assert_eq!(
+            public_key,
+            EncodedEd25519PublicKey::try_from(base58).expect("BUG: Cannot decode base58")
+        );
+
+        EncodedEd25519PublicKey::from_encoded("not hex", RawKeyEncoding::Hex)
+            .expect_err("BUG: Invalid hex decoded");
+        EncodedEd25519PublicKey::from_encoded("not base64!", RawKeyEncoding::Base64)
+            .expect_err("BUG: Invalid base64 decoded");
+        // Valid encoding of bytes that don't represent a key
+        EncodedEd25519PublicKey::from_encoded("00", RawKeyEncoding::Hex)
+            .expect_err("BUG: Short key decoded");
+    }
+
+    #[test]
+    fn certificate_validate_with_time_source() {
+        struct FixedTime(SystemTime);
+        impl TimeSource for FixedTime {
+            fn now(&self) -> SystemTime {
+                self.0
+            }
+        }
+
+        let certificate = build_test_bundle(1_000, 2_000).certificate;
+        let at = |secs| FixedTime(SystemTime::UNIX_EPOCH + Duration::from_secs(secs));
+
+        certificate
+            .validate_with(&at(1_500))
+            .expect("BUG: Certificate not valid within its validity period!");
+        certificate
+            .validate_with(&at(999))
+            .expect_err("BUG: Certificate valid before its validity period!");
+        certificate
+            .validate_with(&at(2_001))
+            .expect_err("BUG: Certificate valid after expiration!");
+    }
+
+    /// Builds a bundle from the test keys with a certificate valid in the specified interval
+    fn build_test_bundle(valid_from: u32, not_valid_after: u32) -> ServerSecurityBundle {
+        let (signed_part, authority_keypair, static_keypair, _signature) =
+            build_test_signed_part_and_auth();
+        let signed_part = SignedPart::new(
+            SignedPartHeader::new(valid_from, not_valid_after),
+            static_keypair.public.clone(),
+            signed_part.authority_public_key,
+        );
+        let signature = signed_part
+            .sign_with(&authority_keypair)
+            .expect("BUG: Failed to sign certificate");
+        ServerSecurityBundle::new(
+            Certificate::new(signed_part, signature),
+            StaticSecretKeyFormat::new(static_keypair.private),
+        )
+        .expect("BUG: Failed to build security bundle")
+    }
+
+    #[tokio::test]
+    async fn scan_certificates_dir_concurrent() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        const CERT_COUNT: usize = 32;
+        const CONCURRENCY: usize = 4;
+
+        let dir = tempfile::tempdir().expect("BUG: Cannot create temporary directory");
+        let certificate = String::try_from(build_test_bundle(1_000, 4_000).certificate)
+            .expect("BUG: Cannot serialize certificate");
+        for i in 0..CERT_COUNT {
+            fs::write(dir.path().join(format!("{:02}.cert", i)), &certificate)
+                .expect("BUG: Cannot write certificate");
+        }
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(3_000);
+        let (_trigger, tripwire) = Tripwire::new();
+
+        let results = scan_certificates_concurrent(dir.path(), now, CONCURRENCY, tripwire.clone())
+            .await
+            .expect("BUG: Cannot scan directory");
+        let expected = scan_certificates(dir.path(), now).expect("BUG: Cannot scan directory");
+        assert_eq!(results.len(), CERT_COUNT);
+        for (result, expected) in results.iter().zip(expected.iter()) {
+            assert_eq!(result.0, expected.0);
+            assert_eq!(
+                result
+                    .1
+                    .as_ref()
+                    .expect("BUG: Valid certificate reported as invalid!"),
+                expected
+                    .1
+                    .as_ref()
+                    .expect("BUG: Valid certificate reported as invalid!"),
+            );
+        }
+
+        // Track the number of certificates being scanned at the same time
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+        let scanned = Arc::new(AtomicUsize::new(0));
+        let paths = certificate_paths(dir.path()).expect("BUG: Cannot list directory");
+        let results = {
+            let running = running.clone();
+            let max_running = max_running.clone();
+            let scanned = scanned.clone();
+            scan_paths_concurrent(paths, CONCURRENCY, tripwire, move |path| {
+                let current = running.fetch_add(1, Ordering::SeqCst) + 1;
+                max_running.fetch_max(current, Ordering::SeqCst);
+                std::thread::sleep(Duration::from_millis(10));
+                let result = scan_certificate(path, now);
+                running.fetch_sub(1, Ordering::SeqCst);
+                scanned.fetch_add(1, Ordering::SeqCst);
+                result
+            })
+            .await
+            .expect("BUG: Cannot scan directory")
+        };
+        assert_eq!(results.len(), CERT_COUNT);
+        assert_eq!(scanned.load(Ordering::SeqCst), CERT_COUNT);
+        let max_running = max_running.load(Ordering::SeqCst);
+        assert!(
+            max_running > 0 && max_running <= CONCURRENCY,
+            "BUG: Scanned {} certificates at once",
+            max_running
+        );
+    }
+
+    #[tokio::test]
+    async fn scan_certificates_dir_cancelled() {
+        let dir = tempfile::tempdir().expect("BUG: Cannot create temporary directory");
+        let certificate = String::try_from(build_test_bundle(1_000, 4_000).certificate)
+            .expect("BUG: Cannot serialize certificate");
+        fs::write(dir.path().join("valid.cert"), &certificate)
+            .expect("BUG: Cannot write certificate");
+        let (trigger, tripwire) = Tripwire::new();
+        trigger.cancel();
+
+        scan_certificates_concurrent(dir.path(), SystemTime::now(), 1, tripwire)
+            .await
+            .expect_err("BUG: Cancelled scan succeeded");
+    }
+
+    #[test]
+    fn scan_certificates_dir() {
+        let dir = tempfile::tempdir().expect("BUG: Cannot create temporary directory");
+        let write_cert = |name: &str, certificate: Certificate| {
+            fs::write(
+                dir.path().join(name),
+                String::try_from(certificate).expect("BUG: Cannot serialize certificate"),
+            )
+            .expect("BUG: Cannot write certificate");
+        };
+        write_cert("expired.cert", build_test_bundle(1_000, 2_000).certificate);
+        write_cert("valid.cert", build_test_bundle(1_000, 4_000).certificate);
+        // Files without the certificate extension are skipped
+        fs::write(dir.path().join("notes.txt"), "not a certificate")
+            .expect("BUG: Cannot write file");
+
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(3_000);
+        let results = scan_certificates(dir.path(), now).expect("BUG: Cannot scan directory");
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].0, dir.path().join("expired.cert"));
+        results[0]
+            .1
+            .as_ref()
+            .expect_err("BUG: Expired certificate reported as valid!");
+        assert_eq!(results[1].0, dir.path().join("valid.cert"));
+        assert_eq!(
+            results[1]
+                .1
+                .as_ref()
+                .expect("BUG: Valid certificate reported as invalid!"),
+            &(SystemTime::UNIX_EPOCH + Duration::from_secs(4_000))
+        );
+    }
+
+    #[test]
+    fn bundle_min_validity() {
+        let bundle = build_test_bundle(1_000, 2_000);
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_990);
+
+        bundle
+            .ensure_min_validity(now, Duration::from_secs(60))
+            .expect_err("BUG: Certificate expiring in 10 seconds accepted");
+        bundle
+            .ensure_min_validity(now, Duration::from_secs(5))
+            .expect("BUG: Certificate with sufficient validity rejected");
+        bundle
+            .ensure_min_validity(now + Duration::from_secs(20), Duration::from_secs(0))
+            .expect_err("BUG: Expired certificate accepted");
+    }
+
+    #[test]
+    fn bundle_renew() {
+        let (_signed_part, authority_keypair, _static_keypair, _signature) =
+            build_test_signed_part_and_auth();
+        let bundle = build_test_bundle(1_000, 2_000);
+
+        let renewed = bundle
+            .renew(SignedPartHeader::new(1_500, 3_000), &authority_keypair)
+            .expect("BUG: Cannot renew bundle");
+        assert_eq!(
+            renewed.certificate.public_key,
+            bundle.certificate.public_key
+        );
+        assert_eq!(
+            renewed
+                .validate_by_time(|| SystemTime::UNIX_EPOCH + Duration::from_secs(2_500))
+                .expect("BUG: Renewed certificate not valid"),
+            SystemTime::UNIX_EPOCH + Duration::from_secs(3_000)
+        );
+
+        let other_authority = ed25519_dalek::Keypair::generate(&mut rand::rngs::OsRng {});
+        bundle
+            .renew(SignedPartHeader::new(1_500, 3_000), &other_authority)
+            .expect_err("BUG: Bundle renewed by a different authority");
+    }
+
+    #[test]
+    fn certificate_check_signing_key() {
+        let (_signed_part, authority_keypair, _static_keypair, _signature) =
+            build_test_signed_part_and_auth();
+        let certificate = build_test_bundle(1_000, 2_000).certificate;
+        certificate
+            .check_signing_key(&authority_keypair.secret)
+            .expect("BUG: Authority signing key refused");
+
+        let other_authority = ed25519_dalek::Keypair::generate(&mut rand::rngs::OsRng {});
+        match certificate.check_signing_key(&other_authority.secret) {
+            Err(Error::Noise(msg)) => assert!(
+                msg.contains(&EncodedEd25519PublicKey::new(other_authority.public).to_string()),
+                "BUG: Error doesn't mention the wrong key: {}",
+                msg
+            ),
+            res => panic!("BUG: Wrong signing key not detected: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn certificate_overlap() {
+        let certificate = build_test_bundle(1_000, 2_000).certificate;
+        for (valid_from, not_valid_after) in &[(1_500, 3_000), (500, 1_000), (2_000, 3_000)] {
+            assert!(
+                certificate.has_overlap_with(
+                    &build_test_bundle(*valid_from, *not_valid_after).certificate
+                ),
+                "BUG: Overlap of {}-{} not detected",
+                valid_from,
+                not_valid_after
+            );
+        }
+        for (valid_from, not_valid_after) in &[(2_001, 3_000), (0, 999)] {
+            let other = build_test_bundle(*valid_from, *not_valid_after).certificate;
+            assert!(!certificate.has_overlap_with(&other));
+            assert!(!other.has_overlap_with(&certificate));
+        }
+    }
+
+    #[test]
+    fn bundle_authority_pubkey() {
+        let bundle = build_test_bundle(1_000, 2_000);
+        assert_eq!(
+            bundle.authority_pubkey().to_string(),
+            "NDemuBPqrZM7XLHut6xjbC5L3DL3kyCapn6W3vc6UK5BwisDq"
+        );
+    }
+
     #[test]
     fn validate_bundle() {
         let (signed_part, _authority_keypair, static_keypair, signature) =
@@ -556,6 +1681,222 @@ pub mod test {
             .expect_err("BUG: Validation passed for inconsistent server security bundle");
     }
 
+    #[test]
+    fn bundle_with_swapped_secret_key() {
+        let (signed_part, _authority_keypair, _static_keypair, signature) =
+            build_test_signed_part_and_auth();
+        let certificate = Certificate::new(signed_part, signature);
+        let other_keypair = noise::generate_keypair().expect("BUG: Failed to generate keypair");
+        let secret_key = StaticSecretKeyFormat::new(other_keypair.private);
+
+        match ServerSecurityBundle::new(certificate.clone(), secret_key.clone()) {
+            Err(Error::Noise(_)) => {}
+            _ => panic!("BUG: Bundle with mismatching keys constructed"),
+        }
+
+        let raw_certificate =
+            serde_json::to_string(&certificate).expect("BUG: Cannot serialize certificate");
+        let raw_secret_key =
+            serde_json::to_string(&secret_key).expect("BUG: Cannot serialize secret key");
+        match ServerSecurityBundle::read_from_strings(&raw_certificate, &raw_secret_key) {
+            Err(Error::Noise(_)) => {}
+            _ => panic!("BUG: Bundle with mismatching keys loaded"),
+        }
+
+        let raw_bundle = format!(
+            r#"{{"certificate": {}, "secret_key": {}}}"#,
+            raw_certificate, raw_secret_key
+        );
+        match ServerSecurityBundle::read_from_string(&raw_bundle) {
+            Err(Error::Noise(_)) => {}
+            _ => panic!("BUG: Bundle with mismatching keys loaded"),
+        }
+    }
+
+    #[test]
+    fn certificate_validate_secret_key() {
+        let (signed_part, _authority_keypair, static_keypair, signature) =
+            build_test_signed_part_and_auth();
+        let certificate = Certificate::new(signed_part, signature);
+        let secret_key = StaticSecretKeyFormat::new(static_keypair.private);
+        certificate
+            .validate_secret_key(&secret_key)
+            .expect("BUG: Validation failed for matching secret key");
+
+        let other_keypair = noise::generate_keypair().expect("BUG: Failed to generate keypair");
+        match certificate.validate_secret_key(&StaticSecretKeyFormat::new(other_keypair.private)) {
+            Err(Error::Noise(_)) => {}
+            res => panic!(
+                "BUG: Unexpected result for mismatching secret key: {:?}",
+                res
+            ),
+        }
+    }
+
+    #[test]
+    fn certificate_time_to_expiry() {
+        const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+        let (_signed_part, authority_keypair, static_keypair, _signature) =
+            build_test_signed_part_and_auth();
+        let header = SignedPartHeader::new(0, 1_000_000_000);
+        let not_valid_after = header.not_valid_after();
+        let signed_part = SignedPart::new(
+            header,
+            static_keypair.public.clone(),
+            authority_keypair.public,
+        );
+        let signature = signed_part
+            .sign_with(&authority_keypair)
+            .expect("BUG: Failed to sign certificate");
+        let certificate = Certificate::new(signed_part, signature);
+
+        let ten_days_before = not_valid_after - 10 * DAY;
+        assert_eq!(certificate.time_to_expiry(ten_days_before), Some(10 * DAY));
+        assert!(!certificate.is_expiring_within(ten_days_before, 7 * DAY));
+        assert!(certificate.is_expiring_within(ten_days_before, 11 * DAY));
+
+        // The certificate is still valid exactly at the not_valid_after time
+        certificate
+            .validate(|| not_valid_after)
+            .expect("BUG: Certificate not valid at not_valid_after");
+        assert_eq!(
+            certificate.time_to_expiry(not_valid_after),
+            Some(Duration::from_secs(0))
+        );
+        assert!(certificate.is_expiring_within(not_valid_after, Duration::from_secs(1)));
+        assert!(!certificate.is_expiring_within(not_valid_after, Duration::from_secs(0)));
+
+        let expired = not_valid_after + Duration::from_secs(1);
+        assert_eq!(certificate.time_to_expiry(expired), None);
+        assert!(certificate.is_expiring_within(expired, Duration::from_secs(0)));
+
+        let bundle = ServerSecurityBundle::new(
+            certificate,
+            StaticSecretKeyFormat::new(static_keypair.private),
+        )
+        .expect("BUG: Cannot create bundle");
+        assert_eq!(bundle.time_to_expiry(ten_days_before), Some(10 * DAY));
+        assert!(bundle.is_expiring_within(ten_days_before, 11 * DAY));
+    }
+
+    #[test]
+    fn certificate_chain() {
+        let root = ed25519_dalek::Keypair::generate(&mut rand::rngs::OsRng {});
+        let intermediate = ed25519_dalek::Keypair::generate(&mut rand::rngs::OsRng {});
+        let static_keypair = noise::generate_keypair().expect("BUG: Failed to generate keypair");
+        let issue =
+            |header: SignedPartHeader, pubkey: Vec<u8>, authority: &ed25519_dalek::Keypair| {
+                let signed_part = SignedPart::new(header, pubkey, authority.public);
+                let signature = signed_part
+                    .sign_with(authority)
+                    .expect("BUG: Failed to sign certificate");
+                Certificate::new(signed_part, signature)
+            };
+
+        let leaf = issue(
+            SignedPartHeader::new(0, u32::MAX),
+            static_keypair.public,
+            &intermediate,
+        );
+        let intermediate_header = SignedPartHeader::new(0, u32::MAX - 1);
+        let intermediate_expiration = intermediate_header.not_valid_after();
+        let intermediate_certificate = issue(
+            intermediate_header,
+            intermediate.public.as_bytes().to_vec(),
+            &root,
+        );
+        let chain = CertificateChain::new(vec![leaf.clone(), intermediate_certificate]);
+        let trusted_roots = [Ed25519PublicKeyFormat::new(root.public)];
+        assert_eq!(
+            chain
+                .validate_chain(&trusted_roots, SystemTime::now)
+                .expect("BUG: Valid chain rejected"),
+            intermediate_expiration
+        );
+
+        let serialized = String::try_from(chain.clone()).expect("BUG: Cannot serialize chain");
+        assert!(serialized.starts_with('['));
+        assert_eq!(
+            chain,
+            CertificateChain::try_from(serialized).expect("BUG: Cannot deserialize chain")
+        );
+
+        // Untrusted root
+        let other_root = ed25519_dalek::Keypair::generate(&mut rand::rngs::OsRng {});
+        chain
+            .validate_chain(
+                &[Ed25519PublicKeyFormat::new(other_root.public)],
+                SystemTime::now,
+            )
+            .expect_err("BUG: Chain with untrusted root accepted");
+        // Missing intermediate certificate
+        CertificateChain::new(vec![leaf.clone()])
+            .validate_chain(&trusted_roots, SystemTime::now)
+            .expect_err("BUG: Chain without intermediate accepted");
+        CertificateChain::new(vec![])
+            .validate_chain(&trusted_roots, SystemTime::now)
+            .expect_err("BUG: Empty chain accepted");
+        // Broken link, the intermediate certificate certifies a different authority
+        let wrong_intermediate = issue(
+            SignedPartHeader::new(0, u32::MAX),
+            other_root.public.as_bytes().to_vec(),
+            &root,
+        );
+        CertificateChain::new(vec![leaf.clone(), wrong_intermediate])
+            .validate_chain(&trusted_roots, SystemTime::now)
+            .expect_err("BUG: Broken chain accepted");
+        // Expired intermediate certificate
+        let expired_intermediate = issue(
+            SignedPartHeader::new(0, 1),
+            intermediate.public.as_bytes().to_vec(),
+            &root,
+        );
+        CertificateChain::new(vec![leaf, expired_intermediate])
+            .validate_chain(&trusted_roots, SystemTime::now)
+            .expect_err("BUG: Chain with expired intermediate accepted");
+    }
+
+    #[test]
+    fn pem_serialization() {
+        let (signed_part, _authority_keypair, static_keypair, signature) =
+            build_test_signed_part_and_auth();
+        let certificate = Certificate::new(signed_part, signature);
+
+        let pem = certificate
+            .to_pem()
+            .expect("BUG: Cannot serialize certificate");
+        assert!(pem.starts_with("-----BEGIN SV2 CERTIFICATE-----\n"));
+        assert!(pem.ends_with("-----END SV2 CERTIFICATE-----\n"));
+        assert!(pem.lines().all(|line| line.len() <= 64));
+        let padded_pem = format!("\n  {}\n\n", pem);
+        assert_eq!(
+            certificate,
+            Certificate::from_pem(&padded_pem).expect("BUG: Cannot parse certificate")
+        );
+
+        let bundle = ServerSecurityBundle::new(
+            certificate,
+            StaticSecretKeyFormat::new(static_keypair.private),
+        )
+        .expect("BUG: Cannot create bundle");
+        let bundle_pem = bundle.to_pem().expect("BUG: Cannot serialize bundle");
+        assert!(bundle_pem.starts_with("-----BEGIN SV2 SERVER SECURITY BUNDLE-----\n"));
+        assert_eq!(
+            bundle,
+            ServerSecurityBundle::from_pem(&bundle_pem).expect("BUG: Cannot parse bundle")
+        );
+
+        // Mismatched labels are rejected
+        match ServerSecurityBundle::from_pem(&pem) {
+            Err(Error::Noise(_)) => {}
+            _ => panic!("BUG: Certificate parsed as a bundle"),
+        }
+        match Certificate::from_pem(&bundle_pem) {
+            Err(Error::Noise(_)) => {}
+            _ => panic!("BUG: Bundle parsed as a certificate"),
+        }
+    }
+
     #[test]
     fn certificate_serialization() {
         let (signed_part, _authority_keypair, _static_keypair, signature) =
@@ -64,6 +64,26 @@ pub(super) enum StepResult {
     Done(Option<super::auth::Certificate>),
 }
 
+/// Limits the time spent in the noise handshake. Exceeding any of the limits fails the handshake
+/// with `Error::NoiseHandshakeTimeout` so that slow peers can be told apart from authentication
+/// failures.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HandshakeTimeout {
+    /// Maximum time for sending or receiving a single handshake message
+    pub message: time::Duration,
+    /// Maximum time for the whole handshake
+    pub total: time::Duration,
+}
+
+impl Default for HandshakeTimeout {
+    fn default() -> Self {
+        Self {
+            message: time::Duration::from_secs(2),
+            total: time::Duration::from_secs(10),
+        }
+    }
+}
+
 /// Objects that can perform 1 handshake step implement this trait
 pub(super) trait Step {
     /// Proceeds with the handshake and processes an optional incoming message - `in_msg` and
@@ -76,6 +96,11 @@ pub(super) trait Step {
 
     /// Transforms step into the handshake state
     fn into_handshake_state(self) -> HandshakeState;
+
+    /// Time limits of the handshake driven by this step
+    fn handshake_timeout(&self) -> HandshakeTimeout {
+        HandshakeTimeout::default()
+    }
 }
 
 /// The purpose of this object is to interpret the `StepResult` instructions while driving the
@@ -89,20 +114,26 @@ impl<T> Handshake<T>
 where
     T: Step,
 {
-    const HANDSHAKE_TIMEOUT: time::Duration = time::Duration::from_secs(2);
-
     pub(super) fn new(handshake_step: T) -> Self {
         Self { handshake_step }
     }
 
+    /// Helper that awaits `future` within the time limit for a single handshake message
+    async fn within_message_timeout<F: Future>(&self, future: F) -> Result<F::Output> {
+        let timeout = self.handshake_step.handshake_timeout().message;
+        future
+            .timeout(timeout)
+            .await
+            .map_err(|_| Error::NoiseHandshakeTimeout(timeout))
+    }
+
     /// Helper that receives 1 handshake message
     async fn receive_message(
         &self,
         handshake_stream: &mut super::NoiseFramedTcpStream,
     ) -> Result<Message> {
-        let handshake_frame: BytesMut = handshake_stream
-            .next()
-            .timeout(Self::HANDSHAKE_TIMEOUT)
+        let handshake_frame: BytesMut = self
+            .within_message_timeout(handshake_stream.next())
             .await?
             // Convert optional frame into an error, unwrap it, and unwrap the
             // payload, too
@@ -110,9 +141,22 @@ where
         Ok(Message::new(handshake_frame))
     }
 
+    /// Drives the handshake within the total time limit. The caller is expected to drop
+    /// `handshake_stream` on error which closes the connection.
     pub(super) async fn complete_handshake(
         &mut self,
         handshake_stream: &mut super::NoiseFramedTcpStream,
+    ) -> Result<Option<super::auth::Certificate>> {
+        let timeout = self.handshake_step.handshake_timeout().total;
+        self.run_steps(handshake_stream)
+            .timeout(timeout)
+            .await
+            .map_err(|_| Error::NoiseHandshakeTimeout(timeout))?
+    }
+
+    async fn run_steps(
+        &mut self,
+        handshake_stream: &mut super::NoiseFramedTcpStream,
     ) -> Result<Option<super::auth::Certificate>> {
         let mut in_msg: Option<Message> = None;
 
@@ -133,18 +177,14 @@ where
                 }
                 // Send out specified messages and wait for response
                 StepResult::ExpectReply(out_msg) => {
-                    handshake_stream
-                        .send(out_msg.inner)
-                        .timeout(Self::HANDSHAKE_TIMEOUT)
+                    self.within_message_timeout(handshake_stream.send(out_msg.inner))
                         .await??;
 
                     let handshake_message = self.receive_message(handshake_stream).await?;
                     (&mut in_msg).replace(handshake_message);
                 }
                 StepResult::NoMoreReply(out_msg) => {
-                    handshake_stream
-                        .send(out_msg.inner)
-                        .timeout(Self::HANDSHAKE_TIMEOUT)
+                    self.within_message_timeout(handshake_stream.send(out_msg.inner))
                         .await??;
                 }
                 // Initiator is now finalized
@@ -28,17 +28,23 @@ use std::task::{Context, Poll};
 use crate::{tokio, tokio_util};
 
 use futures::prelude::*;
+use futures::ready;
 use pin_project::pin_project;
 use tokio::net::{TcpStream, ToSocketAddrs};
 use tokio_util::codec::{Framed, FramedParts};
 
 use crate::framing::Framing;
+use crate::proxy;
 
 #[pin_project]
 #[derive(Debug)]
 pub struct Connection<F: Framing> {
     #[pin]
     pub framed_stream: Framed<TcpStream, F::Codec>,
+    /// Application data carried over from the `ProxyStream` this connection was converted from
+    pub(crate) extensions: proxy::Extensions,
+    /// Information from the PROXY header of the `ProxyStream` this connection was converted from
+    pub(crate) proxy_info: Option<proxy::ProxyInfo>,
 }
 
 impl<F: Framing> Connection<F> {
@@ -46,7 +52,11 @@ impl<F: Framing> Connection<F> {
     pub fn new(stream: TcpStream) -> Self {
         let framed_stream = Framed::new(stream, F::Codec::default());
 
-        Self { framed_stream }
+        Self {
+            framed_stream,
+            extensions: proxy::Extensions::new(),
+            proxy_info: None,
+        }
     }
 
     /// Create a new `Connection` from `FramedParts`.
@@ -57,7 +67,25 @@ impl<F: Framing> Connection<F> {
         new_parts.read_buf = parts.read_buf;
         new_parts.write_buf = parts.write_buf;
         let framed_stream = Framed::from_parts(new_parts);
-        Self { framed_stream }
+        Self {
+            framed_stream,
+            extensions: proxy::Extensions::new(),
+            proxy_info: None,
+        }
+    }
+
+    pub fn extensions(&self) -> &proxy::Extensions {
+        &self.extensions
+    }
+
+    pub fn extensions_mut(&mut self) -> &mut proxy::Extensions {
+        &mut self.extensions
+    }
+
+    /// Information from the PROXY header, available when the connection has been converted from
+    /// a `ProxyStream` that has received the header
+    pub fn proxy_info(&self) -> Option<&proxy::ProxyInfo> {
+        self.proxy_info.as_ref()
     }
 
     pub fn codec_mut(&mut self) -> &mut F::Codec {
@@ -79,6 +107,29 @@ impl<F: Framing> Connection<F> {
         self.framed_stream.get_ref().peer_addr()
     }
 
+    /// Writes PROXY protocol header of given `version` directly into the underlying TCP stream,
+    /// bypassing the codec. This has to be done before any framed message is sent as the
+    /// header must be the very first data the backend receives.
+    pub async fn write_proxy_header(
+        &mut self,
+        version: proxy::ProtocolVersion,
+        original_source: Option<SocketAddr>,
+        original_destination: Option<SocketAddr>,
+    ) -> proxy::error::Result<()> {
+        if !self.framed_stream.write_buffer().is_empty() {
+            return Err(proxy::error::Error::InvalidState(
+                "PROXY header must be written before any framed data".into(),
+            ));
+        }
+        proxy::Connector::new(version)
+            .write_proxy_header(
+                self.framed_stream.get_mut(),
+                original_source,
+                original_destination,
+            )
+            .await
+    }
+
     pub fn into_inner(self) -> Framed<TcpStream, F::Codec> {
         self.framed_stream
     }
@@ -90,11 +141,30 @@ impl<F: Framing> From<TcpStream> for Connection<F> {
     }
 }
 
+/// Frames are decoded by `Framed` including the final frames the codec produces at the end of
+/// the stream (`Decoder::decode_eof()`). Bytes the codec leaves undecoded when the stream ends
+/// indicate a connection closed in the middle of a frame. Such connection results in an error of
+/// `io::ErrorKind::UnexpectedEof` kind rather than in a plain end of the stream.
 impl<F: Framing> Stream for Connection<F> {
     type Item = Result<F::Rx, F::Error>;
 
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
-        self.project().framed_stream.poll_next(cx)
+        let mut framed_stream = self.project().framed_stream;
+        match ready!(framed_stream.as_mut().poll_next(cx)) {
+            None if !framed_stream.read_buffer().is_empty() => {
+                let error = io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!(
+                        "Connection closed in the middle of a frame, {} bytes left",
+                        framed_stream.read_buffer().len()
+                    ),
+                );
+                // The incomplete frame is reported only once, the stream ends afterwards
+                framed_stream.read_buffer_mut().clear();
+                Poll::Ready(Some(Err(error.into())))
+            }
+            result => Poll::Ready(result),
+        }
     }
 }
 
@@ -117,3 +187,176 @@ impl<F: Framing> Sink<F::Tx> for Connection<F> {
         self.project().framed_stream.poll_close(cx)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::BytesMut;
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpListener;
+    use tokio_util::codec::{Decoder, Encoder, LinesCodec, LinesCodecError};
+
+    #[derive(Debug)]
+    struct TestFraming;
+
+    impl Framing for TestFraming {
+        type Tx = String;
+        type Rx = String;
+        type Error = LinesCodecError;
+        type Codec = LinesCodec;
+    }
+
+    /// Same as `LinesCodec` but an unterminated line at the end of the stream is not decoded
+    #[derive(Debug, Default)]
+    struct TerminatedLinesCodec(LinesCodec);
+
+    impl Decoder for TerminatedLinesCodec {
+        type Item = String;
+        type Error = LinesCodecError;
+
+        fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<String>, LinesCodecError> {
+            self.0.decode(buf)
+        }
+
+        fn decode_eof(&mut self, buf: &mut BytesMut) -> Result<Option<String>, LinesCodecError> {
+            self.0.decode(buf)
+        }
+    }
+
+    impl Encoder<String> for TerminatedLinesCodec {
+        type Error = LinesCodecError;
+
+        fn encode(&mut self, line: String, buf: &mut BytesMut) -> Result<(), LinesCodecError> {
+            self.0.encode(line, buf)
+        }
+    }
+
+    #[derive(Debug)]
+    struct TerminatedLinesFraming;
+
+    impl Framing for TerminatedLinesFraming {
+        type Tx = String;
+        type Rx = String;
+        type Error = LinesCodecError;
+        type Codec = TerminatedLinesCodec;
+    }
+
+    /// Sends `data` over a new connection and closes it, returns the accepted connection
+    async fn connection_closed_after<F: Framing>(data: &'static [u8]) -> Connection<F> {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("BUG: Cannot bind listener");
+        let addr = listener
+            .local_addr()
+            .expect("BUG: Cannot get local address");
+        tokio::spawn(async move {
+            let mut stream = TcpStream::connect(addr).await.expect("BUG: Cannot connect");
+            stream.write_all(data).await.expect("BUG: Cannot write");
+        });
+        let (stream, _) = listener.accept().await.expect("BUG: Cannot accept");
+        Connection::new(stream)
+    }
+
+    #[tokio::test]
+    async fn clean_close() {
+        let mut connection: Connection<TestFraming> =
+            connection_closed_after(b"first\nsecond\n").await;
+        for expected in &["first", "second"] {
+            let frame = connection
+                .next()
+                .await
+                .expect("BUG: Stream ended early")
+                .expect("BUG: Cannot decode frame");
+            assert_eq!(frame, *expected);
+        }
+        assert!(connection.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn mid_frame_close() {
+        let mut connection: Connection<TerminatedLinesFraming> =
+            connection_closed_after(b"first\nseco").await;
+        let frame = connection
+            .next()
+            .await
+            .expect("BUG: Stream ended early")
+            .expect("BUG: Cannot decode frame");
+        assert_eq!(frame, "first");
+        match connection.next().await {
+            Some(Err(LinesCodecError::Io(e))) => {
+                assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof)
+            }
+            result => panic!("BUG: Unexpected result: {:?}", result),
+        }
+        assert!(connection.next().await.is_none());
+    }
+
+    /// Codecs that decode a final frame from the remaining bytes at the end of the stream keep
+    /// working
+    #[tokio::test]
+    async fn final_frame_at_eof() {
+        let mut connection: Connection<TestFraming> = connection_closed_after(b"first\nlast").await;
+        for expected in &["first", "last"] {
+            let frame = connection
+                .next()
+                .await
+                .expect("BUG: Stream ended early")
+                .expect("BUG: Cannot decode frame");
+            assert_eq!(frame, *expected);
+        }
+        assert!(connection.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn write_proxy_header() {
+        use proxy::WithProxyInfo;
+
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("BUG: Cannot bind listener");
+        let backend_addr = listener
+            .local_addr()
+            .expect("BUG: Cannot get local address");
+        let src = "10.0.0.1:1111"
+            .parse::<SocketAddr>()
+            .expect("BUG: Cannot parse IP");
+        let dest = "10.0.0.2:2222"
+            .parse::<SocketAddr>()
+            .expect("BUG: Cannot parse IP");
+
+        let backend = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.expect("BUG: Cannot accept");
+            let ps = proxy::Acceptor::new()
+                .require_proxy_header(true)
+                .accept_auto(stream)
+                .await
+                .expect("BUG: Invalid PROXY header received");
+            assert_eq!(Some(src), ps.original_peer_addr());
+            assert_eq!(Some(dest), ps.original_destination_addr());
+            let mut connection = Connection::<TestFraming>::from(ps);
+            connection
+                .next()
+                .await
+                .expect("BUG: Unexpected end of stream")
+                .expect("BUG: Cannot read message")
+        });
+
+        let mut connection = Connection::<TestFraming>::connect(backend_addr)
+            .await
+            .expect("BUG: Cannot connect");
+        connection
+            .write_proxy_header(proxy::ProtocolVersion::V2, Some(src), Some(dest))
+            .await
+            .expect("BUG: Cannot write proxy header");
+        connection
+            .send("HELLO".to_string())
+            .await
+            .expect("BUG: Cannot send message");
+
+        assert_eq!(
+            "HELLO",
+            backend.await.expect("BUG: Backend task failed"),
+            "BUG: Message didn't flow successfully"
+        );
+    }
+}
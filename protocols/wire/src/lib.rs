@@ -25,7 +25,8 @@
 pub(crate) use tokio;
 pub(crate) use tokio_util;
 
-#[macro_use]
+// Logging macros are provided by `tracing` when the feature is enabled
+#[cfg_attr(not(feature = "tracing"), macro_use)]
 extern crate ii_logging;
 
 mod connection;
@@ -23,28 +23,43 @@
 //! Implements  [PROXY protocol](http://www.haproxy.org/download/1.8/doc/proxy-protocol.txt) in tokio
 
 use std::convert::TryInto;
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
 
 use bytes::Buf;
-use bytes::BytesMut;
-use futures::{Future, FutureExt, StreamExt};
+use bytes::{Bytes, BytesMut};
+use futures::future::Either;
+use futures::{Future, FutureExt, StreamExt, TryFutureExt};
 use pin_project::pin_project;
-use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::io::{
+    AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf,
+};
 use tokio::net::TcpStream;
 use tokio_util::codec::{Decoder, Encoder, Framed, FramedParts};
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "tracing")]
+use tracing::{debug, info, trace};
+
 use crate::connection::Connection;
 use crate::framing::Framing;
-use codec::{v1::V1Codec, v2::V2Codec, MAX_HEADER_SIZE};
+use codec::{v1::V1Codec, v2::V2Codec, HeaderCapture, MAX_HEADER_SIZE};
 use error::{Error, Result};
 
 pub mod codec;
 pub mod error;
-pub use codec::ProxyInfo;
+pub mod extensions;
+pub mod listener;
+pub mod observer;
+pub use codec::{v1::V1Parsing, ProxyInfo, SslInfo};
+pub use extensions::Extensions;
+pub use listener::{OpenConnection, ProxyListener};
+pub use observer::{AcceptCounters, AcceptCountersSnapshot, AcceptOutcome, ProxyObserver};
 use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
 
 const V1_TAG: &[u8] = b"PROXY ";
 const V2_TAG: &[u8] = codec::v2::SIGNATURE;
@@ -65,8 +80,25 @@ pub trait WithProxyInfo {
         None
     }
 
+    /// Returns path of original source of the connection when it has been relayed over unix
+    /// domain sockets
+    fn original_peer_unix(&self) -> Option<&Path> {
+        None
+    }
+
+    /// Returns path of original destination of the connection when it has been relayed over
+    /// unix domain sockets
+    fn original_destination_unix(&self) -> Option<&Path> {
+        None
+    }
+
     fn proxy_info(&self) -> Result<ProxyInfo> {
         use std::convert::TryFrom;
+        if let (Some(src), Some(dst)) =
+            (self.original_peer_unix(), self.original_destination_unix())
+        {
+            return Ok(ProxyInfo::from_unix(src, dst));
+        }
         let original_source = self.original_peer_addr();
         let original_destination = self.original_destination_addr();
         ProxyInfo::try_from((original_source, original_destination))
@@ -102,14 +134,28 @@ impl ProtocolConfig {
 }
 
 /// Struct to accept stream with PROXY header and extract information from it
+///
+/// A configured acceptor can process any number of streams via the `*_ref()` methods, e.g.
+/// in an accept loop of a server. Alternatively, it can be cloned for each stream.
+#[derive(Clone)]
 pub struct Acceptor {
     require_proxy_header: bool,
+    observer: Option<Arc<dyn ProxyObserver>>,
+    early_passthrough: bool,
+    header_timeout: Option<std::time::Duration>,
+    max_tlvs: usize,
+    v1_parsing: V1Parsing,
 }
 
 impl Default for Acceptor {
     fn default() -> Self {
         Acceptor {
             require_proxy_header: false,
+            observer: None,
+            early_passthrough: false,
+            header_timeout: None,
+            max_tlvs: codec::v2::tlv::DEFAULT_MAX_TLVS,
+            v1_parsing: V1Parsing::default(),
         }
     }
 }
@@ -123,63 +169,264 @@ impl Acceptor {
     /// create [`ProxyStream`] with appropriate information in it.
     ///
     /// This method may block for ~2 secs until stream timeout is triggered when performing
-    /// autodetection and waiting for `COMMON_HEADER_PREFIX_LEN` bytes to arrive.
-    pub async fn accept_auto<T>(self, mut stream: T) -> Result<ProxyStream<T>>
+    /// autodetection and waiting for `COMMON_HEADER_PREFIX_LEN` bytes to arrive. The whole header
+    /// decoding can be bounded by `with_header_timeout()`.
+    pub async fn accept_auto<T>(self, stream: T) -> Result<ProxyStream<T>>
+    where
+        T: AsyncRead + Send + Unpin,
+    {
+        self.accept_auto_ref(stream).await
+    }
+
+    /// Same as `accept_auto` but the acceptor is not consumed and can be reused
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "accept_auto", skip_all)
+    )]
+    pub async fn accept_auto_ref<T>(&self, stream: T) -> Result<ProxyStream<T>>
+    where
+        T: AsyncRead + Send + Unpin,
+    {
+        self.within_header_timeout(self.decode_auto(stream)).await
+    }
+
+    async fn decode_auto<T>(&self, mut stream: T) -> Result<ProxyStream<T>>
     where
         T: AsyncRead + Send + Unpin,
     {
         trace!("wire: Accepting stream, autodetecting PROXY protocol version ");
         let mut buf = BytesMut::with_capacity(MAX_HEADER_SIZE);
-        // This loop will block for ~2 seconds (read_buf() timeout) if less than
-        // COMMON_HEADER_PREFIX_LEN have arrived
-        while buf.len() < Self::COMMON_HEADER_PREFIX_LEN {
-            let r = stream.read_buf(&mut buf).await?;
-            trace!("wire: Read {} bytes from stream", r);
-            if r == 0 {
-                trace!("wire: no more bytes supplied in the stream, terminating read");
-                break;
-            }
-        }
+        self.read_prefix(&mut stream, &mut buf).await?;
 
         if buf.remaining() < Self::COMMON_HEADER_PREFIX_LEN {
-            return self.try_from_stream_to_proxy_stream(stream, buf);
+            return self.try_from_stream_to_proxy_stream(stream, buf, "No PROXY header detected");
         }
         debug!("wire: Buffered initial {} bytes", buf.remaining());
 
         if buf[0..Self::COMMON_HEADER_PREFIX_LEN] == V1_TAG[0..Self::COMMON_HEADER_PREFIX_LEN] {
             debug!("wire: Detected proxy protocol v1 tag");
-            self.accept_with_codec(Some(buf), stream, V1Codec::new())
-                .await
+            self.accept_with_codec(
+                Some(buf),
+                stream,
+                V1Codec::new().parsing(self.v1_parsing),
+                ProtocolVersion::V1,
+            )
+            .await
         } else if buf[0..Self::COMMON_HEADER_PREFIX_LEN]
             == V2_TAG[0..Self::COMMON_HEADER_PREFIX_LEN]
         {
             debug!("wire: Detected proxy protocol v2 tag");
-            self.accept_with_codec(Some(buf), stream, V2Codec::new())
-                .await
+            self.accept_with_codec(
+                Some(buf),
+                stream,
+                V2Codec::new().max_tlvs(self.max_tlvs),
+                ProtocolVersion::V2,
+            )
+            .await
         } else {
-            self.try_from_stream_to_proxy_stream(stream, buf)
+            self.try_from_stream_to_proxy_stream(stream, buf, "No PROXY header detected")
+        }
+    }
+
+    /// Reads from `stream` into `buf` until `COMMON_HEADER_PREFIX_LEN` bytes are available, the
+    /// stream ends or (with `early_passthrough`) the bytes cannot start a PROXY header
+    async fn read_prefix<T>(&self, stream: &mut T, buf: &mut BytesMut) -> Result<()>
+    where
+        T: AsyncRead + Send + Unpin,
+    {
+        // This loop will block for ~2 seconds (read_buf() timeout) if less than
+        // COMMON_HEADER_PREFIX_LEN have arrived
+        while buf.len() < Self::COMMON_HEADER_PREFIX_LEN {
+            let r = stream.read_buf(buf).await?;
+            trace!("wire: Read {} bytes from stream", r);
+            if r == 0 {
+                trace!("wire: no more bytes supplied in the stream, terminating read");
+                break;
+            }
+            if self.early_passthrough && !Self::may_be_header(buf) {
+                trace!("wire: Received bytes cannot start a PROXY header, terminating read");
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Same as `accept_auto` for streams that are already wrapped in a buffered reader, e.g.
+    /// `tokio::io::BufReader`. Note, that the reader itself has to be passed in, not the stream
+    /// it wraps, otherwise bytes stranded in the reader's buffer are lost.
+    ///
+    /// The reader's buffer is inspected through the `AsyncBufRead` interface first. If it
+    /// already shows that no PROXY header is present, the reader is passed on untouched, i.e.
+    /// no buffered bytes are moved into the `ProxyStream`. Otherwise the header is decoded by
+    /// `accept_auto`, which consumes the buffered bytes before reading the underlying stream.
+    /// Filling the reader's buffer is bounded by `with_header_timeout()` as well.
+    pub async fn accept_auto_buffered<R>(self, stream: R) -> Result<ProxyStream<R>>
+    where
+        R: AsyncBufRead + Send + Unpin,
+    {
+        self.within_header_timeout(self.decode_auto_buffered(stream))
+            .await
+    }
+
+    async fn decode_auto_buffered<R>(&self, mut stream: R) -> Result<ProxyStream<R>>
+    where
+        R: AsyncBufRead + Send + Unpin,
+    {
+        let prefix_len = Self::COMMON_HEADER_PREFIX_LEN;
+        let buffered = stream.fill_buf().await?;
+        if buffered.len() >= prefix_len
+            && buffered[0..prefix_len] != V1_TAG[0..prefix_len]
+            && buffered[0..prefix_len] != V2_TAG[0..prefix_len]
+        {
+            return self.try_from_stream_to_proxy_stream(
+                stream,
+                BytesMut::new(),
+                "No PROXY header detected",
+            );
+        }
+        self.decode_auto(stream).await
+    }
+
+    /// Same as `accept_auto` but the handshake is aborted with `Error::Cancelled` once `cancel`
+    /// resolves. Any future that resolves on shutdown can be used, see
+    /// `accept_auto_until_halted()` for a `Tripwire` of a `HaltHandle`.
+    pub async fn accept_auto_cancellable<T, C>(self, stream: T, cancel: C) -> Result<ProxyStream<T>>
+    where
+        T: AsyncRead + Send + Unpin,
+        C: Future<Output = ()>,
+    {
+        tokio::select! {
+            res = self.accept_auto(stream) => res,
+            _ = cancel => {
+                debug!("wire: Accepting stream cancelled");
+                Err(Error::Cancelled)
+            }
+        }
+    }
+
+    /// Same as `accept_auto_cancellable` but the handshake is aborted once `tripwire` of a
+    /// `HaltHandle` fires, regardless of its halt reason
+    #[cfg(feature = "ii-async-utils")]
+    pub async fn accept_auto_until_halted<T, R>(
+        self,
+        stream: T,
+        tripwire: ii_async_utils::Tripwire<R>,
+    ) -> Result<ProxyStream<T>>
+    where
+        T: AsyncRead + Send + Unpin,
+        R: ii_async_utils::HaltReason,
+    {
+        self.accept_auto_cancellable(stream, tripwire.map(|_| ()))
+            .await
+    }
+
+    /// Accepts the PROXY header like `accept_auto` and then reads the first application frame
+    /// decoded by `codec`. The whole pipeline is bounded by `deadline` so that a client can't
+    /// stall the handshake by delaying each phase just under its individual timeout,
+    /// `Error::Timeout` is returned once the deadline passes. Data received after the first
+    /// frame remain buffered in the returned stream.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "accept_connection", skip_all)
+    )]
+    pub async fn accept_connection<T, C>(
+        self,
+        stream: T,
+        codec: &mut C,
+        deadline: std::time::Duration,
+    ) -> Result<(ProxyStream<T>, C::Item)>
+    where
+        T: AsyncRead + Send + Unpin,
+        C: Decoder,
+        C::Error: std::fmt::Display,
+    {
+        let accept = async move {
+            let mut stream = self.accept_auto(stream).await?;
+            let frame = Self::read_first_frame(&mut stream, codec).await?;
+            Ok((stream, frame))
+        };
+        tokio::time::timeout(deadline, accept).await.map_err(|_| {
+            debug!("wire: Accepting connection timed out");
+            Error::Timeout(deadline)
+        })?
+    }
+
+    /// Decodes a frame from data buffered in `stream`, reading more data as needed
+    async fn read_first_frame<T, C>(stream: &mut ProxyStream<T>, codec: &mut C) -> Result<C::Item>
+    where
+        T: AsyncRead + Unpin,
+        C: Decoder,
+        C::Error: std::fmt::Display,
+    {
+        loop {
+            if let Some(frame) = codec
+                .decode(&mut stream.buf)
+                .map_err(|e| Error::Framing(e.to_string()))?
+            {
+                return Ok(frame);
+            }
+            if stream.inner.read_buf(&mut stream.buf).await? == 0 {
+                return codec
+                    .decode_eof(&mut stream.buf)
+                    .map_err(|e| Error::Framing(e.to_string()))?
+                    .ok_or_else(|| Error::Io(std::io::ErrorKind::UnexpectedEof.into()));
+            }
         }
     }
 
     pub async fn accept_v1<T>(self, stream: T) -> Result<ProxyStream<T>>
+    where
+        T: AsyncRead + Send + Unpin,
+    {
+        self.accept_v1_ref(stream).await
+    }
+
+    /// Same as `accept_v1` but the acceptor is not consumed and can be reused
+    pub async fn accept_v1_ref<T>(&self, stream: T) -> Result<ProxyStream<T>>
     where
         T: AsyncRead + Send + Unpin,
     {
         debug!("wire: Accepting stream, decoding PROXY protocol V1");
-        self.accept_with_codec(None, stream, V1Codec::new()).await
+        self.within_header_timeout(self.accept_with_codec(
+            None,
+            stream,
+            V1Codec::new().parsing(self.v1_parsing),
+            ProtocolVersion::V1,
+        ))
+        .await
     }
 
     pub async fn accept_v2<T>(self, stream: T) -> Result<ProxyStream<T>>
+    where
+        T: AsyncRead + Send + Unpin,
+    {
+        self.accept_v2_ref(stream).await
+    }
+
+    /// Same as `accept_v2` but the acceptor is not consumed and can be reused
+    pub async fn accept_v2_ref<T>(&self, stream: T) -> Result<ProxyStream<T>>
     where
         T: AsyncRead + Send + Unpin,
     {
         debug!("wire: Accepting stream, decoding PROXY protocol V2");
-        self.accept_with_codec(None, stream, V2Codec::new()).await
+        self.within_header_timeout(self.accept_with_codec(
+            None,
+            stream,
+            V2Codec::new().max_tlvs(self.max_tlvs),
+            ProtocolVersion::V2,
+        ))
+        .await
     }
 
     /// Conditionally convert the stream as long as the proxy header is not required or return an
-    /// error
-    fn try_from_stream_to_proxy_stream<T>(&self, stream: T, buf: BytesMut) -> Result<ProxyStream<T>>
+    /// error. `reason` describes why no header has been accepted, it is reported to the observer
+    /// when the stream is refused.
+    fn try_from_stream_to_proxy_stream<T>(
+        &self,
+        stream: T,
+        buf: BytesMut,
+        reason: &str,
+    ) -> Result<ProxyStream<T>>
     where
         T: AsyncRead + Unpin,
     {
@@ -189,53 +436,123 @@ impl Acceptor {
         );
         if self.require_proxy_header {
             debug!("wire: Proxy protocol is required");
+            self.reject(reason, &buf);
             Err(Error::Proxy("Proxy protocol is required".into()))
         } else {
             debug!("wire: No proxy protocol detected, just passing the stream");
+            self.observe(AcceptOutcome::NoHeader);
             Ok(ProxyStream {
                 inner: stream,
                 buf,
                 orig_source: None,
                 orig_destination: None,
+                orig_source_unix: None,
+                orig_destination_unix: None,
+                tlvs: Vec::new(),
+                header_bytes: Bytes::new(),
+                protocol_version: None,
+                extensions: Extensions::new(),
+                trusted_peer: true,
             })
         }
     }
 
-    /// Accept a PROXY protocol of version defined by `codec`. This helper method takes care of
-    /// constructing Framed `read_buf`
+    /// Reports `outcome` to the observer, if any
+    fn observe(&self, outcome: AcceptOutcome) {
+        if let Some(observer) = &self.observer {
+            observer.on_accept(outcome);
+        }
+    }
+
+    /// Fails `decode` with `Error::Proxy` if it doesn't complete within `header_timeout`
+    async fn within_header_timeout<T, F>(&self, decode: F) -> Result<ProxyStream<T>>
+    where
+        F: Future<Output = Result<ProxyStream<T>>>,
+    {
+        match self.header_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, decode)
+                .await
+                .unwrap_or_else(|_| {
+                    debug!("wire: PROXY header read timed out");
+                    self.reject("Header read timed out", &[]);
+                    Err(Error::Proxy("header read timed out".into()))
+                }),
+            None => decode.await,
+        }
+    }
+
+    /// Reports a refused stream to the observer, if any, along with the beginning of the
+    /// rejected `bytes`
+    fn reject(&self, reason: &str, bytes: &[u8]) {
+        if let Some(observer) = &self.observer {
+            observer.on_accept(AcceptOutcome::Rejected);
+            let len = bytes.len().min(observer::REJECTED_BYTES_LIMIT);
+            observer.on_reject(reason, &bytes[..len]);
+        }
+    }
+
+    /// Accept a PROXY protocol of `version` decoded by `codec`. This helper method takes care of
+    /// constructing Framed `read_buf`. The codecs hold no allocations, therefore a new instance
+    /// is created for each stream rather than reusing one. The `version` is reported to the
+    /// observer and recorded in the resulting stream when the header is successfully decoded.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            name = "decode",
+            skip_all,
+            fields(version = ?version, consumed = tracing::field::Empty)
+        )
+    )]
     async fn accept_with_codec<C, T>(
         &self,
         read_buf: Option<BytesMut>,
         stream: T,
         codec: C,
+        version: ProtocolVersion,
     ) -> Result<ProxyStream<T>>
     where
         T: AsyncRead + Unpin,
         C: Encoder<ProxyInfo> + Decoder<Item = ProxyInfo, Error = Error>,
     {
-        let mut framed_parts = FramedParts::new(stream, codec);
+        let mut framed_parts = FramedParts::new(stream, HeaderCapture::new(codec));
         if let Some(read_buf) = read_buf {
             framed_parts.read_buf = read_buf;
         }
         let mut framed = Framed::from_parts(framed_parts);
 
-        let proxy_info_result = framed
-            .next()
-            .await
-            .ok_or_else(|| Error::Proxy("Stream terminated".into()))?;
+        let proxy_info_result = match framed.next().await {
+            Some(proxy_info_result) => proxy_info_result,
+            None => {
+                self.reject("Stream terminated", framed.read_buffer());
+                return Err(Error::Proxy("Stream terminated".into()));
+            }
+        };
 
         let parts = framed.into_parts();
 
         match proxy_info_result {
-            Ok(proxy_info) => Ok(ProxyStream {
-                inner: parts.io,
-                buf: parts.read_buf,
-                orig_source: proxy_info.original_source,
-                orig_destination: proxy_info.original_destination,
-            }),
+            Ok(proxy_info) => {
+                self.observe(match version {
+                    ProtocolVersion::V1 => AcceptOutcome::V1,
+                    ProtocolVersion::V2 => AcceptOutcome::V2,
+                });
+                Ok(ProxyStream {
+                    inner: parts.io,
+                    buf: parts.read_buf,
+                    orig_source: proxy_info.original_source,
+                    orig_destination: proxy_info.original_destination,
+                    orig_source_unix: proxy_info.original_source_unix().map(Into::into),
+                    orig_destination_unix: proxy_info.original_destination_unix().map(Into::into),
+                    tlvs: proxy_info.tlvs,
+                    header_bytes: parts.codec.into_consumed(),
+                    protocol_version: Some(version),
+                    extensions: Extensions::new(),
+                    trusted_peer: true,
+                })
+            }
             Err(e) => {
                 debug!("wire: PROXY protocol header not present: {}", e);
-                self.try_from_stream_to_proxy_stream(parts.io, parts.read_buf)
+                self.try_from_stream_to_proxy_stream(parts.io, parts.read_buf, &e.to_string())
             }
         }
     }
@@ -251,8 +568,56 @@ impl Acceptor {
     pub fn require_proxy_header(self, require_proxy_header: bool) -> Self {
         Acceptor {
             require_proxy_header,
+            ..self
+        }
+    }
+
+    /// Reports the outcome of each accepted stream to `observer`
+    pub fn observer(self, observer: Arc<dyn ProxyObserver>) -> Self {
+        Acceptor {
+            observer: Some(observer),
+            ..self
         }
     }
+
+    /// If true, `accept_auto` stops waiting for the full header prefix as soon as the received
+    /// bytes don't match the beginning of any PROXY header signature. Clients connecting
+    /// directly then don't have to send `COMMON_HEADER_PREFIX_LEN` bytes before their stream is
+    /// passed through (or rejected, if the header is required). Disabled by default.
+    pub fn early_passthrough(self, early_passthrough: bool) -> Self {
+        Acceptor {
+            early_passthrough,
+            ..self
+        }
+    }
+
+    /// Fails accepting with `Error::Proxy` when the complete PROXY header (or the beginning of
+    /// the application data, if the header is optional) doesn't arrive within `header_timeout`.
+    /// This allows dropping slow peers early. The wait is bounded only by the stream itself by
+    /// default.
+    pub fn with_header_timeout(self, header_timeout: std::time::Duration) -> Self {
+        Acceptor {
+            header_timeout: Some(header_timeout),
+            ..self
+        }
+    }
+
+    /// Rejects PROXY protocol V2 headers carrying more than `max_tlvs` TLVs, see
+    /// `V2Codec::max_tlvs()`
+    pub fn max_tlvs(self, max_tlvs: usize) -> Self {
+        Acceptor { max_tlvs, ..self }
+    }
+
+    /// Selects how PROXY protocol V1 headers are parsed, see `V1Codec::parsing()`
+    pub fn v1_parsing(self, v1_parsing: V1Parsing) -> Self {
+        Acceptor { v1_parsing, ..self }
+    }
+
+    /// Returns false if `buf` cannot be the beginning of any supported PROXY header
+    fn may_be_header(buf: &[u8]) -> bool {
+        let len = std::cmp::min(buf.len(), Self::COMMON_HEADER_PREFIX_LEN);
+        buf[0..len] == V1_TAG[0..len] || buf[0..len] == V2_TAG[0..len]
+    }
 }
 
 /// Represent a prepared acceptor for processing incoming bytes
@@ -268,6 +633,15 @@ pub struct AcceptorBuilder<T> {
     config: ProtocolConfig,
     /// Build method for a particular Acceptor variant is selected based on provided configuration
     build_method: BuildMethod<T>,
+    /// Observer passed to all built acceptors
+    observer: Option<Arc<dyn ProxyObserver>>,
+    /// Peers whose PROXY headers are trusted for routing decisions, all peers are trusted if not
+    /// set
+    trusted_proxies: Option<Arc<Vec<IpAddr>>>,
+    /// V1 header parsing used by all built acceptors
+    v1_parsing: V1Parsing,
+    /// Maximum time to decode the PROXY header, see `Acceptor::with_header_timeout()`
+    header_timeout: Option<std::time::Duration>,
 }
 
 impl<T> AcceptorBuilder<T>
@@ -275,8 +649,34 @@ where
     T: AsyncRead + Send + Unpin + 'static,
 {
     pub fn new(config: ProtocolConfig) -> Self {
+        Self {
+            build_method: Self::select_build_method(&config),
+            config,
+            observer: None,
+            trusted_proxies: None,
+            v1_parsing: V1Parsing::default(),
+            header_timeout: None,
+        }
+    }
+
+    /// Reconfigures the accepted PROXY protocol versions, the build method is selected again
+    /// the same way as in `new()`. This allows e.g. turning the PROXY header detection on and off
+    /// at runtime by replacing a shared builder.
+    pub fn with_versions(self, versions: Vec<ProtocolVersion>) -> Self {
+        let config = ProtocolConfig {
+            versions,
+            ..self.config
+        };
+        Self {
+            build_method: Self::select_build_method(&config),
+            config,
+            ..self
+        }
+    }
+
+    fn select_build_method(config: &ProtocolConfig) -> BuildMethod<T> {
         // TODO for now, we only provide hardcoded autodetect build method
-        let build_method = match config.versions.len() {
+        match config.versions.len() {
             0 => {
                 assert!(
                     !config.require_proxy_header,
@@ -301,46 +701,117 @@ where
                 }
             }
             _ => Self::build_auto,
-        };
+        }
+    }
 
+    /// Reports the outcome of each stream accepted by the built acceptors to `observer`
+    pub fn observer(self, observer: Arc<dyn ProxyObserver>) -> Self {
         Self {
-            config,
-            build_method,
+            observer: Some(observer),
+            ..self
+        }
+    }
+
+    /// Routing information declared in PROXY headers (see
+    /// `ProxyStream::original_destination_port()`) is honored only when the real peer is one of
+    /// `proxies`. This prevents a client connecting directly from spoofing a routing decision. The
+    /// real peer has to be provided via `build_from_peer()`, streams built by `build()` are not
+    /// trusted once this is set.
+    pub fn trusted_proxies(self, proxies: Vec<IpAddr>) -> Self {
+        Self {
+            trusted_proxies: Some(Arc::new(proxies)),
+            ..self
+        }
+    }
+
+    /// Selects how PROXY protocol V1 headers are parsed by the built acceptors, see
+    /// `Acceptor::v1_parsing()`
+    pub fn v1_parsing(self, v1_parsing: V1Parsing) -> Self {
+        Self { v1_parsing, ..self }
+    }
+
+    /// Bounds the PROXY header decoding of the built acceptors, see
+    /// `Acceptor::with_header_timeout()`
+    pub fn with_header_timeout(self, header_timeout: std::time::Duration) -> Self {
+        Self {
+            header_timeout: Some(header_timeout),
+            ..self
         }
     }
 
     pub fn build(&self, stream: T) -> AcceptorFuture<T> {
-        (self.build_method)(self, stream)
+        self.build_from_trusted(stream, self.trusted_proxies.is_none())
+    }
+
+    /// Same as `build()`, `peer` is the real address of the other end of `stream` that is checked
+    /// against `trusted_proxies()`
+    pub fn build_from_peer(&self, stream: T, peer: SocketAddr) -> AcceptorFuture<T> {
+        let trusted_peer = match &self.trusted_proxies {
+            Some(trusted_proxies) => trusted_proxies.contains(&peer.ip()),
+            None => true,
+        };
+        self.build_from_trusted(stream, trusted_peer)
+    }
+
+    fn build_from_trusted(&self, stream: T, trusted_peer: bool) -> AcceptorFuture<T> {
+        let accept = (self.build_method)(self, stream);
+        if trusted_peer {
+            accept
+        } else {
+            accept
+                .map_ok(|mut stream| {
+                    stream.trusted_peer = false;
+                    stream
+                })
+                .boxed()
+        }
+    }
+
+    fn acceptor(&self) -> Acceptor {
+        let mut acceptor = Acceptor::new()
+            .require_proxy_header(self.config.require_proxy_header)
+            .v1_parsing(self.v1_parsing);
+        if let Some(header_timeout) = self.header_timeout {
+            acceptor = acceptor.with_header_timeout(header_timeout);
+        }
+        match &self.observer {
+            Some(observer) => acceptor.observer(observer.clone()),
+            None => acceptor,
+        }
     }
 
     /// Builds a special future that only passes back the `stream` wrapped in ProxyStream
     fn build_skip(&self, stream: T) -> AcceptorFuture<T> {
+        if let Some(observer) = &self.observer {
+            observer.on_accept(AcceptOutcome::NoHeader);
+        }
         async move {
             Ok(ProxyStream {
                 inner: stream,
                 buf: BytesMut::new(),
                 orig_source: None,
                 orig_destination: None,
+                orig_source_unix: None,
+                orig_destination_unix: None,
+                tlvs: Vec::new(),
+                header_bytes: Bytes::new(),
+                protocol_version: None,
+                extensions: Extensions::new(),
+                trusted_peer: true,
             })
         }
         .boxed()
     }
     fn build_auto(&self, stream: T) -> AcceptorFuture<T> {
-        let acceptor = Acceptor::new().require_proxy_header(self.config.require_proxy_header);
-
-        acceptor.accept_auto(stream).boxed()
+        self.acceptor().accept_auto(stream).boxed()
     }
 
     fn build_v1(&self, stream: T) -> AcceptorFuture<T> {
-        let acceptor = Acceptor::new().require_proxy_header(self.config.require_proxy_header);
-
-        acceptor.accept_v1(stream).boxed()
+        self.acceptor().accept_v1(stream).boxed()
     }
 
     fn build_v2(&self, stream: T) -> AcceptorFuture<T> {
-        let acceptor = Acceptor::new().require_proxy_header(self.config.require_proxy_header);
-
-        acceptor.accept_v2(stream).boxed()
+        self.acceptor().accept_v2(stream).boxed()
     }
 }
 
@@ -375,15 +846,73 @@ impl Connector {
         original_source: Option<SocketAddr>,
         original_destination: Option<SocketAddr>,
     ) -> Result<()> {
+        let data = self.encode_header(original_source, original_destination)?;
+        dest.write_all(&data).await?;
+        Ok(())
+    }
+
+    /// Adds PROXY protocol V2 header carrying `tlvs` after the address block to given stream, e.g.
+    /// for forwarding the authority to the next proxy in a chain. The whole header has to fit
+    /// into `MAX_HEADER_SIZE` bytes. Fails for V1 connector as V1 has no TLVs.
+    pub async fn write_proxy_header_with_tlvs<T: AsyncWrite + Unpin>(
+        &self,
+        dest: &mut T,
+        original_source: Option<SocketAddr>,
+        original_destination: Option<SocketAddr>,
+        tlvs: &[(u8, &[u8])],
+    ) -> Result<()> {
+        if self.protocol_version != ProtocolVersion::V2 {
+            return Err(Error::Proxy(
+                "TLVs can only be sent in PROXY protocol V2 header".into(),
+            ));
+        }
+        let mut proxy_info: ProxyInfo = (original_source, original_destination).try_into()?;
+        proxy_info.tlvs = tlvs
+            .iter()
+            .map(|(typ, value)| (*typ, Bytes::copy_from_slice(value)))
+            .collect();
+        let len = proxy_info.encoded_len(ProtocolVersion::V2);
+        if len > MAX_HEADER_SIZE {
+            return Err(Error::Proxy(format!(
+                "PROXY header with TLVs takes {} bytes, maximum is {}",
+                len, MAX_HEADER_SIZE
+            )));
+        }
+        let mut data = BytesMut::new();
+        V2Codec::new().encode(proxy_info, &mut data)?;
+        dest.write_all(&data).await?;
+        Ok(())
+    }
+
+    /// Adds the same PROXY protocol header to all `dests`. The header is encoded only once and
+    /// written to all destinations concurrently. The first error encountered is returned.
+    pub async fn write_proxy_header_to_all<'a, I, T>(
+        &self,
+        dests: I,
+        original_source: Option<SocketAddr>,
+        original_destination: Option<SocketAddr>,
+    ) -> Result<()>
+    where
+        I: IntoIterator<Item = &'a mut T>,
+        T: AsyncWrite + Unpin + 'a,
+    {
+        let data = self.encode_header(original_source, original_destination)?;
+        futures::future::try_join_all(dests.into_iter().map(|dest| dest.write_all(&data))).await?;
+        Ok(())
+    }
+
+    fn encode_header(
+        &self,
+        original_source: Option<SocketAddr>,
+        original_destination: Option<SocketAddr>,
+    ) -> Result<BytesMut> {
         let proxy_info = (original_source, original_destination).try_into()?;
         let mut data = BytesMut::new();
         match self.protocol_version {
             ProtocolVersion::V1 => V1Codec::new().encode(proxy_info, &mut data)?,
             ProtocolVersion::V2 => V2Codec::new().encode(proxy_info, &mut data)?,
         }
-
-        dest.write(&data).await?;
-        Ok(())
+        Ok(data)
     }
 }
 
@@ -397,9 +926,41 @@ pub struct ProxyStream<T> {
     buf: BytesMut,
     orig_source: Option<SocketAddr>,
     orig_destination: Option<SocketAddr>,
+    orig_source_unix: Option<PathBuf>,
+    orig_destination_unix: Option<PathBuf>,
+    /// TLV extensions passed in PROXY protocol V2 header
+    tlvs: Vec<(u8, Bytes)>,
+    /// Raw PROXY header as received, see `proxy_header_bytes()`
+    header_bytes: Bytes,
+    /// Version of the decoded PROXY header, see `protocol_version()`
+    protocol_version: Option<ProtocolVersion>,
+    /// Application data attached to the stream, see `Extensions`
+    extensions: Extensions,
+    /// Whether the routing information from the PROXY header can be trusted, see
+    /// `AcceptorBuilder::trusted_proxies()`
+    trusted_peer: bool,
 }
 
 impl<T> ProxyStream<T> {
+    /// Attaches `value` to the stream so that it travels along with it to the handler. Returns the
+    /// previously attached value of the same type, if any.
+    pub fn insert<V: Send + Sync + 'static>(&mut self, value: V) -> Option<V> {
+        self.extensions.insert(value)
+    }
+
+    /// Returns a value of type `V` previously attached by `insert()`
+    pub fn get<V: Send + Sync + 'static>(&self) -> Option<&V> {
+        self.extensions.get()
+    }
+
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.extensions
+    }
+
     /// Returns inner stream, but
     /// only when it is save, e.g. no data in buffer
     pub fn try_into_inner(self) -> Result<T> {
@@ -412,6 +973,52 @@ impl<T> ProxyStream<T> {
         }
     }
 
+    /// Port of the original destination declared in the PROXY header that is suitable for routing
+    /// decisions. `None` is returned when the port is unknown or when the header doesn't come from
+    /// a trusted proxy, see `AcceptorBuilder::trusted_proxies()`.
+    pub fn original_destination_port(&self) -> Option<u16> {
+        if self.trusted_peer {
+            self.orig_destination.map(|addr| addr.port())
+        } else {
+            None
+        }
+    }
+
+    /// Raw bytes of the decoded PROXY header exactly as received, e.g. for logging headers sent
+    /// by a misbehaving load balancer. Empty if no PROXY header has been detected.
+    pub fn proxy_header_bytes(&self) -> &[u8] {
+        &self.header_bytes
+    }
+
+    /// Version of the PROXY protocol the peer has spoken, `None` if the stream has been passed
+    /// through without a PROXY header
+    pub fn protocol_version(&self) -> Option<ProtocolVersion> {
+        self.protocol_version
+    }
+
+    /// Family (high nibble) and transport (low nibble) byte as it appeared in the received
+    /// PROXY protocol V2 header, e.g. `0x11` for TCP over IPv4. This is diagnostic information
+    /// that allows detecting non-conformant senders, `None` if no V2 header has been received.
+    pub fn raw_type_byte(&self) -> Option<u8> {
+        match self.protocol_version {
+            Some(ProtocolVersion::V2) => self.header_bytes.get(V2_TAG.len() + 1).copied(),
+            _ => None,
+        }
+    }
+
+    /// Checks that no buffered data is left unconsumed, which would indicate a framing bug. This
+    /// is the same check as in `try_into_inner()` but without consuming the stream.
+    pub fn assert_buffer_empty(&self) -> Result<()> {
+        if self.buf.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::InvalidState(format!(
+                "Buffer is not empty, {} bytes left unconsumed",
+                self.buf.len()
+            )))
+        }
+    }
+
     /// Direct conversion to FramedParts with arbitrary codec. It eliminates the problem with
     /// `From` implementation that also exists but doesn't simply allow using the 'I' parameter.
     /// See additional notes in `From`
@@ -455,6 +1062,49 @@ impl std::ops::Deref for ProxyStream<TcpStream> {
 //     }
 // }
 
+/// Data buffered while accepting the PROXY header are read first, then the reads are passed
+/// directly to the inner stream without any intermediate buffering. The stream can therefore be
+/// handed over to a TLS acceptor (e.g. `tokio_rustls::TlsAcceptor::accept()`) even when a part of
+/// the ClientHello has been read along with the header.
+impl<T: AsyncRead> AsyncRead for ProxyStream<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        read_buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let this = self.project();
+        if this.buf.is_empty() {
+            return this.inner.poll_read(cx, read_buf);
+        }
+        let len = std::cmp::min(this.buf.len(), read_buf.remaining());
+        read_buf.put_slice(&this.buf[..len]);
+        this.buf.advance(len);
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Writes are passed directly to the inner stream
+impl<T: AsyncWrite> AsyncWrite for ProxyStream<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        self.project().inner.poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        self.project().inner.poll_flush(cx)
+    }
+
+    /// Unlike `ProxyStream::shutdown()`, this doesn't check that the buffered application data
+    /// have been consumed. Only the write half is shut down, so the buffered data can still be
+    /// read, e.g. by a TLS stream that shuts down its writing side first.
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        self.project().inner.poll_shutdown(cx)
+    }
+}
+
 impl<T> WithProxyInfo for ProxyStream<T> {
     fn original_peer_addr(&self) -> Option<SocketAddr> {
         self.orig_source
@@ -463,13 +1113,120 @@ impl<T> WithProxyInfo for ProxyStream<T> {
     fn original_destination_addr(&self) -> Option<SocketAddr> {
         self.orig_destination
     }
+
+    fn original_peer_unix(&self) -> Option<&Path> {
+        self.orig_source_unix.as_deref()
+    }
+
+    fn original_destination_unix(&self) -> Option<&Path> {
+        self.orig_destination_unix.as_deref()
+    }
+
+    fn proxy_info(&self) -> Result<ProxyInfo> {
+        use std::convert::TryFrom;
+        let mut info = match (&self.orig_source_unix, &self.orig_destination_unix) {
+            (Some(src), Some(dst)) => ProxyInfo::from_unix(src, dst),
+            _ => ProxyInfo::try_from((self.orig_source, self.orig_destination))?,
+        };
+        info.tlvs = self.tlvs.clone();
+        Ok(info)
+    }
 }
 
 impl<T: AsyncRead + Send + Unpin> ProxyStream<T> {
     pub async fn new(stream: T) -> Result<Self> {
         Acceptor::default().accept_auto(stream).await
     }
-}
+
+    /// Returns application data received after the PROXY header without consuming them. Waits
+    /// for the peer to send some data if none have been buffered yet, the returned slice is
+    /// empty only when the peer has closed the connection.
+    pub async fn peek_application_data(&mut self) -> Result<&[u8]> {
+        if self.buf.is_empty() {
+            self.inner.read_buf(&mut self.buf).await?;
+        }
+        Ok(&self.buf[..])
+    }
+}
+
+impl<T: AsyncRead + AsyncWrite + Unpin> ProxyStream<T> {
+    /// Relays data between this stream and `other` until both sides are shut down. Application
+    /// data buffered while reading the PROXY header are forwarded to `other` first.
+    ///
+    /// Returns the number of bytes sent to `other` (including the buffered ones) and the number
+    /// of bytes sent back from `other`.
+    pub async fn copy_bidirectional_to<U>(mut self, mut other: U) -> Result<(u64, u64)>
+    where
+        U: AsyncRead + AsyncWrite + Unpin,
+    {
+        let buffered = self.buf.len() as u64;
+        other.write_all(&self.buf).await?;
+        self.buf.clear();
+        let (to_other, from_other) =
+            tokio::io::copy_bidirectional(&mut self.inner, &mut other).await?;
+        Ok((buffered + to_other, from_other))
+    }
+}
+
+/// Identity of a proxied TCP connection, see `ProxyStream::connection_key()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ConnectionKey {
+    /// Real address of the peer, i.e. the proxy if PROXY header has been received
+    pub peer_addr: SocketAddr,
+    /// Local address the connection has been accepted on
+    pub local_addr: SocketAddr,
+    /// Original source passed in the PROXY header
+    pub original_source: Option<SocketAddr>,
+    /// Original destination passed in the PROXY header
+    pub original_destination: Option<SocketAddr>,
+}
+
+/// Result of application codec selection, see `ProxyStream::select_codec()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Choice {
+    Left,
+    Right,
+}
+
+impl ProxyStream<TcpStream> {
+    /// Converts the stream into a `Connection` with one of two framings based on application
+    /// data received after the PROXY header. `choose` is provided with the received bytes, there
+    /// is at least one unless the peer has closed the connection, see `peek_application_data()`.
+    pub async fn select_codec<F, G>(
+        mut self,
+        choose: impl FnOnce(&[u8]) -> Choice,
+    ) -> Result<Either<Connection<F>, Connection<G>>>
+    where
+        F: Framing,
+        G: Framing,
+    {
+        Ok(match choose(self.peek_application_data().await?) {
+            Choice::Left => Either::Left(self.into()),
+            Choice::Right => Either::Right(self.into()),
+        })
+    }
+
+    /// Key combining the real endpoints of the connection with the original addresses passed in
+    /// the PROXY header, e.g. for connection tracking tables. Fails if the socket addresses cannot
+    /// be retrieved, typically when the connection has already been closed.
+    pub fn connection_key(&self) -> Result<ConnectionKey> {
+        Ok(ConnectionKey {
+            peer_addr: self.inner.peer_addr()?,
+            local_addr: self.inner.local_addr()?,
+            original_source: self.orig_source,
+            original_destination: self.orig_destination,
+        })
+    }
+
+    /// Shuts down the write half of the connection so that the peer receives EOF once all
+    /// written data are flushed. Fails without touching the connection when application data
+    /// received after the PROXY header are still buffered, see `assert_buffer_empty()`.
+    pub async fn shutdown(&mut self) -> Result<()> {
+        self.assert_buffer_empty()?;
+        self.inner.shutdown().await?;
+        Ok(())
+    }
+}
 
 impl<F> From<ProxyStream<TcpStream>> for Connection<F>
 where
@@ -477,10 +1234,17 @@ where
     F::Codec: Default,
 {
     fn from(stream: ProxyStream<TcpStream>) -> Self {
+        let proxy_info = if stream.header_bytes.is_empty() {
+            None
+        } else {
+            stream.proxy_info().ok()
+        };
         let mut parts = FramedParts::new(stream.inner, F::Codec::default());
         parts.read_buf = stream.buf; // pass existing read buffer
         Connection {
             framed_stream: Framed::from_parts(parts),
+            extensions: stream.extensions,
+            proxy_info,
         }
     }
 }
@@ -515,6 +1279,7 @@ mod tests {
     use super::*;
     use std::convert::TryFrom;
     use std::net::IpAddr;
+    use tokio_util::codec::{BytesCodec, LinesCodec, LinesCodecError};
 
     /// Test codec for verifying that a message flows correctly through ProxyStream
     struct TestCodec {
@@ -557,6 +1322,28 @@ mod tests {
         }
     }
 
+    /// Text line based framing, e.g. for JSON messages
+    #[derive(Debug)]
+    struct LinesFraming;
+
+    impl Framing for LinesFraming {
+        type Tx = String;
+        type Rx = String;
+        type Error = LinesCodecError;
+        type Codec = LinesCodec;
+    }
+
+    /// Framing that passes raw bytes through
+    #[derive(Debug)]
+    struct BytesFraming;
+
+    impl Framing for BytesFraming {
+        type Tx = bytes::Bytes;
+        type Rx = BytesMut;
+        type Error = std::io::Error;
+        type Codec = BytesCodec;
+    }
+
     /// Helper that
     async fn read_and_compare_message<T: AsyncRead + Unpin>(
         proxy_stream: ProxyStream<T>,
@@ -586,6 +1373,7 @@ mod tests {
             .accept_auto(message)
             .await
             .expect("BUG: Cannot accept message");
+        assert_eq!(ps.protocol_version(), Some(ProtocolVersion::V1));
         assert_eq!(
             "192.168.0.1:56324"
                 .parse::<SocketAddr>()
@@ -616,6 +1404,7 @@ mod tests {
             .accept_auto(&message[..])
             .await
             .expect("BUG: V2 message not accepted");
+        assert_eq!(ps.protocol_version(), Some(ProtocolVersion::V2));
         assert_eq!(
             "192.168.0.1:56324"
                 .parse::<SocketAddr>()
@@ -637,6 +1426,290 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_raw_type_byte() {
+        let mut message = Vec::from(V2_TAG);
+        message.extend(&[
+            0x21, 0x11, 0, 12, 192, 168, 0, 1, 192, 168, 0, 11, 0xdc, 0x04, 1, 187,
+        ]);
+        message.extend(b"Hello");
+        let ps = Acceptor::new()
+            .accept_auto(&message[..])
+            .await
+            .expect("BUG: V2 message not accepted");
+        assert_eq!(Some(0x11), ps.raw_type_byte());
+
+        let ps = Acceptor::new()
+            .accept_auto(&b"PROXY TCP4 192.168.0.1 192.168.0.11 56324 443\r\nHello"[..])
+            .await
+            .expect("BUG: V1 message not accepted");
+        assert_eq!(None, ps.raw_type_byte());
+    }
+
+    #[tokio::test]
+    async fn test_proxy_header_bytes() {
+        const V1_HEADER: &[u8] = b"PROXY TCP4 192.168.0.1 192.168.0.11 56324 443\r\n";
+        let mut message = Vec::from(V1_HEADER);
+        message.extend(b"Hello");
+        let ps = Acceptor::new()
+            .accept_auto(&message[..])
+            .await
+            .expect("BUG: V1 message not accepted");
+        assert_eq!(V1_HEADER, ps.proxy_header_bytes());
+        assert_eq!(b"Hello", &ps.buf[..]);
+
+        // V2 header arriving in chunks that split the fixed part and the addresses
+        let mut header = Vec::from(V2_TAG);
+        header.extend(&[
+            0x21, 0x11, 0, 12, 192, 168, 0, 1, 192, 168, 0, 11, 0xdc, 0x04, 1, 187,
+        ]);
+        let (mut client, server) = tokio::io::duplex(64);
+        let accept = tokio::spawn(Acceptor::new().accept_auto(server));
+        for chunk in [&header[..10], &header[10..20], &header[20..]] {
+            client
+                .write_all(chunk)
+                .await
+                .expect("BUG: Cannot write to client stream");
+            tokio::task::yield_now().await;
+        }
+        client
+            .write_all(b"Hello")
+            .await
+            .expect("BUG: Cannot write to client stream");
+        let ps = accept
+            .await
+            .expect("BUG: Accepting task failed")
+            .expect("BUG: V2 message not accepted");
+        assert_eq!(&header[..], ps.proxy_header_bytes());
+
+        // No header
+        let ps = Acceptor::new()
+            .accept_auto(&b"Hello, world"[..])
+            .await
+            .expect("BUG: Stream without header not passed through");
+        assert!(ps.proxy_header_bytes().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_v2_max_tlvs() {
+        let mut message = Vec::new();
+        message.extend_from_slice(V2_TAG);
+        message.extend(&[
+            0x21, 0x11, 0, 18, 192, 168, 0, 1, 192, 168, 0, 11, 0xdc, 0x04, 1, 187,
+        ]);
+        message.extend(&[0x04, 0, 0, 0x04, 0, 0]);
+
+        Acceptor::new()
+            .require_proxy_header(true)
+            .max_tlvs(1)
+            .accept_auto(&message[..])
+            .await
+            .expect_err("BUG: TLVs exceeding the limit accepted");
+        let ps = Acceptor::new()
+            .require_proxy_header(true)
+            .max_tlvs(2)
+            .accept_auto(&message[..])
+            .await
+            .expect("BUG: V2 message not accepted");
+        assert_eq!(
+            2,
+            ps.proxy_info()
+                .expect("BUG: Cannot build ProxyInfo")
+                .tlvs
+                .len()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_v1_parsing() {
+        const MESSAGE: &[u8] = b"PROXY TCP4 192.168.0.1  192.168.0.11 56324 443\r\nHello";
+
+        Acceptor::new()
+            .require_proxy_header(true)
+            .accept_v1(MESSAGE)
+            .await
+            .expect_err("BUG: Malformed V1 header accepted by default");
+        let ps = Acceptor::new()
+            .require_proxy_header(true)
+            .v1_parsing(V1Parsing::Lenient)
+            .accept_auto(MESSAGE)
+            .await
+            .expect("BUG: Malformed V1 header refused in lenient mode");
+        assert_eq!(ps.original_peer_addr(), "192.168.0.1:56324".parse().ok());
+        read_and_compare_message(ps, Vec::from(&b"Hello"[..])).await;
+
+        let builder = AcceptorBuilder::new(ProtocolConfig::new(true, vec![ProtocolVersion::V1]));
+        builder
+            .build(MESSAGE)
+            .await
+            .expect_err("BUG: Malformed V1 header accepted by default builder");
+        let ps = builder
+            .v1_parsing(V1Parsing::Lenient)
+            .build(MESSAGE)
+            .await
+            .expect("BUG: Malformed V1 header refused by lenient builder");
+        assert_eq!(ps.original_peer_addr(), "192.168.0.1:56324".parse().ok());
+    }
+
+    #[tokio::test]
+    async fn test_v2_unix() {
+        let mut message = Vec::new();
+        message.extend_from_slice(V2_TAG);
+        message.extend(&[0x21, 0x31, 0, 216]);
+        let mut src = [0u8; 108];
+        src[..16].copy_from_slice(b"/run/client.sock");
+        let mut dst = [0u8; 108];
+        dst[..14].copy_from_slice(b"/run/pool.sock");
+        message.extend(&src[..]);
+        message.extend(&dst[..]);
+        message.extend(b"Hello");
+
+        let ps = Acceptor::new()
+            .accept_auto(&message[..])
+            .await
+            .expect("BUG: V2 message not accepted");
+        assert_eq!(Some(Path::new("/run/client.sock")), ps.original_peer_unix());
+        assert_eq!(
+            Some(Path::new("/run/pool.sock")),
+            ps.original_destination_unix()
+        );
+        assert!(ps.original_peer_addr().is_none());
+        assert!(ps.original_destination_addr().is_none());
+        assert_eq!(
+            ProxyInfo::from_unix("/run/client.sock", "/run/pool.sock"),
+            ps.proxy_info().expect("BUG: Cannot build ProxyInfo")
+        );
+        assert_eq!(b"Hello", &ps.buf[..]);
+    }
+
+    #[tokio::test]
+    async fn test_acceptor_reuse() {
+        let acceptor = Acceptor::new().require_proxy_header(true);
+        for (src, message) in [
+            (
+                "192.168.0.1:56324",
+                &b"PROXY TCP4 192.168.0.1 192.168.0.11 56324 443\r\n"[..],
+            ),
+            (
+                "192.168.0.2:56325",
+                &b"PROXY TCP4 192.168.0.2 192.168.0.11 56325 443\r\n"[..],
+            ),
+        ] {
+            let ps = acceptor
+                .accept_auto_ref(message)
+                .await
+                .expect("BUG: V1 message not accepted");
+            assert_eq!(src.parse().ok(), ps.original_peer_addr());
+            let ps = acceptor
+                .accept_v1_ref(message)
+                .await
+                .expect("BUG: V1 message not accepted");
+            assert_eq!(src.parse().ok(), ps.original_peer_addr());
+        }
+        acceptor
+            .accept_v2_ref(&b"Hello, world"[..])
+            .await
+            .expect_err("BUG: Missing PROXY header accepted");
+
+        // Cloned acceptor keeps the configuration
+        acceptor
+            .clone()
+            .accept_auto(&b"Hello, world"[..])
+            .await
+            .expect_err("BUG: Missing PROXY header accepted");
+    }
+
+    #[tokio::test]
+    async fn test_tls_client_hello_handoff() {
+        use tokio_rustls::rustls::{
+            Certificate, ClientConfig, PrivateKey, RootCertStore, ServerConfig, ServerName,
+        };
+        use tokio_rustls::{TlsAcceptor, TlsConnector};
+
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()])
+            .expect("BUG: Cannot generate certificate");
+        let cert_der = Certificate(
+            cert.serialize_der()
+                .expect("BUG: Cannot encode certificate"),
+        );
+        let server_config = ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![cert_der.clone()],
+                PrivateKey(cert.serialize_private_key_der()),
+            )
+            .expect("BUG: Cannot configure TLS server");
+        let mut roots = RootCertStore::empty();
+        roots
+            .add(&cert_der)
+            .expect("BUG: Cannot add root certificate");
+        let client_config = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+
+        // The TLS client talks to a relay that sends the PROXY header along with the beginning
+        // of the ClientHello in a single segment
+        let (tls_client_side, relay_client_side) = tokio::io::duplex(4096);
+        let (relay_server_side, server) = tokio::io::duplex(4096);
+        let (mut from_client, mut to_client) = tokio::io::split(relay_client_side);
+        let (mut from_server, mut to_server) = tokio::io::split(relay_server_side);
+        tokio::spawn(async move {
+            let mut hello_head = [0u8; 20];
+            from_client
+                .read_exact(&mut hello_head)
+                .await
+                .expect("BUG: Cannot read ClientHello");
+            let mut first_segment = b"PROXY TCP4 192.168.0.1 192.168.0.11 56324 443\r\n".to_vec();
+            first_segment.extend_from_slice(&hello_head);
+            to_server
+                .write_all(&first_segment)
+                .await
+                .expect("BUG: Cannot write first segment");
+            let _ = tokio::io::copy(&mut from_client, &mut to_server).await;
+        });
+        tokio::spawn(async move { tokio::io::copy(&mut from_server, &mut to_client).await });
+
+        let client = tokio::spawn(async move {
+            let mut tls = TlsConnector::from(Arc::new(client_config))
+                .connect(
+                    ServerName::try_from("localhost").expect("BUG: Invalid server name"),
+                    tls_client_side,
+                )
+                .await
+                .expect("BUG: TLS handshake failed on the client");
+            tls.write_all(b"PING")
+                .await
+                .expect("BUG: Cannot write request");
+            let mut response = [0u8; 4];
+            tls.read_exact(&mut response)
+                .await
+                .expect("BUG: Cannot read response");
+            assert_eq!(b"PONG", &response);
+        });
+
+        let ps = Acceptor::new()
+            .accept_auto(server)
+            .await
+            .expect("BUG: Cannot accept PROXY header");
+        assert_eq!(ps.original_peer_addr(), "192.168.0.1:56324".parse().ok());
+        assert!(!ps.buf.is_empty(), "BUG: No part of ClientHello buffered");
+        let mut tls = TlsAcceptor::from(Arc::new(server_config))
+            .accept(ps)
+            .await
+            .expect("BUG: TLS handshake failed on the server");
+        let mut request = [0u8; 4];
+        tls.read_exact(&mut request)
+            .await
+            .expect("BUG: Cannot read request");
+        assert_eq!(b"PING", &request);
+        tls.write_all(b"PONG")
+            .await
+            .expect("BUG: Cannot write response");
+        client.await.expect("BUG: Client task failed");
+    }
+
     #[tokio::test]
     async fn test_v1_unknown_long_message() {
         let mut message = "PROXY UNKNOWN\r\n".to_string();
@@ -662,6 +1735,7 @@ mod tests {
             .expect("BUG: cannot create ProxyStream");
         assert!(ps.original_peer_addr().is_none());
         assert!(ps.original_destination_addr().is_none());
+        assert_eq!(ps.protocol_version(), None);
         read_and_compare_message(ps, Vec::from(MESSAGE)).await;
     }
 
@@ -711,6 +1785,247 @@ mod tests {
         read_and_compare_message(ps, Vec::from(MESSAGE)).await;
     }
 
+    #[tokio::test]
+    async fn test_assert_buffer_empty() {
+        const MESSAGE: &[u8] = b"PROXY TCP4 192.168.0.1 192.168.0.11 56324 443\r\nHELLO";
+        let mut ps = Acceptor::new()
+            .accept_auto(MESSAGE)
+            .await
+            .expect("BUG: Cannot accept incoming message");
+        ps.assert_buffer_empty()
+            .expect_err("BUG: Leftover bytes not detected");
+
+        let mut payload = [0u8; 5];
+        ps.read_exact(&mut payload)
+            .await
+            .expect("BUG: Cannot read payload");
+        assert_eq!(&payload, b"HELLO");
+        ps.assert_buffer_empty()
+            .expect("BUG: Consumed buffer reported as non-empty");
+    }
+
+    /// Verify that the buffered data are handed out in parts to small reads and that they stay
+    /// readable after the write half has been shut down
+    #[tokio::test]
+    async fn test_async_read_write() {
+        let (mut client, stream) = tokio::io::duplex(1024);
+        client
+            .write_all(b"PROXY TCP4 192.168.0.1 192.168.0.11 56324 443\r\nBUFFERED")
+            .await
+            .expect("BUG: Cannot write header");
+        let mut ps = Acceptor::new()
+            .accept_auto(stream)
+            .await
+            .expect("BUG: Cannot accept PROXY header");
+        assert_eq!(&ps.buf[..], b"BUFFERED");
+
+        let mut part = [0u8; 3];
+        ps.read_exact(&mut part)
+            .await
+            .expect("BUG: Cannot read payload");
+        assert_eq!(&part, b"BUF");
+
+        ps.shutdown().await.expect("BUG: Cannot shut down");
+        let mut reply = Vec::new();
+        client
+            .read_to_end(&mut reply)
+            .await
+            .expect("BUG: Cannot read reply");
+        assert!(reply.is_empty(), "BUG: Unexpected reply");
+
+        drop(client);
+        let mut rest = Vec::new();
+        ps.read_to_end(&mut rest)
+            .await
+            .expect("BUG: Cannot read payload");
+        assert_eq!(&rest, b"FERED");
+    }
+
+    #[tokio::test]
+    async fn test_accept_connection_deadline() {
+        /// Sends the PROXY header in two parts and then the first frame, each after `delay`
+        async fn send_slowly(mut client: tokio::io::DuplexStream, delay: std::time::Duration) {
+            let parts: [&[u8]; 3] = [
+                b"PROXY TCP4 192.168.0.1 ",
+                b"192.168.0.11 56324 443\r\n",
+                b"HELLO\nWORLD\n",
+            ];
+            for part in parts.iter() {
+                tokio::time::sleep(delay).await;
+                // The client may be dropped by the accepting side already
+                let _ = client.write_all(part).await;
+            }
+        }
+
+        let (client, server) = tokio::io::duplex(1024);
+        tokio::spawn(send_slowly(client, std::time::Duration::from_millis(50)));
+        let (ps, frame) = Acceptor::new()
+            .accept_connection(
+                server,
+                &mut LinesCodec::new(),
+                std::time::Duration::from_secs(1),
+            )
+            .await
+            .expect("BUG: Cannot accept connection");
+        assert_eq!(frame, "HELLO");
+        assert_eq!(
+            ps.original_peer_addr(),
+            Some("192.168.0.1:56324".parse().expect("BUG: Invalid address"))
+        );
+        assert_eq!(&ps.buf[..], b"WORLD\n");
+
+        // Each phase is delayed by less than the deadline, but not all of them together
+        let (client, server) = tokio::io::duplex(1024);
+        tokio::spawn(send_slowly(client, std::time::Duration::from_millis(100)));
+        let err = Acceptor::new()
+            .accept_connection(
+                server,
+                &mut LinesCodec::new(),
+                std::time::Duration::from_millis(250),
+            )
+            .await
+            .expect_err("BUG: Deadline not enforced");
+        assert!(
+            matches!(err, Error::Timeout(_)),
+            "BUG: Unexpected error {}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn test_accept_buffered() {
+        const PAYLOAD: &[u8] = b"Some payload after the header\r\n";
+        let mut message = Vec::from(&b"PROXY TCP4 192.168.0.1 192.168.0.11 56324 443\r\n"[..]);
+        message.extend_from_slice(PAYLOAD);
+
+        // Header spans several fills of the reader's buffer
+        let reader = tokio::io::BufReader::with_capacity(8, &message[..]);
+        let ps = Acceptor::new()
+            .accept_auto_buffered(reader)
+            .await
+            .expect("BUG: Cannot accept message");
+        assert_eq!(
+            "192.168.0.1:56324".parse().ok(),
+            ps.original_peer_addr(),
+            "BUG: Wrong source address"
+        );
+        read_and_compare_message(ps, Vec::from(PAYLOAD)).await;
+
+        // Without the header, the reader is passed on with its buffer intact
+        let reader = tokio::io::BufReader::new(PAYLOAD);
+        let ps = Acceptor::new()
+            .require_proxy_header(false)
+            .accept_auto_buffered(reader)
+            .await
+            .expect("BUG: Cannot accept message");
+        assert!(ps.buf.is_empty());
+        assert_eq!(PAYLOAD, ps.inner.buffer());
+        read_and_compare_message(ps, Vec::from(PAYLOAD)).await;
+    }
+
+    #[tokio::test]
+    async fn test_header_timeout() {
+        let (mut client, server) = tokio::io::duplex(64);
+        client
+            .write_all(b"PR")
+            .await
+            .expect("BUG: Cannot write to client stream");
+        let err = tokio::time::timeout(
+            std::time::Duration::from_millis(500),
+            Acceptor::new()
+                .with_header_timeout(std::time::Duration::from_millis(50))
+                .accept_auto(server),
+        )
+        .await
+        .expect("BUG: Header timeout didn't fire")
+        .expect_err("BUG: Stalled stream accepted");
+        match err {
+            Error::Proxy(msg) => assert_eq!("header read timed out", msg),
+            e => panic!("BUG: Unexpected error: {}", e),
+        }
+
+        // The timeout covers the whole header, not only its prefix
+        let (mut client, server) = tokio::io::duplex(64);
+        client
+            .write_all(b"PROXY TCP4 192.168.0.1")
+            .await
+            .expect("BUG: Cannot write to client stream");
+        tokio::time::timeout(
+            std::time::Duration::from_millis(500),
+            Acceptor::new()
+                .with_header_timeout(std::time::Duration::from_millis(50))
+                .accept_auto(server),
+        )
+        .await
+        .expect("BUG: Header timeout didn't fire")
+        .expect_err("BUG: Stalled stream accepted");
+
+        // The timeout is passed on from the builder
+        let (mut client, server) = tokio::io::duplex(64);
+        client
+            .write_all(b"PR")
+            .await
+            .expect("BUG: Cannot write to client stream");
+        let config = ProtocolConfig::new(true, vec![ProtocolVersion::V1]);
+        let builder =
+            AcceptorBuilder::new(config).with_header_timeout(std::time::Duration::from_millis(50));
+        tokio::time::timeout(std::time::Duration::from_millis(500), builder.build(server))
+            .await
+            .expect("BUG: Header timeout didn't fire")
+            .expect_err("BUG: Stalled stream accepted");
+
+        // A buffered reader that never receives anything is timed out, too
+        let (_client, server) = tokio::io::duplex(64);
+        tokio::time::timeout(
+            std::time::Duration::from_millis(500),
+            Acceptor::new()
+                .with_header_timeout(std::time::Duration::from_millis(50))
+                .accept_auto_buffered(tokio::io::BufReader::new(server)),
+        )
+        .await
+        .expect("BUG: Header timeout didn't fire")
+        .expect_err("BUG: Silent stream accepted");
+
+        // A complete header is not affected
+        let ps = Acceptor::new()
+            .with_header_timeout(std::time::Duration::from_millis(50))
+            .accept_auto(&b"PROXY TCP4 192.168.0.1 192.168.0.11 56324 443\r\nHello"[..])
+            .await
+            .expect("BUG: Cannot accept PROXY header");
+        assert_eq!(b"Hello", &ps.buf[..]);
+    }
+
+    #[tokio::test]
+    async fn test_early_passthrough() {
+        let (mut client, server) = tokio::io::duplex(64);
+        client
+            .write_all(b"GE")
+            .await
+            .expect("BUG: Cannot write to client stream");
+        let ps = tokio::time::timeout(
+            std::time::Duration::from_millis(500),
+            Acceptor::new().early_passthrough(true).accept_auto(server),
+        )
+        .await
+        .expect("BUG: Direct client not passed through early")
+        .expect("BUG: Cannot accept stream");
+        assert_eq!(b"GE", &ps.buf[..]);
+        assert!(ps.original_peer_addr().is_none());
+
+        // Prefix of a PROXY header keeps the acceptor waiting
+        let (mut client, server) = tokio::io::duplex(64);
+        client
+            .write_all(b"PRO")
+            .await
+            .expect("BUG: Cannot write to client stream");
+        tokio::time::timeout(
+            std::time::Duration::from_millis(100),
+            Acceptor::new().early_passthrough(true).accept_auto(server),
+        )
+        .await
+        .expect_err("BUG: Acceptor didn't wait for the rest of PROXY header");
+    }
+
     #[tokio::test]
     async fn test_connect() {
         let mut buf = Vec::new();
@@ -728,6 +2043,393 @@ mod tests {
         assert_eq!(expected.as_bytes(), &buf[..]);
     }
 
+    #[tokio::test]
+    async fn test_connect_with_tlvs() {
+        let src = "127.0.0.1:1111"
+            .parse::<SocketAddr>()
+            .expect("BUG: Cannot parse IP");
+        let dest = "127.0.0.1:2222"
+            .parse::<SocketAddr>()
+            .expect("BUG: Cannot parse IP");
+        let tlvs: &[(u8, &[u8])] = &[
+            (codec::v2::tlv::PP2_TYPE_AUTHORITY, b"pool.example.com"),
+            (0xE0, b"custom namespace"),
+        ];
+        let mut buf = Vec::new();
+        Connector::new(ProtocolVersion::V2)
+            .write_proxy_header_with_tlvs(&mut buf, Some(src), Some(dest), tlvs)
+            .await
+            .expect("BUG: Cannot write proxy header");
+
+        let mut data = BytesMut::from(&buf[..]);
+        let info = V2Codec::new()
+            .decode(&mut data)
+            .expect("BUG: Cannot decode proxy header")
+            .expect("BUG: Incomplete proxy header");
+        assert!(data.is_empty());
+        assert_eq!(Some(src), info.original_source);
+        assert_eq!(Some(dest), info.original_destination);
+        assert_eq!(Some("pool.example.com"), info.authority());
+        assert_eq!(Some(&b"custom namespace"[..]), info.tlv(0xE0));
+
+        let mut buf = Vec::new();
+        Connector::new(ProtocolVersion::V1)
+            .write_proxy_header_with_tlvs(&mut buf, Some(src), Some(dest), tlvs)
+            .await
+            .expect_err("BUG: TLVs written to V1 header");
+        let large = [0; MAX_HEADER_SIZE];
+        Connector::new(ProtocolVersion::V2)
+            .write_proxy_header_with_tlvs(&mut buf, Some(src), Some(dest), &[(0xE0, &large)])
+            .await
+            .expect_err("BUG: Header exceeding maximum size written");
+        assert!(buf.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_connect_to_all() {
+        let mut bufs = [Vec::new(), Vec::new()];
+        let src = "127.0.0.1:1111"
+            .parse::<SocketAddr>()
+            .expect("BUG: Cannot parse IP");
+        let dest = "127.0.0.1:2222"
+            .parse::<SocketAddr>()
+            .expect("BUG: Cannot parse IP");
+        Connector::new(ProtocolVersion::V2)
+            .write_proxy_header_to_all(bufs.iter_mut(), Some(src), Some(dest))
+            .await
+            .expect("BUG: Cannot write proxy headers");
+        assert_eq!(bufs[0], bufs[1]);
+
+        let mut data = BytesMut::from(&bufs[0][..]);
+        let info = V2Codec::new()
+            .decode(&mut data)
+            .expect("BUG: Cannot decode proxy header")
+            .expect("BUG: Incomplete proxy header");
+        assert_eq!(Some(src), info.original_source);
+        assert_eq!(Some(dest), info.original_destination);
+        assert!(data.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_shutdown() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("BUG: Cannot bind listener");
+        let addr = listener
+            .local_addr()
+            .expect("BUG: Cannot get local address");
+
+        let server = tokio::spawn(async move {
+            let mut buffered = Vec::new();
+            for _ in 0..2 {
+                let (stream, _) = listener.accept().await.expect("BUG: Cannot accept");
+                let mut ps = Acceptor::new()
+                    .accept_auto(stream)
+                    .await
+                    .expect("BUG: Cannot accept PROXY header");
+                buffered.push(!ps.buf.is_empty());
+                if ps.buf.is_empty() {
+                    ps.writable().await.expect("BUG: Stream not writable");
+                    ps.try_write(b"Hello").expect("BUG: Cannot write");
+                    ps.shutdown().await.expect("BUG: Cannot shut down");
+                } else {
+                    ps.shutdown()
+                        .await
+                        .expect_err("BUG: Shut down with data buffered");
+                }
+            }
+            buffered
+        });
+
+        let mut client = TcpStream::connect(addr).await.expect("BUG: Cannot connect");
+        client
+            .write_all(b"PROXY TCP4 192.168.0.1 192.168.0.11 56324 443\r\n")
+            .await
+            .expect("BUG: Cannot write PROXY header");
+        let mut received = Vec::new();
+        client
+            .read_to_end(&mut received)
+            .await
+            .expect("BUG: Cannot read until EOF");
+        assert_eq!(b"Hello", &received[..]);
+
+        // Application data sent along with the header are still buffered
+        let mut client = TcpStream::connect(addr).await.expect("BUG: Cannot connect");
+        client
+            .write_all(b"PROXY TCP4 192.168.0.1 192.168.0.11 56324 443\r\nHello")
+            .await
+            .expect("BUG: Cannot write PROXY header");
+        assert_eq!(
+            vec![false, true],
+            server.await.expect("BUG: Server task failed")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_connection_proxy_info() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("BUG: Cannot bind listener");
+        let addr = listener
+            .local_addr()
+            .expect("BUG: Cannot get local address");
+        let mut client = TcpStream::connect(addr).await.expect("BUG: Cannot connect");
+        client
+            .write_all(b"PROXY TCP4 192.168.0.1 192.168.0.11 56324 443\r\n")
+            .await
+            .expect("BUG: Cannot write PROXY header");
+        let (stream, _) = listener.accept().await.expect("BUG: Cannot accept");
+        let ps = Acceptor::new()
+            .accept_auto(stream)
+            .await
+            .expect("BUG: Cannot accept PROXY header");
+
+        let connection = Connection::<LinesFraming>::from(ps);
+        let proxy_info = connection
+            .proxy_info()
+            .expect("BUG: PROXY info lost in conversion");
+        assert_eq!("192.168.0.1:56324".parse().ok(), proxy_info.original_source);
+        assert_eq!(
+            "192.168.0.11:443".parse().ok(),
+            proxy_info.original_destination
+        );
+
+        // Connection without the PROXY header
+        let mut client = TcpStream::connect(addr).await.expect("BUG: Cannot connect");
+        client
+            .write_all(b"HELLO, no PROXY header here\n")
+            .await
+            .expect("BUG: Cannot write message");
+        let (stream, _) = listener.accept().await.expect("BUG: Cannot accept");
+        let ps = Acceptor::new()
+            .accept_auto(stream)
+            .await
+            .expect("BUG: Cannot accept stream without PROXY header");
+        assert!(Connection::<LinesFraming>::from(ps).proxy_info().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_connection_key() {
+        use std::collections::HashSet;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("BUG: Cannot bind listener");
+        let addr = listener
+            .local_addr()
+            .expect("BUG: Cannot get local address");
+        let mut client = TcpStream::connect(addr).await.expect("BUG: Cannot connect");
+        client
+            .write_all(b"PROXY TCP4 192.168.0.1 192.168.0.11 56324 443\r\n")
+            .await
+            .expect("BUG: Cannot write PROXY header");
+        let (stream, _) = listener.accept().await.expect("BUG: Cannot accept");
+        let ps = Acceptor::new()
+            .accept_auto(stream)
+            .await
+            .expect("BUG: Cannot accept PROXY header");
+
+        let key = ps
+            .connection_key()
+            .expect("BUG: Cannot build connection key");
+        assert_eq!(
+            client.local_addr().expect("BUG: Cannot get local address"),
+            key.peer_addr
+        );
+        assert_eq!(addr, key.local_addr);
+        assert_eq!("192.168.0.1:56324".parse().ok(), key.original_source);
+        assert_eq!("192.168.0.11:443".parse().ok(), key.original_destination);
+
+        let mut keys = HashSet::new();
+        keys.insert(key);
+        assert!(keys.contains(
+            &ps.connection_key()
+                .expect("BUG: Cannot build connection key")
+        ));
+        assert!(!keys.contains(&ConnectionKey {
+            original_source: None,
+            ..key
+        }));
+    }
+
+    #[tokio::test]
+    async fn test_select_codec_json() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("BUG: Cannot bind listener");
+        let addr = listener
+            .local_addr()
+            .expect("BUG: Cannot get local address");
+        let mut client = TcpStream::connect(addr).await.expect("BUG: Cannot connect");
+        client
+            .write_all(b"PROXY TCP4 192.168.0.1 192.168.0.11 56324 443\r\n")
+            .await
+            .expect("BUG: Cannot write PROXY header");
+
+        let (stream, _) = listener.accept().await.expect("BUG: Cannot accept");
+        let ps = Acceptor::new()
+            .accept_auto(stream)
+            .await
+            .expect("BUG: Cannot accept PROXY header");
+        // The message is sent only once the PROXY header has been accepted, the selection
+        // has to wait for it
+        let writer = tokio::spawn(async move {
+            client
+                .write_all(b"{\"id\":1}\n")
+                .await
+                .expect("BUG: Cannot write message");
+            client
+        });
+
+        let select = |buf: &[u8]| match buf.first() {
+            Some(b'{') => Choice::Left,
+            _ => Choice::Right,
+        };
+        let selected = ps
+            .select_codec::<LinesFraming, BytesFraming>(select)
+            .await
+            .expect("BUG: Cannot select codec");
+        let _client = writer.await.expect("BUG: Client task failed");
+        match selected {
+            Either::Left(mut connection) => assert_eq!(
+                "{\"id\":1}",
+                connection
+                    .next()
+                    .await
+                    .expect("BUG: Unexpected end of stream")
+                    .expect("BUG: Cannot read message"),
+            ),
+            Either::Right(_) => panic!("BUG: JSON codec not selected"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_peek_application_data() {
+        let (mut client, proxy_side) = tokio::io::duplex(64);
+        client
+            .write_all(b"PROXY TCP4 192.168.0.1 192.168.0.11 56324 443\r\n")
+            .await
+            .expect("BUG: Cannot write PROXY header");
+        let mut ps = Acceptor::new()
+            .accept_auto(proxy_side)
+            .await
+            .expect("BUG: Cannot accept PROXY header");
+        assert!(ps.buf.is_empty(), "BUG: Unexpected application data");
+
+        // Nothing has been sent yet, so peeking has to wait
+        assert!(ps.peek_application_data().now_or_never().is_none());
+        client
+            .write_all(b"HELLO")
+            .await
+            .expect("BUG: Cannot write message");
+        assert_eq!(
+            b"HELLO",
+            ps.peek_application_data()
+                .await
+                .expect("BUG: Cannot peek application data")
+        );
+        // Peeking doesn't consume the data
+        let mut received = [0u8; 5];
+        ps.read_exact(&mut received)
+            .await
+            .expect("BUG: Cannot read message");
+        assert_eq!(b"HELLO", &received);
+
+        drop(client);
+        assert!(ps
+            .peek_application_data()
+            .await
+            .expect("BUG: Cannot peek application data")
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_copy_bidirectional_to() {
+        let (mut client, proxy_side) = tokio::io::duplex(64);
+        let (proxy_upstream, mut upstream) = tokio::io::duplex(64);
+
+        // Application data arrive along with the PROXY header so that they end up buffered
+        client
+            .write_all(b"PROXY TCP4 192.168.0.1 192.168.0.11 56324 443\r\nHELLO")
+            .await
+            .expect("BUG: Cannot write message");
+        let ps = Acceptor::new()
+            .accept_auto(proxy_side)
+            .await
+            .expect("BUG: Cannot accept PROXY header");
+        assert_eq!(b"HELLO", &ps.buf[..], "BUG: Message not buffered");
+        let relay = tokio::spawn(ps.copy_bidirectional_to(proxy_upstream));
+
+        let mut received = [0u8; 5];
+        upstream
+            .read_exact(&mut received)
+            .await
+            .expect("BUG: Cannot read relayed message");
+        assert_eq!(b"HELLO", &received);
+        upstream
+            .write_all(b"HI")
+            .await
+            .expect("BUG: Cannot write reply");
+        let mut reply = [0u8; 2];
+        client
+            .read_exact(&mut reply)
+            .await
+            .expect("BUG: Cannot read relayed reply");
+        assert_eq!(b"HI", &reply);
+
+        drop(client);
+        drop(upstream);
+        assert_eq!(
+            (5, 2),
+            relay
+                .await
+                .expect("BUG: Relay task failed")
+                .expect("BUG: Relaying failed")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_accept_auto_cancelled() {
+        // Keep the client end open so that the handshake waits for more bytes
+        let (mut client, stream) = tokio::io::duplex(64);
+        client
+            .write_all(b"PROXY TCP4")
+            .await
+            .expect("BUG: Cannot write message");
+        let (trigger, tripwire) = tokio::sync::oneshot::channel::<()>();
+
+        let accept =
+            tokio::spawn(Acceptor::new().accept_auto_cancellable(stream, tripwire.map(|_| ())));
+        tokio::task::yield_now().await;
+        trigger.send(()).expect("BUG: Cannot fire tripwire");
+
+        match accept.await.expect("BUG: Accepting task failed") {
+            Err(Error::Cancelled) => (),
+            res => panic!("BUG: Expected Error::Cancelled, got: {:?}", res),
+        }
+    }
+
+    #[cfg(feature = "ii-async-utils")]
+    #[tokio::test]
+    async fn test_accept_auto_until_halted() {
+        let (mut client, stream) = tokio::io::duplex(64);
+        client
+            .write_all(b"PROXY TCP4")
+            .await
+            .expect("BUG: Cannot write message");
+        let halt_handle = ii_async_utils::HaltHandle::<u8>::default();
+
+        let accept =
+            tokio::spawn(Acceptor::new().accept_auto_until_halted(stream, halt_handle.tripwire()));
+        tokio::task::yield_now().await;
+        halt_handle.halt_with(1);
+
+        match accept.await.expect("BUG: Accepting task failed") {
+            Err(Error::Cancelled) => (),
+            res => panic!("BUG: Expected Error::Cancelled, got: {:?}", res),
+        }
+    }
+
     /// Helper that allows testing `AcceptorBuilder` that it internally configures the correct
     /// build method that matches `expected_build_method` based on a specified protocol version
     fn test_acceptor_builder(
@@ -820,6 +2522,359 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn acceptor_builder_with_versions() {
+        const MESSAGE: &[u8] = b"PROXY TCP4 192.168.0.1 192.168.0.11 56324 443\r\nHello";
+
+        let builder = AcceptorBuilder::new(ProtocolConfig::new(false, vec![]));
+        let ps = builder
+            .build(MESSAGE)
+            .await
+            .expect("BUG: Cannot accept message");
+        // No detection, the header is passed through as data
+        assert_eq!(ps.original_peer_addr(), None);
+        assert_eq!(ps.protocol_version(), None);
+        read_and_compare_message(ps, Vec::from(MESSAGE)).await;
+
+        let builder = builder.with_versions(vec![ProtocolVersion::V1, ProtocolVersion::V2]);
+        let ps = builder
+            .build(MESSAGE)
+            .await
+            .expect("BUG: Cannot accept message");
+        assert_eq!(ps.original_peer_addr(), "192.168.0.1:56324".parse().ok());
+        assert_eq!(ps.protocol_version(), Some(ProtocolVersion::V1));
+        read_and_compare_message(ps, Vec::from(&b"Hello"[..])).await;
+
+        // With the header required, only the configured version is accepted
+        let builder = AcceptorBuilder::new(ProtocolConfig::new(true, vec![ProtocolVersion::V2]));
+        assert!(
+            builder.build(MESSAGE).await.is_err(),
+            "BUG: V1 header accepted by a V2 only builder"
+        );
+        let ps = builder
+            .with_versions(vec![ProtocolVersion::V1])
+            .build(MESSAGE)
+            .await
+            .expect("BUG: Cannot accept message");
+        assert_eq!(ps.protocol_version(), Some(ProtocolVersion::V1));
+        read_and_compare_message(ps, Vec::from(&b"Hello"[..])).await;
+    }
+
+    #[tokio::test]
+    async fn test_extensions_survive_move() {
+        #[derive(Debug, PartialEq)]
+        struct Route {
+            backend: &'static str,
+        }
+
+        const MESSAGE: &[u8] = b"MEMAM PROXY HEADER, CHUDACEK JA";
+        let mut ps = ProxyStream::new(MESSAGE)
+            .await
+            .expect("BUG: cannot create ProxyStream");
+        assert!(ps.insert(Route { backend: "pool-1" }).is_none());
+
+        // Hand the stream over to a handler task
+        tokio::spawn(async move {
+            assert_eq!(Some(&Route { backend: "pool-1" }), ps.get::<Route>());
+            assert!(ps.get::<u32>().is_none());
+            read_and_compare_message(ps, Vec::from(MESSAGE)).await;
+        })
+        .await
+        .expect("BUG: handler task failed");
+    }
+
+    #[tokio::test]
+    async fn test_read_large_payload() {
+        const PAYLOAD_LEN: usize = 4 * 1024 * 1024;
+        const CHUNK_LEN: usize = 1000;
+        let payload: Vec<u8> = (0..PAYLOAD_LEN).map(|i| (i % 251) as u8).collect();
+
+        let (mut client, stream) = tokio::io::duplex(64 * 1024);
+        let expected_payload = payload.clone();
+        let sender = tokio::spawn(async move {
+            client
+                .write_all(b"PROXY TCP4 192.168.0.1 192.168.0.11 56324 443\r\n")
+                .await
+                .expect("BUG: Cannot write header");
+            client
+                .write_all(&payload)
+                .await
+                .expect("BUG: Cannot write payload");
+        });
+
+        let mut ps = Acceptor::new()
+            .accept_auto(stream)
+            .await
+            .expect("BUG: Cannot accept PROXY header");
+        let buf_capacity = ps.buf.capacity();
+
+        // Read in small chunks so that the buffered part is drained across multiple reads
+        let mut received = Vec::with_capacity(PAYLOAD_LEN);
+        let mut chunk = [0u8; CHUNK_LEN];
+        loop {
+            let len = ps.read(&mut chunk).await.expect("BUG: Cannot read payload");
+            if len == 0 {
+                break;
+            }
+            assert!(len <= CHUNK_LEN);
+            received.extend_from_slice(&chunk[..len]);
+            // The internal buffer never grows while streaming
+            assert!(ps.buf.capacity() <= buf_capacity);
+        }
+        sender.await.expect("BUG: Sender task failed");
+
+        assert_eq!(received.len(), PAYLOAD_LEN);
+        assert!(received == expected_payload, "BUG: Payload corrupted");
+    }
+
+    #[tokio::test]
+    async fn test_v1_unknown_large_payload() {
+        const PAYLOAD_LEN: usize = 4 * 1024 * 1024;
+        let payload: Vec<u8> = (0..PAYLOAD_LEN).map(|i| (i % 251) as u8).collect();
+
+        let (mut client, stream) = tokio::io::duplex(64 * 1024);
+        let expected_payload = payload.clone();
+        let sender = tokio::spawn(async move {
+            client
+                .write_all(b"PROXY UNKNOWN\r\n")
+                .await
+                .expect("BUG: Cannot write header");
+            client
+                .write_all(&payload)
+                .await
+                .expect("BUG: Cannot write payload");
+        });
+
+        let mut ps = Acceptor::new()
+            .accept_auto(stream)
+            .await
+            .expect("BUG: Cannot accept PROXY header");
+        assert!(ps.original_peer_addr().is_none());
+        // Only data received along with the header are buffered, the rest streams through
+        assert!(ps.buf.capacity() < PAYLOAD_LEN / 4);
+
+        let mut received = Vec::with_capacity(PAYLOAD_LEN);
+        ps.read_to_end(&mut received)
+            .await
+            .expect("BUG: Cannot read payload");
+        sender.await.expect("BUG: Sender task failed");
+        assert!(received == expected_payload, "BUG: Payload corrupted");
+    }
+
+    #[cfg(feature = "tracing")]
+    #[tokio::test]
+    async fn test_tracing_spans() {
+        use std::sync::Mutex;
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Subscriber};
+        use tracing_subscriber::layer::{Context, SubscriberExt};
+        use tracing_subscriber::registry::LookupSpan;
+        use tracing_subscriber::Layer;
+
+        #[derive(Debug, Default)]
+        struct RecordedSpan {
+            name: &'static str,
+            parent: Option<&'static str>,
+            fields: Vec<(&'static str, String)>,
+            events: usize,
+        }
+
+        struct FieldVisitor<'a>(&'a mut Vec<(&'static str, String)>);
+
+        impl Visit for FieldVisitor<'_> {
+            fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+                self.0.push((field.name(), format!("{:?}", value)));
+            }
+        }
+
+        /// Records all spans along with their fields and the number of events inside them
+        #[derive(Clone, Default)]
+        struct SpanRecorder(Arc<Mutex<Vec<(Id, RecordedSpan)>>>);
+
+        impl SpanRecorder {
+            fn with_span<F: FnOnce(&mut RecordedSpan)>(&self, id: &Id, f: F) {
+                let mut spans = self.0.lock().expect("BUG: Poisoned mutex");
+                if let Some((_, span)) = spans.iter_mut().find(|(span_id, _)| span_id == id) {
+                    f(span);
+                }
+            }
+        }
+
+        impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for SpanRecorder {
+            fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+                let mut span = RecordedSpan {
+                    name: attrs.metadata().name(),
+                    parent: ctx
+                        .span(id)
+                        .and_then(|span| span.parent())
+                        .map(|parent| parent.name()),
+                    ..Default::default()
+                };
+                attrs.record(&mut FieldVisitor(&mut span.fields));
+                self.0
+                    .lock()
+                    .expect("BUG: Poisoned mutex")
+                    .push((id.clone(), span));
+            }
+
+            fn on_record(&self, id: &Id, values: &Record<'_>, _ctx: Context<'_, S>) {
+                self.with_span(id, |span| {
+                    values.record(&mut FieldVisitor(&mut span.fields))
+                });
+            }
+
+            fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+                if let Some(span) = ctx.event_span(event) {
+                    self.with_span(&span.id(), |span| span.events += 1);
+                }
+            }
+        }
+
+        const V2_MESSAGE: &[u8] =
+            b"\r\n\r\n\0\r\nQUIT\n\x21\x11\x00\x0c\xc0\xa8\x00\x01\xc0\xa8\x00\x0b\xdc\x04\x01\xbb";
+        let recorder = SpanRecorder::default();
+        let _guard =
+            tracing::subscriber::set_default(tracing_subscriber::registry().with(recorder.clone()));
+        Acceptor::new()
+            .accept_auto(V2_MESSAGE)
+            .await
+            .expect("BUG: Cannot accept message");
+
+        let spans = recorder.0.lock().expect("BUG: Poisoned mutex");
+        let span = |name| {
+            spans
+                .iter()
+                .map(|(_, span)| span)
+                .find(|span| span.name == name)
+                .unwrap_or_else(|| panic!("BUG: Missing span {}", name))
+        };
+        let accept_span = span("accept_auto");
+        assert_eq!(accept_span.parent, None);
+        assert!(accept_span.events > 0);
+        let decode_span = span("decode");
+        assert_eq!(decode_span.parent, Some("accept_auto"));
+        assert!(decode_span.fields.contains(&("version", "V2".to_string())));
+        assert!(decode_span
+            .fields
+            .contains(&("consumed", V2_MESSAGE.len().to_string())));
+    }
+
+    #[tokio::test]
+    async fn test_accept_counters() {
+        // Messages have to be 'static for AcceptorBuilder
+        const V2_MESSAGE: &[u8] =
+            b"\r\n\r\n\0\r\nQUIT\n\x21\x11\x00\x0c\xc0\xa8\x00\x01\xc0\xa8\x00\x0b\xdc\x04\x01\xbb";
+        let messages: [&[u8]; 4] = [
+            b"PROXY TCP4 192.168.0.1 192.168.0.11 56324 443\r\n",
+            b"PROXY TCP4 192.168.0.2 192.168.0.11 56324 443\r\n",
+            V2_MESSAGE,
+            b"MEMAM PROXY HEADER, CHUDACEK JA",
+        ];
+
+        let counters = Arc::new(AcceptCounters::new());
+        let builder = AcceptorBuilder::new(ProtocolConfig::new(
+            false,
+            vec![ProtocolVersion::V1, ProtocolVersion::V2],
+        ))
+        .observer(counters.clone());
+        for message in messages.iter() {
+            builder
+                .build(*message)
+                .await
+                .expect("BUG: Cannot accept message");
+        }
+        Acceptor::new()
+            .require_proxy_header(true)
+            .observer(counters.clone())
+            .accept_auto(messages[3])
+            .await
+            .expect_err("BUG: Stream without PROXY header accepted");
+
+        assert_eq!(
+            counters.snapshot(),
+            AcceptCountersSnapshot {
+                v1: 2,
+                v2: 1,
+                no_header: 1,
+                rejected: 1,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_observer_on_reject() {
+        #[derive(Default)]
+        struct RejectRecorder {
+            rejected: std::sync::Mutex<Vec<(String, Vec<u8>)>>,
+        }
+
+        impl ProxyObserver for RejectRecorder {
+            fn on_accept(&self, _outcome: AcceptOutcome) {}
+
+            fn on_reject(&self, reason: &str, bytes: &[u8]) {
+                self.rejected
+                    .lock()
+                    .expect("BUG: Poisoned mutex")
+                    .push((reason.to_string(), bytes.to_vec()));
+            }
+        }
+
+        let malformed = b"PROXY TCP4 192.168.0.1 192.168.0.X 56324 443\r\n";
+        let long_garbage = [0x42u8; 2 * observer::REJECTED_BYTES_LIMIT];
+
+        let recorder = Arc::new(RejectRecorder::default());
+        for message in [&malformed[..], &long_garbage[..]].iter() {
+            Acceptor::new()
+                .require_proxy_header(true)
+                .observer(recorder.clone())
+                .accept_auto(*message)
+                .await
+                .expect_err("BUG: Invalid PROXY header accepted");
+        }
+
+        let rejected = recorder.rejected.lock().expect("BUG: Poisoned mutex");
+        assert_eq!(rejected.len(), 2);
+        assert_eq!(rejected[0].1, &malformed[..]);
+        assert_eq!(rejected[1].0, "No PROXY header detected");
+        assert_eq!(
+            rejected[1].1,
+            &long_garbage[..observer::REJECTED_BYTES_LIMIT]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_trusted_proxies_destination_port() {
+        const MESSAGE: &[u8] = b"PROXY TCP4 192.168.0.1 192.168.0.11 56324 443\r\n";
+        let proxy: IpAddr = [10, 0, 0, 1].into();
+        let builder = AcceptorBuilder::new(ProtocolConfig::new(
+            true,
+            vec![ProtocolVersion::V1, ProtocolVersion::V2],
+        ))
+        .trusted_proxies(vec![proxy]);
+
+        let ps = builder
+            .build_from_peer(MESSAGE, SocketAddr::new(proxy, 40000))
+            .await
+            .expect("BUG: Cannot accept message");
+        assert_eq!(ps.original_destination_port(), Some(443));
+
+        let ps = builder
+            .build_from_peer(MESSAGE, SocketAddr::new([10, 0, 0, 2].into(), 40000))
+            .await
+            .expect("BUG: Cannot accept message");
+        assert_eq!(ps.original_destination_port(), None);
+        // The declared address itself is still available for logging
+        assert!(ps.original_destination_addr().is_some());
+
+        // The peer is unknown
+        let ps = builder
+            .build(MESSAGE)
+            .await
+            .expect("BUG: Cannot accept message");
+        assert_eq!(ps.original_destination_port(), None);
+    }
+
     #[test]
     fn correct_proxy_info_format() {
         let src = SocketAddr::new(IpAddr::from([5, 4, 3, 2]), 5432);
@@ -21,16 +21,83 @@
 // contact us at opensource@braiins.com.
 
 use super::error::{Error, Result};
+use super::ProtocolVersion;
+use bytes::{Bytes, BytesMut};
 use ii_logging::slog::{Record, Serializer, KV};
 use std::convert::TryFrom;
 use std::fmt;
-use std::net::SocketAddr;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use tokio_util::codec::{Decoder, Encoder};
+
+#[cfg(feature = "tracing")]
+use tracing::debug;
 
 pub mod v1;
 pub mod v2;
 
+pub use v2::tlv::SslInfo;
+
 pub(crate) const MAX_HEADER_SIZE: usize = 536;
 
+/// Records the size of a decoded header into the `consumed` field of the current tracing span
+#[cfg(feature = "tracing")]
+pub(crate) fn record_consumed(len: usize) {
+    tracing::Span::current().record("consumed", len);
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn record_consumed(_len: usize) {}
+
+/// Codec that keeps a copy of all bytes consumed by the wrapped decoder, i.e. the raw PROXY
+/// header once it is decoded. Encoding is passed through. Only bytes that haven't been seen in
+/// previous calls are copied, so the overhead stays linear even when the header arrives in many
+/// small chunks.
+pub(crate) struct HeaderCapture<C> {
+    codec: C,
+    /// Bytes consumed by `codec` followed by a copy of the not yet consumed part of the buffer
+    captured: BytesMut,
+    consumed: usize,
+}
+
+impl<C> HeaderCapture<C> {
+    pub(crate) fn new(codec: C) -> Self {
+        Self {
+            codec,
+            captured: BytesMut::new(),
+            consumed: 0,
+        }
+    }
+
+    /// Bytes consumed by the wrapped codec so far
+    pub(crate) fn into_consumed(mut self) -> Bytes {
+        self.captured.truncate(self.consumed);
+        self.captured.freeze()
+    }
+}
+
+impl<C: Decoder> Decoder for HeaderCapture<C> {
+    type Item = C::Item;
+    type Error = C::Error;
+
+    fn decode(&mut self, buf: &mut BytesMut) -> std::result::Result<Option<C::Item>, C::Error> {
+        let mirrored = self.captured.len() - self.consumed;
+        self.captured.extend_from_slice(&buf[mirrored..]);
+        let len = buf.len();
+        let result = self.codec.decode(buf);
+        self.consumed += len - buf.len();
+        result
+    }
+}
+
+impl<I, C: Encoder<I>> Encoder<I> for HeaderCapture<C> {
+    type Error = C::Error;
+
+    fn encode(&mut self, item: I, buf: &mut BytesMut) -> std::result::Result<(), C::Error> {
+        self.codec.encode(item, buf)
+    }
+}
+
 /// Type of transport
 #[derive(PartialEq, Eq, Debug, Clone, Copy)]
 pub enum SocketType {
@@ -43,7 +110,7 @@ pub enum SocketType {
 }
 
 /// Contains information from PROXY protocol
-#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+#[derive(PartialEq, Eq, Debug, Clone)]
 pub struct ProxyInfo {
     /// Type of transport
     pub socket_type: SocketType,
@@ -51,6 +118,21 @@ pub struct ProxyInfo {
     pub original_source: Option<SocketAddr>,
     /// Original destination address passed in PROXY protocol
     pub original_destination: Option<SocketAddr>,
+    /// Type and value of TLV extensions following the addresses (PROXY protocol V2 only)
+    pub tlvs: Vec<(u8, Bytes)>,
+    /// Addresses of a connection over unix domain sockets, see `from_unix()`
+    unix: Option<UnixInfo>,
+    /// Family and transport byte of a decoded PROXY protocol V2 header, see `raw_type_byte()`
+    raw_type: Option<u8>,
+}
+
+/// Original addresses of a connection over unix domain sockets (PROXY protocol V2 only)
+#[derive(PartialEq, Eq, Debug, Clone)]
+struct UnixInfo {
+    source: PathBuf,
+    destination: PathBuf,
+    /// `SOCK_DGRAM` transport as opposed to `SOCK_STREAM`
+    datagram: bool,
 }
 
 impl Default for ProxyInfo {
@@ -59,7 +141,185 @@ impl Default for ProxyInfo {
             socket_type: SocketType::Unknown,
             original_source: Default::default(),
             original_destination: Default::default(),
+            tlvs: Vec::new(),
+            unix: None,
+            raw_type: None,
+        }
+    }
+}
+
+impl ProxyInfo {
+    /// Creates info describing a connection over unix stream sockets, such info can only be
+    /// encoded by PROXY protocol V2. The socket type of the info is `SocketType::Unknown`, the
+    /// paths are available via `original_source_unix()` and `original_destination_unix()`.
+    pub fn from_unix(source: impl Into<PathBuf>, destination: impl Into<PathBuf>) -> Self {
+        Self::with_unix(source.into(), destination.into(), false)
+    }
+
+    /// Same as `from_unix()` but for unix datagram sockets. Such header is only decoded by
+    /// `from_datagram()`.
+    pub fn from_unix_datagram(source: impl Into<PathBuf>, destination: impl Into<PathBuf>) -> Self {
+        Self::with_unix(source.into(), destination.into(), true)
+    }
+
+    fn with_unix(source: PathBuf, destination: PathBuf, datagram: bool) -> Self {
+        Self {
+            unix: Some(UnixInfo {
+                source,
+                destination,
+                datagram,
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// Original source path of a connection relayed over unix domain sockets
+    pub fn original_source_unix(&self) -> Option<&Path> {
+        self.unix.as_ref().map(|unix| unix.source.as_path())
+    }
+
+    /// Original destination path of a connection relayed over unix domain sockets
+    pub fn original_destination_unix(&self) -> Option<&Path> {
+        self.unix.as_ref().map(|unix| unix.destination.as_path())
+    }
+
+    /// Family (high nibble) and transport (low nibble) byte exactly as it appeared in the decoded
+    /// PROXY protocol V2 header, e.g. `0x11` for TCP over IPv4. This is diagnostic information
+    /// that allows detecting non-conformant senders, `None` unless decoded from a V2 header.
+    pub fn raw_type_byte(&self) -> Option<u8> {
+        self.raw_type
+    }
+
+    /// Address family nibble of `raw_type_byte()`, e.g. `0x1` for IPv4
+    pub fn raw_family_byte(&self) -> Option<u8> {
+        self.raw_type.map(|raw_type| raw_type >> 4)
+    }
+
+    /// Number of bytes the PROXY header of given `version` describing this info occupies when
+    /// encoded, zero if `version` cannot represent this info
+    pub fn encoded_len(&self, version: ProtocolVersion) -> usize {
+        match version {
+            ProtocolVersion::V1 => v1::encoded_len(self),
+            ProtocolVersion::V2 => v2::encoded_len(self),
+        }
+    }
+
+    /// Decodes PROXY header from the beginning of `bytes` using the codec of given `version`
+    /// without auto-detection, e.g. when analyzing a capture with known PROXY protocol version.
+    /// Returns the decoded info and the number of bytes the header occupies.
+    pub fn decode_as(bytes: &[u8], version: ProtocolVersion) -> Result<(Self, usize)> {
+        Self::decode_as_with(bytes, version, v1::V1Parsing::default())
+    }
+
+    /// Same as `decode_as` but V1 headers are parsed according to `v1_parsing`
+    pub fn decode_as_with(
+        bytes: &[u8],
+        version: ProtocolVersion,
+        v1_parsing: v1::V1Parsing,
+    ) -> Result<(Self, usize)> {
+        let mut buf = BytesMut::from(bytes);
+        let info = match version {
+            ProtocolVersion::V1 => v1::V1Codec::new().parsing(v1_parsing).decode(&mut buf)?,
+            ProtocolVersion::V2 => v2::V2Codec::new().decode(&mut buf)?,
+        }
+        .ok_or_else(|| Error::Proxy("Incomplete PROXY header".into()))?;
+        Ok((info, bytes.len() - buf.len()))
+    }
+
+    /// Decodes PROXY protocol V2 header from the beginning of a single datagram (UDP relays
+    /// send the header in the same packet as the first payload). Returns the decoded info and the
+    /// payload following the header.
+    pub fn from_datagram(buf: &[u8]) -> Result<(Self, &[u8])> {
+        const LEN_OFFSET: usize = v2::SIGNATURE.len() + 2;
+        if buf.len() < LEN_OFFSET + 2 {
+            return Err(Error::Proxy("Datagram too short for PROXY header".into()));
         }
+        let header_len =
+            LEN_OFFSET + 2 + u16::from_be_bytes([buf[LEN_OFFSET], buf[LEN_OFFSET + 1]]) as usize;
+        if buf.len() < header_len {
+            return Err(Error::Proxy("PROXY header exceeds datagram".into()));
+        }
+        // Only the header is copied, the payload is returned as is
+        let mut header = BytesMut::from(&buf[..header_len]);
+        let info = v2::V2Codec::new()
+            .datagram()
+            .decode(&mut header)?
+            .ok_or_else(|| Error::Proxy("Incomplete PROXY header in datagram".into()))?;
+        Ok((info, &buf[header_len..]))
+    }
+
+    /// Textual representation of the PROXY protocol V1 header without the terminating CRLF,
+    /// e.g. `PROXY TCP4 192.168.0.1 192.168.0.11 56324 443`. Fails for address families that
+    /// cannot be represented in V1.
+    pub fn to_v1_string(&self) -> Result<String> {
+        match (
+            self.socket_type,
+            self.original_source,
+            self.original_destination,
+        ) {
+            _ if self.unix.is_some() => Err(Error::Proxy(
+                "Unix socket addresses cannot be represented in PROXY protocol V1".into(),
+            )),
+            (SocketType::Ipv4, Some(SocketAddr::V4(_)), Some(SocketAddr::V4(_)))
+            | (SocketType::Ipv6, Some(SocketAddr::V6(_)), Some(SocketAddr::V6(_))) => {
+                let mut line = String::new();
+                v1::write_header(self, &mut line).expect("BUG: Cannot write to String");
+                line.truncate(line.trim_end_matches("\r\n").len());
+                Ok(line)
+            }
+            (SocketType::Unknown, _, _) => Err(Error::Proxy(
+                "Unknown address family cannot be represented in PROXY protocol V1".into(),
+            )),
+            _ => Err(Error::Proxy(
+                "Inconsistent source and destination addresses".into(),
+            )),
+        }
+    }
+
+    /// Original source IP address masked to the subnet of `v4_prefix` or `v6_prefix` bits based on
+    /// the address family, e.g. for aggregating connections by /24 or /48 subnets without
+    /// keeping exact client addresses. Prefixes longer than the address are capped.
+    pub fn original_source_subnet(&self, v4_prefix: u8, v6_prefix: u8) -> Option<IpAddr> {
+        match self.original_source?.ip() {
+            IpAddr::V4(ip) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - u32::from(v4_prefix.min(32)))
+                    .unwrap_or(0);
+                Some(IpAddr::V4(Ipv4Addr::from(u32::from(ip) & mask)))
+            }
+            IpAddr::V6(ip) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - u32::from(v6_prefix.min(128)))
+                    .unwrap_or(0);
+                Some(IpAddr::V6(Ipv6Addr::from(u128::from(ip) & mask)))
+            }
+        }
+    }
+
+    /// Value of the first TLV of type `typ`, see the `v2::tlv::PP2_TYPE_*` constants
+    pub fn tlv(&self, typ: u8) -> Option<&[u8]> {
+        self.tlvs
+            .iter()
+            .find(|(t, _)| *t == typ)
+            .map(|(_, value)| &value[..])
+    }
+
+    /// Host name the client connected to (typically SNI) if the proxy has passed the
+    /// `PP2_TYPE_AUTHORITY` TLV. Value that is not valid UTF-8 is ignored.
+    pub fn authority(&self) -> Option<&str> {
+        std::str::from_utf8(self.tlv(v2::tlv::PP2_TYPE_AUTHORITY)?).ok()
+    }
+
+    /// Details about the TLS connection of the client if the proxy has passed the
+    /// `PP2_TYPE_SSL` TLV. Malformed TLV is ignored.
+    pub fn ssl_info(&self) -> Option<SslInfo> {
+        let (_, value) = self
+            .tlvs
+            .iter()
+            .find(|(typ, _)| *typ == v2::tlv::PP2_TYPE_SSL)?;
+        SslInfo::parse(value.clone())
+            .map_err(|e| debug!("wire: Invalid SSL TLV: {}", e))
+            .ok()
     }
 }
 
@@ -71,18 +331,27 @@ impl TryFrom<(Option<SocketAddr>, Option<SocketAddr>)> for ProxyInfo {
                 socket_type: SocketType::Ipv4,
                 original_source: s,
                 original_destination: d,
+                tlvs: Vec::new(),
+                unix: None,
+                raw_type: None,
             }),
 
             (s @ Some(SocketAddr::V6(_)), d @ Some(SocketAddr::V6(_))) => Ok(ProxyInfo {
                 socket_type: SocketType::Ipv6,
                 original_source: s,
                 original_destination: d,
+                tlvs: Vec::new(),
+                unix: None,
+                raw_type: None,
             }),
 
             (None, None) => Ok(ProxyInfo {
                 socket_type: SocketType::Unknown,
                 original_source: None,
                 original_destination: None,
+                tlvs: Vec::new(),
+                unix: None,
+                raw_type: None,
             }),
 
             _ => Err(Error::Proxy(
@@ -92,15 +361,23 @@ impl TryFrom<(Option<SocketAddr>, Option<SocketAddr>)> for ProxyInfo {
     }
 }
 
+impl ProxyInfo {
+    /// Formats either the IP or the unix address, `None` when neither is known
+    fn format_addr(ip: Option<SocketAddr>, unix: Option<&Path>) -> Option<String> {
+        ip.map(|addr| addr.to_string())
+            .or_else(|| unix.map(|path| path.display().to_string()))
+    }
+}
+
 impl fmt::Display for ProxyInfo {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
             "ProxyInfo[SRC:{}, DST:{}]",
-            self.original_source
-                .map_or_else(|| "N/A".to_string(), |s| s.to_string()),
-            self.original_destination
-                .map_or_else(|| "N/A".to_string(), |s| s.to_string())
+            Self::format_addr(self.original_source, self.original_source_unix())
+                .unwrap_or_else(|| "N/A".to_string()),
+            Self::format_addr(self.original_destination, self.original_destination_unix())
+                .unwrap_or_else(|| "N/A".to_string())
         )
     }
 }
@@ -113,17 +390,250 @@ impl KV for ProxyInfo {
     ) -> ii_logging::slog::Result {
         const DST_KEY: &str = "PROXY_DST";
         const SRC_KEY: &str = "PROXY_SRC";
-        if let Some(src) = self.original_source {
-            serializer.emit_str(SRC_KEY, &src.to_string())?;
+        if let Some(src) = Self::format_addr(self.original_source, self.original_source_unix()) {
+            serializer.emit_str(SRC_KEY, &src)?;
         } else {
             serializer.emit_none(SRC_KEY)?;
         }
 
-        if let Some(dst) = self.original_destination {
-            serializer.emit_str(DST_KEY, &dst.to_string())?;
+        if let Some(dst) =
+            Self::format_addr(self.original_destination, self.original_destination_unix())
+        {
+            serializer.emit_str(DST_KEY, &dst)?;
         } else {
             serializer.emit_none(DST_KEY)?;
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::BytesMut;
+    use tokio_util::codec::Encoder;
+
+    #[test]
+    fn original_source_subnet() {
+        let info = |src: &str, dst: &str| {
+            ProxyInfo::try_from((src.parse().ok(), dst.parse().ok()))
+                .expect("BUG: Cannot build ProxyInfo")
+        };
+
+        let v4 = info("192.168.1.123:56324", "10.0.0.1:443");
+        assert_eq!(
+            v4.original_source_subnet(24, 48),
+            "192.168.1.0".parse().ok()
+        );
+        assert_eq!(v4.original_source_subnet(0, 0), "0.0.0.0".parse().ok());
+        assert_eq!(
+            v4.original_source_subnet(40, 48),
+            "192.168.1.123".parse().ok()
+        );
+
+        let v6 = info("[2001:db8:1234:5678::1]:56324", "[2001:db8::2]:443");
+        assert_eq!(
+            v6.original_source_subnet(24, 48),
+            "2001:db8:1234::".parse().ok()
+        );
+
+        assert_eq!(ProxyInfo::default().original_source_subnet(24, 48), None);
+    }
+
+    #[test]
+    fn datagram() {
+        let mut datagram = Vec::from(v2::SIGNATURE);
+        // PROXY command, UDP over IPv4
+        datagram.extend_from_slice(&[0x21, 0x12, 0, 12]);
+        datagram.extend_from_slice(&[10, 0, 0, 1, 10, 0, 0, 2, 0x13, 0x88, 0x00, 0x35]);
+        datagram.extend_from_slice(b"payload");
+
+        let (info, payload) = ProxyInfo::from_datagram(&datagram).expect("BUG: Cannot decode");
+        assert_eq!(SocketType::Ipv4, info.socket_type);
+        assert_eq!("10.0.0.1:5000".parse().ok(), info.original_source);
+        assert_eq!("10.0.0.2:53".parse().ok(), info.original_destination);
+        assert_eq!(b"payload", payload);
+
+        // UDP addresses are not decoded from a stream
+        let info = v2::V2Codec::new()
+            .decode(&mut BytesMut::from(&datagram[..]))
+            .expect("BUG: Cannot decode")
+            .expect("BUG: Header not complete");
+        assert_eq!(SocketType::Unknown, info.socket_type);
+        assert_eq!(None, info.original_source);
+
+        // Declared length exceeds the datagram
+        assert!(ProxyInfo::from_datagram(&datagram[..20]).is_err());
+        assert!(ProxyInfo::from_datagram(b"payload").is_err());
+    }
+
+    #[test]
+    fn decode_as() {
+        let v1 = b"PROXY TCP4 192.168.0.1 192.168.0.11 56324 443\r\npayload";
+        let (info, len) =
+            ProxyInfo::decode_as(v1, ProtocolVersion::V1).expect("BUG: Cannot decode V1");
+        assert_eq!(v1.len() - b"payload".len(), len);
+        assert_eq!("192.168.0.1:56324".parse().ok(), info.original_source);
+        assert_eq!("192.168.0.11:443".parse().ok(), info.original_destination);
+        assert_eq!(None, info.raw_type_byte());
+
+        let mut v2 = Vec::from(v2::SIGNATURE);
+        v2.extend_from_slice(&[0x21, 0x11, 0, 12]);
+        v2.extend_from_slice(&[10, 0, 0, 1, 10, 0, 0, 2, 0xdc, 0x04, 0x0d, 0x05]);
+        let header_len = v2.len();
+        v2.extend_from_slice(b"payload");
+        let (info, len) =
+            ProxyInfo::decode_as(&v2, ProtocolVersion::V2).expect("BUG: Cannot decode V2");
+        assert_eq!(header_len, len);
+        assert_eq!("10.0.0.1:56324".parse().ok(), info.original_source);
+        assert_eq!("10.0.0.2:3333".parse().ok(), info.original_destination);
+        assert_eq!(Some(0x11), info.raw_type_byte());
+        assert_eq!(Some(0x1), info.raw_family_byte());
+
+        // Hint doesn't match the actual header
+        ProxyInfo::decode_as(v1, ProtocolVersion::V2).expect_err("BUG: V1 decoded as V2");
+        ProxyInfo::decode_as(&v2, ProtocolVersion::V1).expect_err("BUG: V2 decoded as V1");
+        ProxyInfo::decode_as(&v2[..20], ProtocolVersion::V2)
+            .expect_err("BUG: Incomplete header decoded");
+
+        let v1 = b"PROXY TCP4 192.168.0.1  192.168.0.11 56324 443\r\npayload";
+        ProxyInfo::decode_as(v1, ProtocolVersion::V1).expect_err("BUG: Double spaces accepted");
+        let (info, len) =
+            ProxyInfo::decode_as_with(v1, ProtocolVersion::V1, v1::V1Parsing::Lenient)
+                .expect("BUG: Cannot decode V1 leniently");
+        assert_eq!(v1.len() - b"payload".len(), len);
+        assert_eq!("192.168.0.1:56324".parse().ok(), info.original_source);
+    }
+
+    #[test]
+    fn v1_string() {
+        let info = ProxyInfo::try_from((
+            "192.168.0.1:56324".parse().ok(),
+            "192.168.0.11:443".parse().ok(),
+        ))
+        .expect("BUG: Invalid addresses");
+        assert_eq!(
+            "PROXY TCP4 192.168.0.1 192.168.0.11 56324 443",
+            info.to_v1_string().expect("BUG: Cannot format V1 header")
+        );
+
+        let info = ProxyInfo::try_from((
+            "[2001:db8::1]:56324".parse().ok(),
+            "[2001:db8::2]:443".parse().ok(),
+        ))
+        .expect("BUG: Invalid addresses");
+        assert_eq!(
+            "PROXY TCP6 2001:db8::1 2001:db8::2 56324 443",
+            info.to_v1_string().expect("BUG: Cannot format V1 header")
+        );
+
+        assert!(ProxyInfo::default().to_v1_string().is_err());
+
+        // The V1 encoder refuses unix addresses the same way
+        let unix = ProxyInfo::from_unix("/run/client.sock", "/run/pool.sock");
+        assert!(unix.to_v1_string().is_err());
+        v1::V1Codec::new()
+            .encode(unix.clone(), &mut BytesMut::new())
+            .expect_err("BUG: Unix addresses encoded in V1");
+        assert_eq!(0, unix.encoded_len(ProtocolVersion::V1));
+    }
+
+    #[test]
+    fn encoded_len_matches_encoding() {
+        let addrs = [
+            ("1.2.3.4:5", "6.7.8.9:10"),
+            ("192.168.100.200:65535", "10.0.0.1:443"),
+            ("[::1]:1", "[::2]:2"),
+            (
+                "[2001:db8:85a3::8a2e:370:7334]:56324",
+                "[fe80::1ff:fe23:4567:890a]:443",
+            ),
+        ];
+        let mut infos: Vec<ProxyInfo> = addrs
+            .iter()
+            .map(|(src, dst)| {
+                let src = src.parse::<SocketAddr>().expect("BUG: Cannot parse IP");
+                let dst = dst.parse::<SocketAddr>().expect("BUG: Cannot parse IP");
+                ProxyInfo::try_from((Some(src), Some(dst))).expect("BUG: Invalid addresses")
+            })
+            .collect();
+        infos.push(ProxyInfo::default());
+        let mut with_tlvs = infos[0].clone();
+        with_tlvs.tlvs = vec![
+            (
+                v2::tlv::PP2_TYPE_AUTHORITY,
+                Bytes::from_static(b"example.com"),
+            ),
+            (v2::tlv::PP2_TYPE_NOOP, Bytes::new()),
+        ];
+        infos.push(with_tlvs);
+
+        for info in infos {
+            let mut buf = BytesMut::new();
+            v1::V1Codec::new()
+                .encode(info.clone(), &mut buf)
+                .expect("BUG: Cannot encode V1 header");
+            assert_eq!(
+                info.encoded_len(ProtocolVersion::V1),
+                buf.len(),
+                "BUG: V1 length mismatch for {}",
+                info
+            );
+
+            let mut buf = BytesMut::new();
+            v2::V2Codec::new()
+                .encode(info.clone(), &mut buf)
+                .expect("BUG: Cannot encode V2 header");
+            assert_eq!(
+                info.encoded_len(ProtocolVersion::V2),
+                buf.len(),
+                "BUG: V2 length mismatch for {}",
+                info
+            );
+        }
+    }
+
+    #[test]
+    fn tlv_accessors() {
+        use v2::tlv::*;
+
+        // Header as sent by AWS NLB with the VPC endpoint ID followed by padding
+        let mut header = Vec::from(v2::SIGNATURE);
+        header.extend_from_slice(&[0x21, 0x11, 0, 12 + 19 + 26 + 3]);
+        header.extend_from_slice(&[10, 0, 0, 1, 10, 0, 0, 2, 0xdc, 0x04, 0x0d, 0x05]);
+        header.extend_from_slice(&[PP2_TYPE_AUTHORITY, 0, 16]);
+        header.extend_from_slice(b"pool.example.com");
+        header.extend_from_slice(&[PP2_TYPE_AWS, 0, 23, PP2_SUBTYPE_AWS_VPCE_ID]);
+        header.extend_from_slice(b"vpce-08d2bf15fac5001c9");
+        header.extend_from_slice(&[PP2_TYPE_NOOP, 0, 0]);
+        let mut buf = BytesMut::from(&header[..]);
+        buf.extend_from_slice(b"payload");
+
+        let mut codec = v2::V2Codec::new();
+        let info = codec
+            .decode(&mut buf)
+            .expect("BUG: ProxyInfo decoding failed")
+            .expect("BUG: No ProxyInfo decoded");
+        assert_eq!(&b"payload"[..], &buf[..]);
+        assert_eq!(Some("pool.example.com"), info.authority());
+        assert_eq!(
+            Some(&b"\x01vpce-08d2bf15fac5001c9"[..]),
+            info.tlv(PP2_TYPE_AWS)
+        );
+        assert_eq!(Some(&[][..]), info.tlv(PP2_TYPE_NOOP));
+        assert_eq!(None, info.tlv(PP2_TYPE_SSL));
+
+        // Encoding the decoded info reproduces the original header
+        let mut encoded = BytesMut::new();
+        codec
+            .encode(info.clone(), &mut encoded)
+            .expect("BUG: Cannot encode V2 header");
+        assert_eq!(&header[..], &encoded[..]);
+
+        // Authority that is not valid UTF-8
+        let mut invalid = info;
+        invalid.tlvs = vec![(PP2_TYPE_AUTHORITY, Bytes::from_static(b"\xff"))];
+        assert_eq!(None, invalid.authority());
+        assert_eq!(Some(&b"\xff"[..]), invalid.tlv(PP2_TYPE_AUTHORITY));
+    }
+}
@@ -20,19 +20,40 @@
 // of such proprietary license or if you have any other questions, please
 // contact us at opensource@braiins.com.
 
+use std::fmt;
 use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::str::FromStr;
 
-use super::{ProxyInfo, SocketType, MAX_HEADER_SIZE};
+use super::{record_consumed, ProxyInfo, SocketType, MAX_HEADER_SIZE};
 use crate::proxy::error::{Error, Result};
 
-use bytes::{Buf, BufMut, BytesMut};
+use bytes::{Buf, BytesMut};
 use tokio_util::codec::{Decoder, Encoder};
 
+#[cfg(feature = "tracing")]
+use tracing::debug;
+
+/// Grammar of PROXY protocol v1 headers accepted by `V1Codec`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum V1Parsing {
+    /// Any deviation from the header grammar is refused, i.e. the fields have to be separated by
+    /// single spaces and an `UNKNOWN` header may carry either no fields or the complete field
+    /// list
+    Strict,
+    /// The fields have to be separated by single spaces, anything following `UNKNOWN` is
+    /// ignored as required by the specification
+    #[default]
+    Standard,
+    /// Same as `Standard` but repeated spaces between the fields are tolerated, e.g. for buggy
+    /// proxies. An `UNKNOWN` header with a partial field list yields no addresses.
+    Lenient,
+}
+
 /// Encoder and Decoder for PROXY protocol v1
 pub struct V1Codec {
     next_pos: usize,
     pass_header: bool,
+    parsing: V1Parsing,
 }
 
 impl Default for V1Codec {
@@ -46,6 +67,7 @@ impl V1Codec {
         V1Codec {
             next_pos: 0,
             pass_header: false,
+            parsing: V1Parsing::default(),
         }
     }
 
@@ -53,7 +75,34 @@ impl V1Codec {
         V1Codec {
             next_pos: 0,
             pass_header,
+            parsing: V1Parsing::default(),
+        }
+    }
+
+    /// Selects the accepted header grammar, `V1Parsing::Standard` by default. Headers that
+    /// don't match the grammar are refused with `Error::Proxy`.
+    pub fn parsing(self, parsing: V1Parsing) -> Self {
+        Self { parsing, ..self }
+    }
+
+    /// Discards the state of a partially decoded header so that the codec can be reused for
+    /// another stream. The state is reset automatically once a header is decoded. The codec
+    /// holds no allocations, therefore creating a new instance is equally cheap.
+    pub fn reset(&mut self) {
+        self.next_pos = 0;
+    }
+
+    /// Appends the header describing `item` to `buf` reserving the required capacity upfront, so
+    /// that a single buffer can be reused for many headers.
+    pub fn encode_into(&self, item: &ProxyInfo, buf: &mut BytesMut) -> Result<()> {
+        if item.unix.is_some() {
+            return Err(Error::Proxy(
+                "Unix socket addresses cannot be represented in PROXY protocol V1".into(),
+            ));
         }
+        buf.reserve(encoded_len(item));
+        write_header(item, buf).expect("BUG: Cannot write to BytesMut");
+        Ok(())
     }
 }
 
@@ -78,26 +127,53 @@ impl Decoder for V1Codec {
     type Item = ProxyInfo;
     type Error = Error;
 
+    /// Only the header is scanned for the EOL and consumed, data following it (e.g. a large
+    /// payload after `PROXY UNKNOWN\r\n`) are left untouched in `buf`
     fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>> {
-        if let Some(eol_pos) = buf[self.next_pos..].windows(2).position(|w| w == b"\r\n") {
+        let search_end = buf.len().min(MAX_HEADER_SIZE);
+        if let Some(eol_pos) = buf[self.next_pos.min(search_end)..search_end]
+            .windows(2)
+            .position(|w| w == b"\r\n")
+        {
             let eol_pos = eol_pos + self.next_pos;
+            self.reset();
+            record_consumed(eol_pos + 2);
             let header = std::str::from_utf8(&buf[..eol_pos])?;
 
             debug!("Proxy header is {}", header);
-            let parts: Vec<_> = header.split(' ').collect();
-            if parts[0] != "PROXY" {
+            let parts: Vec<_> = match self.parsing {
+                V1Parsing::Lenient => header.split(' ').filter(|part| !part.is_empty()).collect(),
+                V1Parsing::Strict | V1Parsing::Standard => header.split(' ').collect(),
+            };
+            if parts.first() != Some(&"PROXY") {
                 return Err(Error::Proxy("Protocol tag is wrong".into()));
             }
+            if self.parsing == V1Parsing::Strict && parts.iter().any(|part| part.is_empty()) {
+                return Err(Error::Proxy(format!(
+                    "Empty field in proxy header v1: {}",
+                    header
+                )));
+            }
             if parts.len() < 2 {
                 return Err(Error::Proxy("At least two parts are needed".into()));
             }
 
             let res = match parts[1] {
-                "UNKNOWN" => Ok(Some(ProxyInfo {
-                    socket_type: SocketType::Unknown,
-                    original_source: None,
-                    original_destination: None,
-                })),
+                // Both the bare form and the form with the complete field list are valid
+                "UNKNOWN"
+                    if self.parsing != V1Parsing::Strict
+                        || parts.len() == 2
+                        || parts.len() == 6 =>
+                {
+                    Ok(Some(ProxyInfo {
+                        socket_type: SocketType::Unknown,
+                        original_source: None,
+                        original_destination: None,
+                        tlvs: Vec::new(),
+                        unix: None,
+                        raw_type: None,
+                    }))
+                }
                 "TCP4" if parts.len() == 6 => {
                     let (original_source, original_destination) =
                         parse_addresses::<Ipv4Addr>(&parts)?;
@@ -108,6 +184,9 @@ impl Decoder for V1Codec {
                         socket_type: SocketType::Ipv4,
                         original_source: Some(original_source),
                         original_destination: Some(original_destination),
+                        tlvs: Vec::new(),
+                        unix: None,
+                        raw_type: None,
                     }))
                 }
                 "TCP6" if parts.len() == 6 => {
@@ -120,6 +199,9 @@ impl Decoder for V1Codec {
                         socket_type: SocketType::Ipv6,
                         original_source: Some(original_source),
                         original_destination: Some(original_destination),
+                        tlvs: Vec::new(),
+                        unix: None,
+                        raw_type: None,
                     }))
                 }
                 _ => Err(Error::Proxy(format!("Invalid proxy header v1: {}", header))),
@@ -142,40 +224,60 @@ impl Decoder for V1Codec {
 impl Encoder<ProxyInfo> for V1Codec {
     type Error = Error;
     fn encode(&mut self, item: ProxyInfo, header: &mut BytesMut) -> Result<()> {
-        header.put(&b"PROXY "[..]);
-
-        let proto = match item {
-            ProxyInfo {
-                socket_type: SocketType::Ipv4,
-                ..
-            } => "TCP4",
-            ProxyInfo {
-                socket_type: SocketType::Ipv6,
-                ..
-            } => "TCP6",
-            ProxyInfo {
-                socket_type: SocketType::Unknown,
-                ..
-            } => {
-                header.put(&b"UNKNOWN\r\n"[..]);
-                return Ok(());
-            }
-        };
-        let original_source = item.original_source.expect("BUG: Source IP missing");
-        let original_destination = item.original_destination.expect("BUG: Source IP missing");
-        header.put(
-            format!(
-                "{} {} {} {} {}\r\n",
-                proto,
-                original_source.ip(),
-                original_destination.ip(),
-                original_source.port(),
-                original_destination.port()
-            )
-            .as_bytes(),
-        );
+        self.encode_into(&item, header)
+    }
+}
 
-        Ok(())
+/// Writes the textual representation of the PROXY header, fails for unix socket addresses
+pub(super) fn write_header<W: fmt::Write>(item: &ProxyInfo, header: &mut W) -> fmt::Result {
+    if item.unix.is_some() {
+        return Err(fmt::Error);
+    }
+    header.write_str("PROXY ")?;
+
+    let proto = match item {
+        ProxyInfo {
+            socket_type: SocketType::Ipv4,
+            ..
+        } => "TCP4",
+        ProxyInfo {
+            socket_type: SocketType::Ipv6,
+            ..
+        } => "TCP6",
+        ProxyInfo {
+            socket_type: SocketType::Unknown,
+            ..
+        } => return header.write_str("UNKNOWN\r\n"),
+    };
+    let original_source = item.original_source.expect("BUG: Source IP missing");
+    let original_destination = item.original_destination.expect("BUG: Source IP missing");
+    write!(
+        header,
+        "{} {} {} {} {}\r\n",
+        proto,
+        original_source.ip(),
+        original_destination.ip(),
+        original_source.port(),
+        original_destination.port()
+    )
+}
+
+/// Length of the PROXY header describing `item`, see `ProxyInfo::encoded_len()`
+pub(super) fn encoded_len(item: &ProxyInfo) -> usize {
+    /// Only counts the written bytes
+    struct Counter(usize);
+
+    impl fmt::Write for Counter {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            self.0 += s.len();
+            Ok(())
+        }
+    }
+
+    let mut counter = Counter(0);
+    match write_header(item, &mut counter) {
+        Ok(()) => counter.0,
+        Err(_) => 0,
     }
 }
 
@@ -249,6 +351,118 @@ mod tests {
         assert_eq!(b"Usak", &buf[..]);
     }
 
+    #[test]
+    fn test_v1_codec_reuse() {
+        let mut codec = V1Codec::new();
+        let mut buf = BytesMut::from(&b"PROXY TCP4 192.168.0.1 192.168.0.11 56324"[..]);
+        assert!(codec
+            .decode(&mut buf)
+            .expect("BUG: cannot decode")
+            .is_none());
+        buf.put(&b" 443\r\n"[..]);
+        let first = codec
+            .decode(&mut buf)
+            .expect("BUG: cannot decode")
+            .expect("BUG: Header decoding failed");
+        assert_eq!(SocketType::Ipv4, first.socket_type);
+
+        // An independent, shorter header
+        let mut buf = BytesMut::from(&b"PROXY UNKNOWN\r\n"[..]);
+        let second = codec
+            .decode(&mut buf)
+            .expect("BUG: cannot decode")
+            .expect("BUG: Header decoding failed");
+        assert_eq!(SocketType::Unknown, second.socket_type);
+
+        // Explicit reset after an incomplete header
+        let mut buf = BytesMut::from(&b"PROXY TCP4 192.168.0.1 192.168.0.11 56324 443"[..]);
+        assert!(codec
+            .decode(&mut buf)
+            .expect("BUG: cannot decode")
+            .is_none());
+        codec.reset();
+        let mut buf = BytesMut::from(&b"PROXY UNKNOWN\r\n"[..]);
+        let third = codec
+            .decode(&mut buf)
+            .expect("BUG: cannot decode")
+            .expect("BUG: Header decoding failed");
+        assert_eq!(SocketType::Unknown, third.socket_type);
+    }
+
+    #[test]
+    fn test_v1_unknown_large_payload() {
+        const PAYLOAD_LEN: usize = 4 * 1024 * 1024;
+        let payload: Vec<u8> = b"line\r\n"
+            .iter()
+            .cycle()
+            .take(PAYLOAD_LEN)
+            .copied()
+            .collect();
+        let mut buf = BytesMut::from(&b"PROXY UNKNOWN\r\n"[..]);
+        buf.extend_from_slice(&payload);
+
+        let info = V1Codec::new()
+            .decode(&mut buf)
+            .expect("BUG: cannot decode")
+            .expect("BUG: Header decoding failed");
+        assert_eq!(SocketType::Unknown, info.socket_type);
+        assert!(buf == payload, "BUG: Payload consumed by the codec");
+
+        // EOL beyond the maximum header size is not searched for in the payload
+        let mut buf = BytesMut::from(&b"PROXY UNKNOWN "[..]);
+        buf.extend_from_slice(&[b'A'; MAX_HEADER_SIZE]);
+        buf.extend_from_slice(&payload);
+        V1Codec::new()
+            .decode(&mut buf)
+            .expect_err("BUG: Overlong header accepted");
+    }
+
+    #[test]
+    fn test_v1_parsing() {
+        use V1Parsing::{Lenient, Standard, Strict};
+        let decode = |parsing: V1Parsing, header: &[u8]| {
+            V1Codec::new()
+                .parsing(parsing)
+                .decode(&mut BytesMut::from(header))
+                .map(|info| info.expect("BUG: Header not complete"))
+        };
+
+        // Repeated spaces between the fields are refused unless parsing leniently
+        let header = b"PROXY TCP4  192.168.0.1 192.168.0.11  56324 443\r\n";
+        let info = decode(Lenient, header).expect("BUG: Double spaces refused in lenient mode");
+        assert_eq!(info.original_source, "192.168.0.1:56324".parse().ok());
+        assert_eq!(info.original_destination, "192.168.0.11:443".parse().ok());
+        decode(Standard, header).expect_err("BUG: Double spaces accepted by default");
+        decode(Strict, header).expect_err("BUG: Double spaces accepted in strict mode");
+        assert_eq!(V1Parsing::default(), Standard);
+
+        // UNKNOWN with a truncated field list
+        let header = b"PROXY UNKNOWN 192.168.0.1 192.168.0.11\r\n";
+        for &parsing in &[Lenient, Standard] {
+            let info = decode(parsing, header).expect("BUG: Truncated UNKNOWN refused");
+            assert_eq!(SocketType::Unknown, info.socket_type);
+            assert_eq!(info.original_source, None);
+            assert_eq!(info.original_destination, None);
+        }
+        decode(Strict, header).expect_err("BUG: Truncated UNKNOWN accepted in strict mode");
+
+        // Complete headers are valid in all modes
+        for &parsing in &[Lenient, Standard, Strict] {
+            for &header in &[
+                &b"PROXY UNKNOWN\r\n"[..],
+                &b"PROXY UNKNOWN 192.168.0.1 192.168.0.11 56324 443\r\n"[..],
+                &b"PROXY TCP4 192.168.0.1 192.168.0.11 56324 443\r\n"[..],
+            ] {
+                decode(parsing, header).expect("BUG: Valid header refused");
+            }
+            // Addresses cannot be recovered from a truncated TCP4 header
+            decode(parsing, b"PROXY TCP4 192.168.0.1 192.168.0.11 56324\r\n")
+                .expect_err("BUG: Truncated TCP4 accepted");
+            decode(parsing, b"PROXY \r\n").expect_err("BUG: Header without protocol accepted");
+            decode(parsing, b" \r\n").expect_err("BUG: Header without tag accepted");
+        }
+    }
+
     #[test]
     fn test_long_v1_header_without_eol() {
         let data = (b'a'..b'z').cycle().take(600).collect::<Vec<_>>();
@@ -273,6 +487,9 @@ mod tests {
             socket_type: SocketType::Ipv4,
             original_source: "192.168.0.1:56324".parse().ok(),
             original_destination: "192.168.0.11:443".parse().ok(),
+            tlvs: Vec::new(),
+            unix: None,
+            raw_type: None,
         };
 
         let mut buf = BytesMut::new();
@@ -283,6 +500,38 @@ mod tests {
         assert_eq!(header_bytes, &buf[..]);
     }
 
+    #[test]
+    fn test_v1_encode_into() {
+        let info = ProxyInfo {
+            socket_type: SocketType::Ipv4,
+            original_source: "192.168.0.1:56324".parse().ok(),
+            original_destination: "192.168.0.11:443".parse().ok(),
+            tlvs: Vec::new(),
+            unix: None,
+            raw_type: None,
+        };
+        let info2 = ProxyInfo {
+            original_source: "192.168.0.2:56325".parse().ok(),
+            ..info.clone()
+        };
+        let mut codec = V1Codec::new();
+        let mut buf = BytesMut::new();
+        for item in [&info, &info2] {
+            codec
+                .encode_into(item, &mut buf)
+                .expect("BUG: Cannot encode V1 header");
+        }
+
+        for expected in [info, info2] {
+            let decoded = codec
+                .decode(&mut buf)
+                .expect("BUG: Cannot decode V1 header")
+                .expect("BUG: No V1 header decoded");
+            assert_eq!(expected, decoded);
+        }
+        assert!(buf.is_empty());
+    }
+
     #[test]
     fn test_v1_header_creation_for_ipv6() {
         let header_bytes = b"PROXY TCP6 ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff aaaa:aaaa:aaaa:aaaa:aaaa:aaaa:aaaa:aaaa 65535 65534\r\n";
@@ -294,6 +543,9 @@ mod tests {
             original_destination: "[aaaa:aaaa:aaaa:aaaa:aaaa:aaaa:aaaa:aaaa]:65534"
                 .parse()
                 .ok(),
+            tlvs: Vec::new(),
+            unix: None,
+            raw_type: None,
         };
 
         let mut buf = BytesMut::new();
@@ -20,22 +20,33 @@
 // of such proprietary license or if you have any other questions, please
 // contact us at opensource@braiins.com.
 
+use std::convert::TryFrom;
 use std::net::SocketAddr;
 
-use super::{ProxyInfo, SocketType};
+use super::{record_consumed, ProxyInfo, SocketType};
 use crate::proxy::error::{Error, Result};
 
 use bytes::BytesMut;
 use proto::*;
 use tokio_util::codec::{Decoder, Encoder};
 
+#[cfg(feature = "tracing")]
+use tracing::warn;
+
 pub mod proto;
+pub mod tlv;
 
 pub const SIGNATURE: &[u8] = b"\x0D\x0A\x0D\x0A\x00\x0D\x0A\x51\x55\x49\x54\x0A";
 
 pub struct V2Codec {
     socket_type: Option<SocketType>,
     remains: usize,
+    /// Family and transport byte of the header being decoded
+    protocol: u8,
+    /// Maximum number of TLVs accepted in a single header
+    max_tlvs: usize,
+    /// Whether UDP addresses are decoded, see `datagram()`
+    datagram: bool,
 }
 
 impl Default for V2Codec {
@@ -43,6 +54,9 @@ impl Default for V2Codec {
         V2Codec {
             socket_type: None,
             remains: 0,
+            protocol: PROTOCOL_UNSPEC,
+            max_tlvs: tlv::DEFAULT_MAX_TLVS,
+            datagram: false,
         }
     }
 }
@@ -51,6 +65,97 @@ impl V2Codec {
     pub fn new() -> Self {
         Default::default()
     }
+
+    /// Headers carrying more than `max_tlvs` TLVs are rejected with an error, so that a sender
+    /// can't force excessive allocations by packing many tiny TLVs into the header. Defaults to
+    /// `tlv::DEFAULT_MAX_TLVS`.
+    pub fn max_tlvs(self, max_tlvs: usize) -> Self {
+        Self { max_tlvs, ..self }
+    }
+
+    /// Decodes addresses of UDP and unix datagram headers, which only make sense when the header
+    /// is received in a datagram (see `ProxyInfo::from_datagram()`). Stream acceptors treat
+    /// datagram transports as unsupported protocol.
+    pub(super) fn datagram(self) -> Self {
+        Self {
+            datagram: true,
+            ..self
+        }
+    }
+
+    /// Discards the state of a partially decoded header so that the codec can be reused for
+    /// another stream. The state is reset automatically once a header is decoded. The codec
+    /// holds no allocations, therefore creating a new instance is equally cheap.
+    pub fn reset(&mut self) {
+        self.socket_type = None;
+        self.remains = 0;
+        self.protocol = PROTOCOL_UNSPEC;
+    }
+
+    /// Decodes the address block of the socket type `t` and the TLVs following it
+    fn decode_addresses(&self, t: SocketType, mut data_buf: BytesMut) -> Result<ProxyInfo> {
+        Ok(match t {
+            SocketType::Ipv4 => {
+                let addresses = Ip4Addresses::deserialize(&mut data_buf)?;
+                let (src, dst) = addresses.into();
+                ProxyInfo {
+                    socket_type: t,
+                    original_source: Some(SocketAddr::V4(src)),
+                    original_destination: Some(SocketAddr::V4(dst)),
+                    tlvs: tlv::parse(data_buf.freeze(), self.max_tlvs)?,
+                    unix: None,
+                    raw_type: None,
+                }
+            }
+            SocketType::Ipv6 => {
+                let addresses = Ip6Addresses::deserialize(&mut data_buf)?;
+                let (src, dst) = addresses.into();
+                ProxyInfo {
+                    socket_type: t,
+                    original_source: Some(SocketAddr::V6(src)),
+                    original_destination: Some(SocketAddr::V6(dst)),
+                    tlvs: tlv::parse(data_buf.freeze(), self.max_tlvs)?,
+                    unix: None,
+                    raw_type: None,
+                }
+            }
+            SocketType::Unknown => match self.protocol {
+                PROTOCOL_UNIX_SOCKET => self.decode_unix(data_buf, false)?,
+                PROTOCOL_UNIX_DATAGRAM if self.datagram => self.decode_unix(data_buf, true)?,
+                PROTOCOL_UNSPEC => ProxyInfo {
+                    socket_type: t,
+                    original_source: None,
+                    original_destination: None,
+                    tlvs: tlv::parse(data_buf.freeze(), self.max_tlvs)?,
+                    unix: None,
+                    raw_type: None,
+                },
+                // Address block of unsupported protocol cannot be told apart from the TLVs
+                _ => ProxyInfo::default(),
+            },
+        })
+    }
+
+    fn decode_unix(&self, mut data_buf: BytesMut, datagram: bool) -> Result<ProxyInfo> {
+        let addresses = UnixAddresses::deserialize(&mut data_buf)?;
+        let (src, dst) = addresses.into();
+        Ok(ProxyInfo {
+            tlvs: tlv::parse(data_buf.freeze(), self.max_tlvs)?,
+            ..ProxyInfo::with_unix(src, dst, datagram)
+        })
+    }
+
+    /// Appends the header describing `item` to `buf` reserving the required capacity upfront, so
+    /// that a single buffer can be reused for many headers. `buf` is left intact on error.
+    pub fn encode_into(&self, item: &ProxyInfo, buf: &mut BytesMut) -> Result<()> {
+        buf.reserve(encoded_len(item));
+        let start = buf.len();
+        let result = write_header(item, buf);
+        if result.is_err() {
+            buf.truncate(start);
+        }
+        result
+    }
 }
 
 impl Decoder for V2Codec {
@@ -64,34 +169,15 @@ impl Decoder for V2Codec {
                     if self.remains > 0 && buf.len() < self.remains {
                         return Ok(None);
                     } else {
-                        let mut data_buf = buf.split_to(self.remains);
-                        let info = match t {
-                            SocketType::Ipv4 => {
-                                let addresses = Ip4Addresses::deserialize(&mut data_buf)?;
-                                let (src, dst) = addresses.into();
-                                ProxyInfo {
-                                    socket_type: t,
-                                    original_source: Some(SocketAddr::V4(src)),
-                                    original_destination: Some(SocketAddr::V4(dst)),
-                                }
-                            }
-                            SocketType::Ipv6 => {
-                                let addresses = Ip6Addresses::deserialize(&mut data_buf)?;
-                                let (src, dst) = addresses.into();
-                                ProxyInfo {
-                                    socket_type: t,
-                                    original_source: Some(SocketAddr::V6(src)),
-                                    original_destination: Some(SocketAddr::V6(dst)),
-                                }
-                            }
-                            SocketType::Unknown => ProxyInfo {
-                                socket_type: t,
-                                original_source: None,
-                                original_destination: None,
-                            },
-                        };
-                        self.socket_type = None;
-                        self.remains = 0;
+                        let data_buf = buf.split_to(self.remains);
+                        let result = self.decode_addresses(t, data_buf);
+                        let (remains, protocol) = (self.remains, self.protocol);
+                        // The whole header has been consumed, the codec is ready for another one
+                        // even if the header turns out to be invalid
+                        self.reset();
+                        let mut info = result?;
+                        info.raw_type = Some(protocol);
+                        record_consumed(SIZE_HEADER as usize + remains);
                         return Ok(Some(info));
                     }
                 }
@@ -101,9 +187,23 @@ impl Decoder for V2Codec {
                     } else {
                         let header = Header::deserialize(buf)?;
                         self.remains = header.len as usize;
+                        self.protocol = header.protocol;
                         match header.protocol {
                             PROTOCOL_TCP_IP4 => self.socket_type = Some(SocketType::Ipv4),
                             PROTOCOL_TCP_IP6 => self.socket_type = Some(SocketType::Ipv6),
+                            PROTOCOL_UDP_IP4 if self.datagram => {
+                                self.socket_type = Some(SocketType::Ipv4)
+                            }
+                            PROTOCOL_UDP_IP6 if self.datagram => {
+                                self.socket_type = Some(SocketType::Ipv6)
+                            }
+                            // Unix addresses are decoded along with unknown socket type
+                            PROTOCOL_UNSPEC | PROTOCOL_UNIX_SOCKET => {
+                                self.socket_type = Some(SocketType::Unknown)
+                            }
+                            PROTOCOL_UNIX_DATAGRAM if self.datagram => {
+                                self.socket_type = Some(SocketType::Unknown)
+                            }
                             p => {
                                 warn!("Yet unsupported protocol, code {}", p);
                                 self.socket_type = Some(SocketType::Unknown);
@@ -119,42 +219,72 @@ impl Decoder for V2Codec {
 impl Encoder<ProxyInfo> for V2Codec {
     type Error = Error;
     fn encode(&mut self, item: ProxyInfo, buf: &mut BytesMut) -> Result<()> {
-        let header = Header::new(item.socket_type);
-        header.serialize(buf);
-        match item.socket_type {
-            SocketType::Ipv4 => {
-                if let (Some(SocketAddr::V4(src)), Some(SocketAddr::V4(dst))) =
-                    (item.original_source, item.original_destination)
-                {
-                    let addresses: Ip4Addresses = (src, dst).into();
-                    addresses.serialize(buf);
-                } else {
-                    return Err(Error::Proxy("Both V4 addresses must be present".into()));
-                }
+        self.encode_into(&item, buf)
+    }
+}
+
+/// Writes the binary representation of the PROXY header
+fn write_header(item: &ProxyInfo, buf: &mut BytesMut) -> Result<()> {
+    let mut header = address_header(item);
+    header.len = u16::try_from(header.len as usize + tlv::encoded_len(&item.tlvs))
+        .map_err(|_| Error::Proxy("TLVs exceed maximum header length".into()))?;
+    header.serialize(buf);
+    if let Some(unix) = &item.unix {
+        let addresses =
+            UnixAddresses::try_from((unix.source.as_path(), unix.destination.as_path()))?;
+        addresses.serialize(buf);
+        tlv::serialize(&item.tlvs, buf);
+        return Ok(());
+    }
+    match item.socket_type {
+        SocketType::Ipv4 => {
+            if let (Some(SocketAddr::V4(src)), Some(SocketAddr::V4(dst))) =
+                (item.original_source, item.original_destination)
+            {
+                let addresses: Ip4Addresses = (src, dst).into();
+                addresses.serialize(buf);
+            } else {
+                return Err(Error::Proxy("Both V4 addresses must be present".into()));
             }
+        }
 
-            SocketType::Ipv6 => {
-                if let (Some(SocketAddr::V6(src)), Some(SocketAddr::V6(dst))) =
-                    (item.original_source, item.original_destination)
-                {
-                    let addresses: Ip6Addresses = (src, dst).into();
-                    addresses.serialize(buf);
-                } else {
-                    return Err(Error::Proxy("Both V4 addresses must be present".into()));
-                }
+        SocketType::Ipv6 => {
+            if let (Some(SocketAddr::V6(src)), Some(SocketAddr::V6(dst))) =
+                (item.original_source, item.original_destination)
+            {
+                let addresses: Ip6Addresses = (src, dst).into();
+                addresses.serialize(buf);
+            } else {
+                return Err(Error::Proxy("Both V4 addresses must be present".into()));
             }
-            SocketType::Unknown => (),
         }
+        SocketType::Unknown => (),
+    }
+    tlv::serialize(&item.tlvs, buf);
+
+    Ok(())
+}
 
-        Ok(())
+/// Length of the PROXY header describing `item`, see `ProxyInfo::encoded_len()`
+pub(super) fn encoded_len(item: &ProxyInfo) -> usize {
+    let header = address_header(item);
+    (SIZE_HEADER + header.len) as usize + tlv::encoded_len(&item.tlvs)
+}
+
+/// Fixed part of the header describing the addresses of `item`
+fn address_header(item: &ProxyInfo) -> Header {
+    match &item.unix {
+        Some(unix) => Header::new_unix(unix.datagram),
+        None => Header::new(item.socket_type),
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use bytes::BufMut;
+    use bytes::{BufMut, Bytes};
     use futures::StreamExt;
+    use std::path::Path;
     use tokio::io::{AsyncRead, AsyncWrite};
     use tokio_util::codec::{Framed, FramedParts};
 
@@ -205,6 +335,73 @@ mod tests {
         assert_eq!(5, buf.len());
     }
 
+    #[test]
+    fn test_v2_tlv_length_mismatch() {
+        // Builds IPv4 header with declared length covering the addresses and `tlvs` bytes
+        let msg_with_tlvs = |tlvs: &[u8]| {
+            let mut buf = BytesMut::new();
+            buf.extend_from_slice(SIGNATURE);
+            buf.put_u8(0x21);
+            buf.put_u8(0x11);
+            buf.put_u16(12 + tlvs.len() as u16);
+            buf.extend(&[127, 0, 0, 1, 127, 0, 0, 2, 0, 80, 1, 187]);
+            buf.extend(tlvs);
+            buf.extend(b"Hello");
+            buf
+        };
+
+        let mut buf = msg_with_tlvs(&[tlv::PP2_TYPE_NOOP, 0, 2, 0, 0]);
+        let info = V2Codec::new()
+            .decode(&mut buf)
+            .expect("BUG: ProxyInfo decoding failed")
+            .expect("BUG: No ProxyInfo decoded");
+        assert_eq!(
+            vec![(tlv::PP2_TYPE_NOOP, Bytes::from_static(&[0, 0]))],
+            info.tlvs
+        );
+        assert_eq!(&b"Hello"[..], &buf[..]);
+
+        // TLV value overruns the declared length into the payload
+        let mut buf = msg_with_tlvs(&[tlv::PP2_TYPE_NOOP, 0, 4, 0, 0]);
+        V2Codec::new()
+            .decode(&mut buf)
+            .expect_err("BUG: TLV overrunning header length accepted");
+
+        // Declared length leaves bytes that don't form a TLV
+        let mut buf = msg_with_tlvs(&[tlv::PP2_TYPE_NOOP, 0, 0, 0, 0]);
+        V2Codec::new()
+            .decode(&mut buf)
+            .expect_err("BUG: Bytes trailing TLVs accepted");
+    }
+
+    #[test]
+    fn test_v2_codec_reuse() {
+        let mut codec = V2Codec::new();
+        for _ in 0..2 {
+            let mut buf = test_msg_ip4("Hello");
+            let info = codec
+                .decode(&mut buf)
+                .expect("BUG: ProxyInfo not decoded")
+                .expect("BUG: ProxyInfo decoding failed");
+            assert_eq!(SocketType::Ipv4, info.socket_type);
+            assert_eq!(b"Hello", &buf[..]);
+        }
+
+        // Explicit reset after an incomplete header
+        let mut buf = test_msg_ip4("");
+        buf.truncate(buf.len() - 1);
+        assert!(codec
+            .decode(&mut buf)
+            .expect("BUG: cannot decode")
+            .is_none());
+        codec.reset();
+        let mut buf = test_msg_ip4("");
+        codec
+            .decode(&mut buf)
+            .expect("BUG: ProxyInfo not decoded")
+            .expect("BUG: ProxyInfo decoding failed");
+    }
+
     #[tokio::test]
     async fn test_accept_v2_framed() {
         let buf = std::io::Cursor::new(test_msg_ip4("Hello").to_vec());
@@ -224,6 +421,9 @@ mod tests {
             socket_type: SocketType::Ipv4,
             original_source: Some(src_addr),
             original_destination: Some(dst_addr),
+            tlvs: Vec::new(),
+            unix: None,
+            raw_type: None,
         };
         let mut buf = BytesMut::new();
         let mut codec = V2Codec::new();
@@ -234,10 +434,76 @@ mod tests {
             .decode(&mut buf)
             .expect("BUG: No ProxyInfo decoded")
             .expect("BUG: ProxyInfo decoding failed");
-        assert_eq!(info, info2);
+        assert_eq!(Some(PROTOCOL_TCP_IP4), info2.raw_type_byte());
+        assert_eq!(Some(0x1), info2.raw_family_byte());
+        assert_eq!(
+            info,
+            ProxyInfo {
+                raw_type: None,
+                ..info2
+            }
+        );
         assert!(buf.is_empty());
     }
 
+    #[test]
+    fn test_v2_encode_into() {
+        let info = ProxyInfo {
+            socket_type: SocketType::Ipv4,
+            original_source: "127.0.0.1:80".parse().ok(),
+            original_destination: "127.0.0.2:443".parse().ok(),
+            tlvs: Vec::new(),
+            unix: None,
+            raw_type: None,
+        };
+        let info2 = ProxyInfo {
+            socket_type: SocketType::Ipv6,
+            original_source: "[::1]:80".parse().ok(),
+            original_destination: "[::2]:443".parse().ok(),
+            tlvs: vec![(tlv::PP2_TYPE_AUTHORITY, Bytes::from_static(b"example.com"))],
+            unix: None,
+            raw_type: None,
+        };
+        let mut codec = V2Codec::new();
+        let mut buf = BytesMut::new();
+        codec
+            .encode_into(&info, &mut buf)
+            .expect("BUG: encoding failed");
+        codec
+            .encode_into(&info2, &mut buf)
+            .expect("BUG: encoding failed");
+        assert_eq!(buf.len(), encoded_len(&info) + encoded_len(&info2));
+
+        for (expected, raw_type) in [(&info, PROTOCOL_TCP_IP4), (&info2, PROTOCOL_TCP_IP6)] {
+            let decoded = codec
+                .decode(&mut buf)
+                .expect("BUG: ProxyInfo decoding failed")
+                .expect("BUG: No ProxyInfo decoded");
+            assert_eq!(Some(raw_type), decoded.raw_type_byte());
+            assert_eq!(
+                expected,
+                &ProxyInfo {
+                    raw_type: None,
+                    ..decoded
+                }
+            );
+        }
+        assert!(buf.is_empty());
+
+        // A failed encoding leaves the buffer intact
+        let invalid = ProxyInfo {
+            original_destination: info2.original_destination,
+            ..info.clone()
+        };
+        codec
+            .encode_into(&info, &mut buf)
+            .expect("BUG: encoding failed");
+        codec
+            .encode_into(&invalid, &mut buf)
+            .expect_err("BUG: Mismatched addresses encoded");
+        assert_eq!(buf.len(), encoded_len(&info));
+    }
+
     #[test]
     fn test_v2_ip6_encode_decode() {
         let src_addr: SocketAddr = "[ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff]:80"
@@ -250,6 +516,9 @@ mod tests {
             socket_type: SocketType::Ipv6,
             original_source: Some(src_addr),
             original_destination: Some(dst_addr),
+            tlvs: Vec::new(),
+            unix: None,
+            raw_type: None,
         };
         let mut buf = BytesMut::new();
         let mut codec = V2Codec::new();
@@ -258,7 +527,127 @@ mod tests {
             .decode(&mut buf)
             .expect("BUG: No ProxyInfo decoded")
             .expect("BUG: ProxInfo decoding failed");
-        assert_eq!(info, info2);
+        assert_eq!(Some(PROTOCOL_TCP_IP6), info2.raw_type_byte());
+        assert_eq!(Some(0x2), info2.raw_family_byte());
+        assert_eq!(
+            info,
+            ProxyInfo {
+                raw_type: None,
+                ..info2
+            }
+        );
         assert!(buf.is_empty());
     }
+
+    #[test]
+    fn test_v2_unix_encode_decode() {
+        let mut info = ProxyInfo::from_unix("/run/client.sock", "/run/pool.sock");
+        info.tlvs = vec![(tlv::PP2_TYPE_AUTHORITY, Bytes::from_static(b"example.com"))];
+        let mut buf = BytesMut::new();
+        let mut codec = V2Codec::new();
+        codec.encode(info.clone(), &mut buf).expect("BUG: encoding");
+        assert_eq!(buf.len(), encoded_len(&info));
+        assert_eq!(PROTOCOL_UNIX_SOCKET, buf[13]);
+        buf.extend_from_slice(b"Hello");
+
+        let info2 = codec
+            .decode(&mut buf)
+            .expect("BUG: No ProxyInfo decoded")
+            .expect("BUG: ProxInfo decoding failed");
+        assert_eq!(Some(PROTOCOL_UNIX_SOCKET), info2.raw_type_byte());
+        assert_eq!(
+            info,
+            ProxyInfo {
+                raw_type: None,
+                ..info2.clone()
+            }
+        );
+        assert_eq!(SocketType::Unknown, info2.socket_type);
+        assert_eq!(
+            Some(Path::new("/run/client.sock")),
+            info2.original_source_unix()
+        );
+        assert_eq!(
+            Some(Path::new("/run/pool.sock")),
+            info2.original_destination_unix()
+        );
+        assert_eq!(b"Hello", &buf[..]);
+
+        // Datagram sockets are decoded only from datagrams
+        let info = ProxyInfo::from_unix_datagram("/run/client.sock", "/run/pool.sock");
+        let mut buf = BytesMut::new();
+        codec.encode(info.clone(), &mut buf).expect("BUG: encoding");
+        assert_eq!(PROTOCOL_UNIX_DATAGRAM, buf[13]);
+        let info2 = V2Codec::new()
+            .datagram()
+            .decode(&mut buf.clone())
+            .expect("BUG: No ProxyInfo decoded")
+            .expect("BUG: ProxInfo decoding failed");
+        assert_eq!(
+            info,
+            ProxyInfo {
+                raw_type: None,
+                ..info2
+            }
+        );
+        let info2 = codec
+            .decode(&mut buf)
+            .expect("BUG: No ProxyInfo decoded")
+            .expect("BUG: ProxInfo decoding failed");
+        // The raw byte is available even for the unsupported protocol
+        assert_eq!(Some(PROTOCOL_UNIX_DATAGRAM), info2.raw_type_byte());
+        assert_eq!(
+            ProxyInfo::default(),
+            ProxyInfo {
+                raw_type: None,
+                ..info2
+            }
+        );
+
+        // Path doesn't fit the address block
+        let long_path = format!("/run/{}", "x".repeat(108));
+        codec
+            .encode(ProxyInfo::from_unix(long_path, "/run/pool.sock"), &mut buf)
+            .expect_err("BUG: Too long unix path encoded");
+    }
+
+    #[test]
+    fn test_v2_max_tlvs() {
+        // IPv4 header followed by `count` empty TLVs
+        let msg_with_tlvs = |count: usize| {
+            let mut buf = BytesMut::new();
+            buf.extend_from_slice(SIGNATURE);
+            buf.put_u8(0x21);
+            buf.put_u8(0x11);
+            buf.put_u16(12 + 3 * count as u16);
+            buf.extend(&[127, 0, 0, 1, 127, 0, 0, 2, 0, 80, 1, 187]);
+            for _ in 0..count {
+                buf.extend(&[tlv::PP2_TYPE_NOOP, 0, 0]);
+            }
+            buf
+        };
+
+        let info = V2Codec::new()
+            .decode(&mut msg_with_tlvs(tlv::DEFAULT_MAX_TLVS))
+            .expect("BUG: ProxyInfo decoding failed")
+            .expect("BUG: No ProxyInfo decoded");
+        assert_eq!(tlv::DEFAULT_MAX_TLVS, info.tlvs.len());
+        match V2Codec::new().decode(&mut msg_with_tlvs(tlv::DEFAULT_MAX_TLVS + 1)) {
+            Err(Error::Proxy(_)) => (),
+            r => panic!(
+                "BUG: TLVs exceeding the limit not rejected: {:?}",
+                r.map(|_| ())
+            ),
+        }
+
+        let mut codec = V2Codec::new().max_tlvs(2);
+        codec
+            .decode(&mut msg_with_tlvs(1000))
+            .expect_err("BUG: TLVs exceeding the limit accepted");
+        // The codec is reset by the error and decodes another header
+        codec
+            .decode(&mut msg_with_tlvs(2))
+            .expect("BUG: ProxyInfo decoding failed")
+            .expect("BUG: No ProxyInfo decoded");
+    }
 }
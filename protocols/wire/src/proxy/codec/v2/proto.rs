@@ -22,7 +22,9 @@
 
 #![allow(dead_code)]
 
+use std::convert::TryFrom;
 use std::net::{SocketAddrV4, SocketAddrV6};
+use std::path::{Path, PathBuf};
 
 use super::{SocketType, SIGNATURE};
 
@@ -104,6 +106,8 @@ pub(super) const SIZE_HEADER: u16 = 16;
 const SIZE_ADDRESSES_IP4: u16 = 12;
 const SIZE_ADDRESSES_IP6: u16 = 36;
 const SIZE_ADDRESSES_UNIX: u16 = 216;
+/// Size of a single `\0` padded path in the unix addresses block
+const SIZE_UNIX_PATH: usize = 108;
 
 #[derive(Error, Debug)]
 pub enum Error {
@@ -113,6 +117,8 @@ pub enum Error {
     AddressIp4(String),
     #[error("Invalid IP6 address: {0}")]
     AddressIp6(String),
+    #[error("Invalid unix address: {0}")]
+    AddressUnix(String),
 }
 
 type Result<T> = std::result::Result<T, Error>;
@@ -141,6 +147,19 @@ impl Header {
             len,
         }
     }
+
+    /// Header of unix stream or `datagram` socket addresses
+    pub(super) fn new_unix(datagram: bool) -> Self {
+        Header {
+            version_and_command: VERSION_COMMAND,
+            protocol: if datagram {
+                PROTOCOL_UNIX_DATAGRAM
+            } else {
+                PROTOCOL_UNIX_SOCKET
+            },
+            len: SIZE_ADDRESSES_UNIX,
+        }
+    }
 }
 
 impl Serialize for Header {
@@ -296,9 +315,104 @@ impl Serialize for Ip6Addresses {
     }
 }
 
-struct UnixAddresses {
-    src_addr: [u8; 108],
-    dst_addr: [u8; 108],
+#[derive(Debug, PartialEq, Eq)]
+pub(super) struct UnixAddresses {
+    src_addr: [u8; SIZE_UNIX_PATH],
+    dst_addr: [u8; SIZE_UNIX_PATH],
+}
+
+impl UnixAddresses {
+    /// Pads `path` with `\0` to the fixed size, the terminating `\0` may be omitted only when
+    /// the path takes up the whole space
+    fn pad_path(path: &Path) -> Result<[u8; SIZE_UNIX_PATH]> {
+        let bytes = path_to_bytes(path)?;
+        if bytes.len() > SIZE_UNIX_PATH {
+            return Err(Error::AddressUnix(format!(
+                "Path exceeds {} bytes: {}",
+                SIZE_UNIX_PATH,
+                path.display()
+            )));
+        }
+        let mut padded = [0; SIZE_UNIX_PATH];
+        padded[..bytes.len()].copy_from_slice(&bytes);
+        Ok(padded)
+    }
+
+    fn unpad_path(padded: &[u8; SIZE_UNIX_PATH]) -> PathBuf {
+        let len = padded
+            .iter()
+            .position(|b| *b == 0)
+            .unwrap_or(SIZE_UNIX_PATH);
+        path_from_bytes(&padded[..len])
+    }
+}
+
+#[cfg(unix)]
+fn path_to_bytes(path: &Path) -> Result<Vec<u8>> {
+    use std::os::unix::ffi::OsStrExt;
+    Ok(path.as_os_str().as_bytes().to_vec())
+}
+
+#[cfg(not(unix))]
+fn path_to_bytes(path: &Path) -> Result<Vec<u8>> {
+    path.to_str()
+        .map(|path| path.as_bytes().to_vec())
+        .ok_or_else(|| Error::AddressUnix(format!("Path is not valid UTF-8: {}", path.display())))
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    std::ffi::OsStr::from_bytes(bytes).into()
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: &[u8]) -> PathBuf {
+    String::from_utf8_lossy(bytes).into_owned().into()
+}
+
+impl TryFrom<(&Path, &Path)> for UnixAddresses {
+    type Error = Error;
+
+    fn try_from(addresses: (&Path, &Path)) -> Result<Self> {
+        let (src, dst) = addresses;
+        Ok(UnixAddresses {
+            src_addr: Self::pad_path(src)?,
+            dst_addr: Self::pad_path(dst)?,
+        })
+    }
+}
+
+impl From<UnixAddresses> for (PathBuf, PathBuf) {
+    fn from(addresses: UnixAddresses) -> Self {
+        (
+            UnixAddresses::unpad_path(&addresses.src_addr),
+            UnixAddresses::unpad_path(&addresses.dst_addr),
+        )
+    }
+}
+
+impl Serialize for UnixAddresses {
+    fn serialize(&self, buf: &mut BytesMut) {
+        buf.reserve(SIZE_ADDRESSES_UNIX as usize);
+        buf.put(&self.src_addr[..]);
+        buf.put(&self.dst_addr[..]);
+    }
+
+    fn deserialize(buf: &mut BytesMut) -> Result<Self> {
+        if buf.len() < SIZE_ADDRESSES_UNIX as usize {
+            return Err(Error::AddressUnix(
+                "Too short for unix addresses block".into(),
+            ));
+        }
+        let mut src_addr = [0; SIZE_UNIX_PATH];
+        let mut dst_addr = [0; SIZE_UNIX_PATH];
+        src_addr.copy_from_slice(&buf[..SIZE_UNIX_PATH]);
+        buf.advance(SIZE_UNIX_PATH);
+        dst_addr.copy_from_slice(&buf[..SIZE_UNIX_PATH]);
+        buf.advance(SIZE_UNIX_PATH);
+        Ok(UnixAddresses { src_addr, dst_addr })
+    }
 }
 
 #[cfg(test)]
@@ -350,4 +464,35 @@ mod test {
         let (src_addr2, dst_addr2) = a2.into();
         assert_eq!((src_addr, dst_addr), (src_addr2, dst_addr2));
     }
+
+    #[test]
+    fn test_unix_addresses_serialize_deserialize() {
+        let src_path = Path::new("/run/proxy/client.sock");
+        let dst_path = Path::new("/run/pool/stratum.sock");
+        let a1 = UnixAddresses::try_from((src_path, dst_path)).expect("BUG: Cannot convert paths");
+        let mut buf = BytesMut::new();
+        a1.serialize(&mut buf);
+        assert_eq!(SIZE_ADDRESSES_UNIX as usize, buf.len());
+        let a2 =
+            UnixAddresses::deserialize(&mut buf).expect("BUG: Cannot deserialize unix addresses");
+        assert_eq!(a1, a2);
+        assert!(buf.is_empty());
+
+        let (src_path2, dst_path2) = a2.into();
+        assert_eq!(
+            (src_path, dst_path),
+            (src_path2.as_path(), dst_path2.as_path())
+        );
+
+        // Path taking up the whole space has no terminating `\0`
+        let long_path = PathBuf::from("/".repeat(SIZE_UNIX_PATH));
+        let addresses = UnixAddresses::try_from((long_path.as_path(), dst_path))
+            .expect("BUG: Cannot convert paths");
+        let (long_path2, _): (PathBuf, PathBuf) = addresses.into();
+        assert_eq!(long_path, long_path2);
+
+        let too_long_path = PathBuf::from("/".repeat(SIZE_UNIX_PATH + 1));
+        UnixAddresses::try_from((too_long_path.as_path(), dst_path))
+            .expect_err("BUG: Path exceeding the block accepted");
+    }
 }
@@ -0,0 +1,243 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Type-Length-Value extensions appended after the address block of the PROXY protocol v2
+//! header
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+use crate::proxy::error::{Error, Result};
+
+/// Application-Layer Protocol Negotiation (ALPN)
+pub const PP2_TYPE_ALPN: u8 = 0x01;
+/// Host name passed by the client (SNI)
+pub const PP2_TYPE_AUTHORITY: u8 = 0x02;
+/// CRC32c checksum of the PROXY header
+pub const PP2_TYPE_CRC32C: u8 = 0x03;
+/// Ignored padding
+pub const PP2_TYPE_NOOP: u8 = 0x04;
+/// Opaque connection identifier
+pub const PP2_TYPE_UNIQUE_ID: u8 = 0x05;
+/// Details about the TLS connection of the client, see `SslInfo`
+pub const PP2_TYPE_SSL: u8 = 0x20;
+/// TLS version, sub-TLV of `PP2_TYPE_SSL`
+pub const PP2_SUBTYPE_SSL_VERSION: u8 = 0x21;
+/// Common name of the client certificate, sub-TLV of `PP2_TYPE_SSL`
+pub const PP2_SUBTYPE_SSL_CN: u8 = 0x22;
+/// Cipher used by the client connection, sub-TLV of `PP2_TYPE_SSL`
+pub const PP2_SUBTYPE_SSL_CIPHER: u8 = 0x23;
+/// Signature algorithm of the client certificate, sub-TLV of `PP2_TYPE_SSL`
+pub const PP2_SUBTYPE_SSL_SIG_ALG: u8 = 0x24;
+/// Key algorithm of the client certificate, sub-TLV of `PP2_TYPE_SSL`
+pub const PP2_SUBTYPE_SSL_KEY_ALG: u8 = 0x25;
+/// AWS specific data, the first byte of the value is a subtype
+pub const PP2_TYPE_AWS: u8 = 0xEA;
+/// VPC endpoint ID of the client, subtype of `PP2_TYPE_AWS`
+pub const PP2_SUBTYPE_AWS_VPCE_ID: u8 = 0x01;
+
+/// Client connected over TLS
+const PP2_CLIENT_SSL: u8 = 0x01;
+/// Client provided a certificate over the current connection
+const PP2_CLIENT_CERT_CONN: u8 = 0x02;
+/// Client provided a certificate at least once over the TLS session
+const PP2_CLIENT_CERT_SESS: u8 = 0x04;
+
+/// Default limit of TLVs parsed from a single header, see `V2Codec::max_tlvs()`
+pub const DEFAULT_MAX_TLVS: usize = 32;
+
+/// Type (1B) and length (2B) preceding each TLV value
+const SIZE_TLV_HEADER: usize = 3;
+/// Client flags (1B) and verification result (4B) preceding the sub-TLVs of `PP2_TYPE_SSL`
+const SIZE_SSL_HEADER: usize = 5;
+
+/// Splits `buf` into a sequence of TLVs. The whole `buf` has to be covered by well-formed TLVs,
+/// i.e. the TLVs have to end exactly where the declared header length ends. Any mismatch
+/// indicates a corrupted header or an attempt to smuggle bytes in or out of the payload.
+/// At most `max_tlvs` TLVs are accepted to bound the work spent on a single header.
+pub(crate) fn parse(mut buf: Bytes, max_tlvs: usize) -> Result<Vec<(u8, Bytes)>> {
+    let mut tlvs = Vec::new();
+    while buf.has_remaining() {
+        if tlvs.len() == max_tlvs {
+            return Err(Error::Proxy(format!(
+                "Number of TLVs exceeds the limit of {}",
+                max_tlvs
+            )));
+        }
+        if buf.len() < SIZE_TLV_HEADER {
+            return Err(Error::Proxy(format!(
+                "Truncated TLV header, {} trailing bytes within declared header length",
+                buf.len()
+            )));
+        }
+        let typ = buf.get_u8();
+        let len = buf.get_u16() as usize;
+        if buf.len() < len {
+            return Err(Error::Proxy(format!(
+                "TLV 0x{:02x} value exceeds declared header length",
+                typ
+            )));
+        }
+        tlvs.push((typ, buf.split_to(len)));
+    }
+    Ok(tlvs)
+}
+
+/// Number of bytes `tlvs` occupy when serialized
+pub(crate) fn encoded_len(tlvs: &[(u8, Bytes)]) -> usize {
+    tlvs.iter()
+        .map(|(_, value)| SIZE_TLV_HEADER + value.len())
+        .sum()
+}
+
+pub(crate) fn serialize(tlvs: &[(u8, Bytes)], buf: &mut BytesMut) {
+    buf.reserve(encoded_len(tlvs));
+    for (typ, value) in tlvs {
+        buf.put_u8(*typ);
+        buf.put_u16(value.len() as u16);
+        buf.put(&value[..]);
+    }
+}
+
+/// Details about the TLS connection of the client as passed in the `PP2_TYPE_SSL` TLV
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct SslInfo {
+    /// Client presented a certificate and it has been successfully verified
+    pub verified: bool,
+    /// TLS version, e.g. "TLSv1.3"
+    pub version: Option<String>,
+    /// Common name of the client certificate
+    pub cn: Option<String>,
+    /// Cipher used by the connection, e.g. "ECDHE-RSA-AES128-GCM-SHA256"
+    pub cipher: Option<String>,
+}
+
+impl SslInfo {
+    /// Parses value of the `PP2_TYPE_SSL` TLV
+    pub(crate) fn parse(mut value: Bytes) -> Result<Self> {
+        if value.len() < SIZE_SSL_HEADER {
+            return Err(Error::Proxy("Truncated SSL TLV".into()));
+        }
+        let client = value.get_u8();
+        let verify = value.get_u32();
+        let mut info = SslInfo {
+            verified: client & PP2_CLIENT_SSL != 0
+                && client & (PP2_CLIENT_CERT_CONN | PP2_CLIENT_CERT_SESS) != 0
+                && verify == 0,
+            ..Default::default()
+        };
+        for (typ, value) in parse(value, DEFAULT_MAX_TLVS)? {
+            let field = match typ {
+                PP2_SUBTYPE_SSL_VERSION => &mut info.version,
+                PP2_SUBTYPE_SSL_CN => &mut info.cn,
+                PP2_SUBTYPE_SSL_CIPHER => &mut info.cipher,
+                _ => continue,
+            };
+            *field = Some(String::from_utf8_lossy(&value).into_owned());
+        }
+        Ok(info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy::codec::v2::{V2Codec, SIGNATURE};
+    use crate::proxy::codec::SocketType;
+    use tokio_util::codec::Decoder;
+
+    fn put_tlv(buf: &mut BytesMut, typ: u8, value: &[u8]) {
+        buf.put_u8(typ);
+        buf.put_u16(value.len() as u16);
+        buf.put(value);
+    }
+
+    #[test]
+    fn test_ssl_info() {
+        let mut ssl = BytesMut::new();
+        ssl.put_u8(PP2_CLIENT_SSL | PP2_CLIENT_CERT_CONN);
+        ssl.put_u32(0);
+        put_tlv(&mut ssl, PP2_SUBTYPE_SSL_VERSION, b"TLSv1.3");
+        put_tlv(&mut ssl, PP2_SUBTYPE_SSL_CN, b"worker.example.com");
+        put_tlv(&mut ssl, PP2_SUBTYPE_SSL_CIPHER, b"TLS_AES_256_GCM_SHA384");
+        put_tlv(&mut ssl, PP2_SUBTYPE_SSL_KEY_ALG, b"RSA2048");
+
+        let mut tlvs = BytesMut::new();
+        put_tlv(&mut tlvs, PP2_TYPE_AUTHORITY, b"pool.example.com");
+        put_tlv(&mut tlvs, PP2_TYPE_SSL, &ssl);
+
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(SIGNATURE);
+        buf.put_u8(0x21);
+        buf.put_u8(0x11);
+        buf.put_u16(12 + tlvs.len() as u16);
+        buf.extend(&[127, 0, 0, 1, 127, 0, 0, 2, 0, 80, 1, 187]);
+        buf.extend_from_slice(&tlvs);
+        buf.extend_from_slice(b"Hello");
+
+        let info = V2Codec::new()
+            .decode(&mut buf)
+            .expect("BUG: ProxyInfo not decoded")
+            .expect("BUG: ProxyInfo decoding failed");
+        assert_eq!(SocketType::Ipv4, info.socket_type);
+        assert_eq!(b"Hello", &buf[..]);
+        assert_eq!(2, info.tlvs.len());
+        assert_eq!(
+            (PP2_TYPE_AUTHORITY, Bytes::from_static(b"pool.example.com")),
+            info.tlvs[0]
+        );
+        assert_eq!(
+            Some(SslInfo {
+                verified: true,
+                version: Some("TLSv1.3".into()),
+                cn: Some("worker.example.com".into()),
+                cipher: Some("TLS_AES_256_GCM_SHA384".into()),
+            }),
+            info.ssl_info()
+        );
+    }
+
+    #[test]
+    fn test_ssl_info_unverified() {
+        let mut ssl = BytesMut::new();
+        ssl.put_u8(PP2_CLIENT_SSL | PP2_CLIENT_CERT_CONN);
+        ssl.put_u32(1);
+        let info = SslInfo::parse(ssl.freeze()).expect("BUG: Cannot parse SSL TLV");
+        assert_eq!(SslInfo::default(), info);
+
+        assert!(SslInfo::parse(Bytes::from_static(&[PP2_CLIENT_SSL, 0, 0])).is_err());
+    }
+
+    #[test]
+    fn test_truncated_tlv() {
+        assert!(parse(Bytes::from_static(&[PP2_TYPE_NOOP, 0]), DEFAULT_MAX_TLVS).is_err());
+        assert!(parse(
+            Bytes::from_static(&[PP2_TYPE_NOOP, 0, 2, 0]),
+            DEFAULT_MAX_TLVS
+        )
+        .is_err());
+        assert_eq!(
+            vec![(PP2_TYPE_NOOP, Bytes::new())],
+            parse(Bytes::from_static(&[PP2_TYPE_NOOP, 0, 0]), DEFAULT_MAX_TLVS)
+                .expect("BUG: Cannot parse TLV")
+        );
+    }
+}
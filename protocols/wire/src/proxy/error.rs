@@ -47,6 +47,15 @@ pub enum Error {
 
     #[error("Invalid state: {0}")]
     InvalidState(String),
+
+    #[error("Accepting stream cancelled")]
+    Cancelled,
+
+    #[error("Accepting connection timed out after {0:?}")]
+    Timeout(std::time::Duration),
+
+    #[error("Cannot decode first frame: {0}")]
+    Framing(String),
 }
 
 /// Convenient Result type, with our Error included
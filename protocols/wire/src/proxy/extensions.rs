@@ -0,0 +1,102 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Type map for attaching arbitrary application data to a `ProxyStream`
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+
+/// A type map of values attached to a stream by the accepting layer, so that a handler further
+/// down the pipeline can pick them up (e.g. a routing decision) without a side map.
+///
+/// At most one value of each type can be stored, inserting another value of the same type replaces
+/// the previous one.
+#[derive(Default)]
+pub struct Extensions {
+    map: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+}
+
+impl Extensions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inserts `value` and returns the previously stored value of the same type, if any
+    pub fn insert<V: Send + Sync + 'static>(&mut self, value: V) -> Option<V> {
+        self.map
+            .insert(TypeId::of::<V>(), Box::new(value))
+            .and_then(|previous| previous.downcast().ok().map(|boxed| *boxed))
+    }
+
+    pub fn get<V: Send + Sync + 'static>(&self) -> Option<&V> {
+        self.map
+            .get(&TypeId::of::<V>())
+            .and_then(|value| value.downcast_ref())
+    }
+
+    pub fn get_mut<V: Send + Sync + 'static>(&mut self) -> Option<&mut V> {
+        self.map
+            .get_mut(&TypeId::of::<V>())
+            .and_then(|value| value.downcast_mut())
+    }
+
+    pub fn remove<V: Send + Sync + 'static>(&mut self) -> Option<V> {
+        self.map
+            .remove(&TypeId::of::<V>())
+            .and_then(|value| value.downcast().ok().map(|boxed| *boxed))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+}
+
+/// Stored values are opaque, only their count is shown
+impl fmt::Debug for Extensions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Extensions")
+            .field("len", &self.map.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_replaces_value_of_same_type() {
+        let mut extensions = Extensions::new();
+        assert_eq!(extensions.insert(5u32), None);
+        assert_eq!(extensions.insert(7u32), Some(5u32));
+        assert_eq!(extensions.insert("backend"), None);
+        assert_eq!(extensions.len(), 2);
+        assert_eq!(extensions.get::<u32>(), Some(&7));
+        assert_eq!(extensions.remove::<&str>(), Some("backend"));
+        assert!(extensions.get::<u64>().is_none());
+    }
+}
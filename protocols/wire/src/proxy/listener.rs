@@ -0,0 +1,235 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! TCP listener that accepts streams with PROXY protocol header
+
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use futures::stream::FuturesUnordered;
+use futures::{FutureExt, StreamExt};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+use super::error::Result;
+use super::{AcceptorBuilder, AcceptorFuture, ProtocolConfig, ProxyStream};
+
+/// Keeps the connection counted as open until dropped. It is attached to the extensions of the
+/// accepted `ProxyStream` (and moves into a `Connection` converted from it).
+///
+/// `Framed` and `FramedParts` have no extensions, take the guard out of the stream with
+/// `stream.extensions_mut().remove::<OpenConnection>()` before converting it and keep it
+/// alongside the framed stream.
+#[derive(Debug)]
+pub struct OpenConnection(Arc<AtomicUsize>);
+
+impl OpenConnection {
+    fn new(open_connections: Arc<AtomicUsize>) -> Self {
+        open_connections.fetch_add(1, Ordering::Relaxed);
+        Self(open_connections)
+    }
+}
+
+impl Drop for OpenConnection {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Listener that accepts TCP connections and processes their PROXY protocol header according to
+/// the provided configuration. The listener keeps track of connections that are currently open.
+///
+/// PROXY headers of all accepted connections are decoded concurrently, therefore a peer that
+/// stalls in the middle of its header doesn't block accepting other connections.
+pub struct ProxyListener {
+    listener: TcpListener,
+    acceptor_builder: AcceptorBuilder<TcpStream>,
+    open_connections: Arc<AtomicUsize>,
+    /// Accepted connections whose PROXY header is being decoded
+    pending: FuturesUnordered<AcceptorFuture<TcpStream>>,
+}
+
+impl ProxyListener {
+    pub async fn bind<A: ToSocketAddrs>(addr: A, config: ProtocolConfig) -> Result<Self> {
+        Ok(Self::new(
+            TcpListener::bind(addr).await?,
+            AcceptorBuilder::new(config),
+        ))
+    }
+
+    /// Builds the listener from an already bound `listener`, the PROXY header of each connection
+    /// is processed by an acceptor built by `acceptor_builder`
+    pub fn new(listener: TcpListener, acceptor_builder: AcceptorBuilder<TcpStream>) -> Self {
+        Self {
+            listener,
+            acceptor_builder,
+            open_connections: Arc::new(AtomicUsize::new(0)),
+            pending: FuturesUnordered::new(),
+        }
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Returns the next connection whose PROXY header has been processed, an error is returned
+    /// when accepting a connection or processing its header fails. Connections are accepted
+    /// while headers of previously accepted ones are still being decoded. The connection counts
+    /// as open from the moment it is accepted until the returned stream or a `Connection`
+    /// converted from it is dropped. Converting the stream into `Framed` or `FramedParts` ends
+    /// the tracking unless its `OpenConnection` guard is kept.
+    ///
+    /// The method is cancel safe, connections with a pending header are kept in the listener.
+    pub async fn accept(&mut self) -> Result<ProxyStream<TcpStream>> {
+        loop {
+            tokio::select! {
+                accepted = self.listener.accept() => {
+                    let (stream, peer) = accepted?;
+                    let open_connection = OpenConnection::new(self.open_connections.clone());
+                    let header = self.acceptor_builder.build_from_peer(stream, peer);
+                    self.pending.push(
+                        async move {
+                            let mut stream = header.await?;
+                            stream.insert(open_connection);
+                            Ok(stream)
+                        }
+                        .boxed(),
+                    );
+                }
+                Some(stream) = self.pending.next() => return stream,
+            }
+        }
+    }
+
+    /// Number of accepted connections that haven't been dropped yet
+    pub fn open_connections(&self) -> usize {
+        self.open_connections.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy::{ProtocolVersion, WithProxyInfo};
+    use crate::{Connection, Framing};
+    use tokio::io::AsyncWriteExt;
+
+    #[derive(Debug)]
+    struct TestFraming;
+
+    impl Framing for TestFraming {
+        type Tx = String;
+        type Rx = String;
+        type Error = tokio_util::codec::LinesCodecError;
+        type Codec = tokio_util::codec::LinesCodec;
+    }
+
+    #[tokio::test]
+    async fn open_connections() {
+        let mut listener = ProxyListener::bind(
+            "127.0.0.1:0",
+            ProtocolConfig::new(false, vec![ProtocolVersion::V1, ProtocolVersion::V2]),
+        )
+        .await
+        .expect("BUG: Cannot bind listener");
+        let addr = listener
+            .local_addr()
+            .expect("BUG: Cannot get local address");
+
+        let mut clients = Vec::new();
+        let mut streams = Vec::new();
+        for _ in 0..2 {
+            let mut client = TcpStream::connect(addr).await.expect("BUG: Cannot connect");
+            client
+                .write_all(b"PROXY UNKNOWN\r\n")
+                .await
+                .expect("BUG: Cannot write PROXY header");
+            clients.push(client);
+            streams.push(listener.accept().await.expect("BUG: Cannot accept"));
+        }
+        assert_eq!(listener.open_connections(), 2);
+
+        drop(streams.pop());
+        assert_eq!(listener.open_connections(), 1);
+
+        // The connection is still tracked once converted into a `Connection`
+        let connection: Connection<TestFraming> =
+            streams.pop().expect("BUG: Missing stream").into();
+        assert_eq!(listener.open_connections(), 1);
+        drop(connection);
+        assert_eq!(listener.open_connections(), 0);
+
+        // The guard taken out of the stream keeps a framed connection tracked
+        let mut client = TcpStream::connect(addr).await.expect("BUG: Cannot connect");
+        client
+            .write_all(b"PROXY UNKNOWN\r\n")
+            .await
+            .expect("BUG: Cannot write PROXY header");
+        let mut stream = listener.accept().await.expect("BUG: Cannot accept");
+        let open_connection = stream
+            .extensions_mut()
+            .remove::<OpenConnection>()
+            .expect("BUG: Missing connection guard");
+        let framed = tokio_util::codec::Framed::from_parts(
+            stream.into_framed_parts::<tokio_util::codec::LinesCodec, String>(),
+        );
+        assert_eq!(listener.open_connections(), 1);
+        drop(framed);
+        assert_eq!(listener.open_connections(), 1);
+        drop(open_connection);
+        assert_eq!(listener.open_connections(), 0);
+    }
+
+    #[tokio::test]
+    async fn stalled_header_does_not_block_accept() {
+        let mut listener = ProxyListener::bind(
+            "127.0.0.1:0",
+            ProtocolConfig::new(true, vec![ProtocolVersion::V1, ProtocolVersion::V2]),
+        )
+        .await
+        .expect("BUG: Cannot bind listener");
+        let addr = listener
+            .local_addr()
+            .expect("BUG: Cannot get local address");
+
+        let mut stalled_client = TcpStream::connect(addr).await.expect("BUG: Cannot connect");
+        stalled_client
+            .write_all(b"PROXY TCP4 192.168.0.1 ")
+            .await
+            .expect("BUG: Cannot write PROXY header");
+        let mut client = TcpStream::connect(addr).await.expect("BUG: Cannot connect");
+        client
+            .write_all(b"PROXY TCP4 192.168.0.2 192.168.0.11 56324 443\r\n")
+            .await
+            .expect("BUG: Cannot write PROXY header");
+
+        let stream = tokio::time::timeout(std::time::Duration::from_secs(5), listener.accept())
+            .await
+            .expect("BUG: Accept blocked by a stalled peer")
+            .expect("BUG: Cannot accept");
+        assert_eq!(
+            stream.original_peer_addr(),
+            "192.168.0.2:56324".parse().ok()
+        );
+        assert_eq!(listener.open_connections(), 2);
+    }
+}
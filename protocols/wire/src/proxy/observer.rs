@@ -0,0 +1,96 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+//! Hooks for observing the outcome of PROXY protocol acceptance, e.g. for metrics
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Classification of a stream processed by `Acceptor`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AcceptOutcome {
+    /// Valid PROXY protocol V1 header received
+    V1,
+    /// Valid PROXY protocol V2 header received
+    V2,
+    /// No PROXY protocol header detected and the stream has been passed through
+    NoHeader,
+    /// The stream has been refused as PROXY header was required but missing or invalid
+    Rejected,
+}
+
+/// Maximum number of rejected bytes passed to `ProxyObserver::on_reject()`
+pub const REJECTED_BYTES_LIMIT: usize = 64;
+
+/// Observer of streams processed by `Acceptor`, see `Acceptor::observer()`
+pub trait ProxyObserver: Send + Sync {
+    fn on_accept(&self, outcome: AcceptOutcome);
+
+    /// Called along with `on_accept(AcceptOutcome::Rejected)`, `bytes` is the beginning of the
+    /// data that failed to parse as a PROXY header, at most `REJECTED_BYTES_LIMIT` bytes. This
+    /// allows capturing malformed headers without enabling trace logging.
+    fn on_reject(&self, _reason: &str, _bytes: &[u8]) {}
+}
+
+/// Observer that tallies accepted streams per `AcceptOutcome`
+#[derive(Debug, Default)]
+pub struct AcceptCounters {
+    v1: AtomicU64,
+    v2: AtomicU64,
+    no_header: AtomicU64,
+    rejected: AtomicU64,
+}
+
+/// Point-in-time values of `AcceptCounters`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AcceptCountersSnapshot {
+    pub v1: u64,
+    pub v2: u64,
+    pub no_header: u64,
+    pub rejected: u64,
+}
+
+impl AcceptCounters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn snapshot(&self) -> AcceptCountersSnapshot {
+        AcceptCountersSnapshot {
+            v1: self.v1.load(Ordering::Relaxed),
+            v2: self.v2.load(Ordering::Relaxed),
+            no_header: self.no_header.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl ProxyObserver for AcceptCounters {
+    fn on_accept(&self, outcome: AcceptOutcome) {
+        let counter = match outcome {
+            AcceptOutcome::V1 => &self.v1,
+            AcceptOutcome::V2 => &self.v2,
+            AcceptOutcome::NoHeader => &self.no_header,
+            AcceptOutcome::Rejected => &self.rejected,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
@@ -299,7 +299,8 @@ impl ErrorLabeling for error::Error {
                 StratumError::Noise(_)
                 | StratumError::NoiseEncoding(_)
                 | StratumError::NoiseProtocol(_)
-                | StratumError::NoiseSignature(_) => "noise",
+                | StratumError::NoiseSignature(_)
+                | StratumError::UnsupportedCertificateVersion { .. } => "noise",
                 StratumError::V2(_) => "downstream",
                 StratumError::V1(_) => "upstream",
                 _ => "stratum_other",
@@ -94,7 +94,7 @@ impl ConnTranslation {
             v2_translation_tx,
             Default::default(),
             metrics.clone(),
-            v2_peer_addr.proxy_info,
+            v2_peer_addr.proxy_info.clone(),
         );
 
         Self {
@@ -203,7 +203,7 @@ impl ConnTranslation {
             metrics.accounted_spawn(Self::v1_send_task(
                 v1_conn_tx,
                 self.v1_translation_rx,
-                self.v2_peer_addr,
+                self.v2_peer_addr.clone(),
             ));
             metrics.accounted_spawn(Self::v2_send_task(
                 v2_conn_tx,
@@ -214,7 +214,7 @@ impl ConnTranslation {
             tokio::spawn(Self::v1_send_task(
                 v1_conn_tx,
                 self.v1_translation_rx,
-                self.v2_peer_addr,
+                self.v2_peer_addr.clone(),
             ));
             tokio::spawn(Self::v2_send_task(
                 v2_conn_tx,
@@ -394,13 +394,13 @@ where
         let proxy_info = proxy_stream
             .proxy_info()
             .map_err(DownstreamError::ProxyProtocol)?;
-        self.downstream_peer.set_proxy_info(proxy_info);
+        self.downstream_peer.set_proxy_info(proxy_info.clone());
 
         debug!(
             "Received connection from: {}, local destination: {}",
             self.downstream_peer.direct_peer,
             local_addr.to_string();
-            proxy_info
+            &proxy_info
         );
         // Connect to upstream V1 server
         let mut v1_client = Client::new(self.v1_upstream_addr.clone());
@@ -433,7 +433,7 @@ where
         debug!(
             "Established translation connection with upstream V1 {}",
             v1_peer_addr;
-            proxy_info
+            &proxy_info
         );
         let v2_framed_stream = match self.security_context.as_ref() {
             Some(security_context) => security_context
@@ -448,7 +448,7 @@ where
         self.connection_handler
             .handle_connection(
                 v2_framed_stream,
-                self.downstream_peer,
+                self.downstream_peer.clone(),
                 v1_framed_stream,
                 v1_peer_addr,
             )
@@ -462,7 +462,7 @@ where
         let timer = std::time::Instant::now();
         // TODO report full address info here once ProxyConnection has internal information about
         // (possible provide full 'ProxyInfo')
-        let proxy_info = self.downstream_peer.proxy_info;
+        let proxy_info = self.downstream_peer.proxy_info.clone();
         match self.do_handle().await {
             Ok(()) => {
                 if let Some(x) = metrics.as_ref() {
@@ -27,7 +27,7 @@ use std::{fmt, net::SocketAddr};
 
 /// Downstream peer representation as a direct peer address with optional original peer address
 /// known, for example from PROXY protocol.
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug)]
 pub struct DownstreamPeer {
     pub direct_peer: SocketAddr,
     /// Track additional information about the peer
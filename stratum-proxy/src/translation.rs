@@ -412,7 +412,7 @@ impl V2ToV1Translation {
 
     /// Sets the current pending channel to operational state and submits success message
     fn finalize_open_channel(&mut self) -> Result<()> {
-        trace!("finalize_open_channel()"; self.proxy_info);
+        trace!("finalize_open_channel()"; &self.proxy_info);
         let mut init_target: Uint256Bytes = Uint256Bytes([0; 32]);
         self.v2_target
             .expect("BUG: initial target still not defined when attempting to finalize OpenStandardMiningChannel")
@@ -421,7 +421,7 @@ impl V2ToV1Translation {
         // when V1 authorization has already taken place, report channel opening success
         if let Some(v2_channel_details) = self.v2_channel_details.as_ref() {
             self.state = V2ToV1TranslationState::Operational;
-            debug!("Switching mining channel to operational mode"; self.proxy_info);
+            debug!("Switching mining channel to operational mode"; &self.proxy_info);
             let msg = v2::messages::OpenStandardMiningChannelSuccess {
                 req_id: v2_channel_details.req_id,
                 channel_id: Self::CHANNEL_ID,
@@ -449,7 +449,7 @@ impl V2ToV1Translation {
     /// Send new target
     /// TODO extend the translation unit test accordingly
     fn send_set_target(&mut self) -> Result<()> {
-        trace!("send_set_target()"; self.proxy_info);
+        trace!("send_set_target()"; &self.proxy_info);
         let max_target = Uint256Bytes::from(self.v2_target.expect(
             "BUG: initial target still not defined when attempting to finalize \
              OpenStandardMiningChannel",
@@ -470,7 +470,7 @@ impl V2ToV1Translation {
             "abort_open_channel() - channel details: {:?}, msg: {}",
             self.v2_channel_details,
             err_msg;
-            self.proxy_info
+            &self.proxy_info
         );
         self.state = V2ToV1TranslationState::V1SubscribeOrAuthorizeFail;
 
@@ -489,7 +489,7 @@ impl V2ToV1Translation {
             if let Err(submit_err) = self.submit_v2_message(msg) {
                 info!(
                     "abort_open_channel() failed: {:?}, abort message: {}",
-                    submit_err, err_msg; self.proxy_info
+                    submit_err, err_msg; &self.proxy_info
                 );
             }
         } else {
@@ -513,7 +513,7 @@ impl V2ToV1Translation {
             id,
             self.state,
             payload;
-            self.proxy_info
+            &self.proxy_info
         );
 
         // TODO review the use of serde_json here, it may be possible to eliminate this dependency
@@ -525,7 +525,7 @@ impl V2ToV1Translation {
             "Evaluating: version-rolling state == {:?} && mask=={:x?}",
             payload.0["version-rolling"].as_bool(),
             proposed_version_mask;
-            self.proxy_info
+            &self.proxy_info
         );
         if payload.0["version-rolling"].as_bool() == Some(true)
             && (proposed_version_mask.0).0 == ii_stratum::BIP320_N_VERSION_MASK
@@ -558,7 +558,7 @@ impl V2ToV1Translation {
             id,
             self.state,
             payload;
-            self.proxy_info
+            &self.proxy_info
         );
         // TODO consolidate into abort_connection() + communicate shutdown of this
         // connection similarly everywhere in the code
@@ -581,10 +581,10 @@ impl V2ToV1Translation {
             // Handle the actual submission result
             .map(|bool_result| {
                 if bool_result.0 {
-                    info!("Support for #xnsub enabled"; self.proxy_info);
+                    info!("Support for #xnsub enabled"; &self.proxy_info);
                     self.v1_xnsub_enabled = true;
                 } else {
-                    error!("Pool refused to enable #xnsub"; self.proxy_info);
+                    error!("Pool refused to enable #xnsub"; &self.proxy_info);
                     self.v1_xnsub_enabled = false;
                 }
             })
@@ -611,7 +611,7 @@ impl V2ToV1Translation {
             id,
             self.state,
             payload;
-            self.proxy_info
+            &self.proxy_info
         );
         let subscribe_result = v1::messages::SubscribeResult::try_from(payload).map_err(|e| {
             // Aborting channel failed, we can only log about it
@@ -644,14 +644,14 @@ impl V2ToV1Translation {
             id,
             self.state,
             payload;
-            self.proxy_info
+            &self.proxy_info
         );
         // Authorize is expected as a plain boolean answer
         v1::messages::BooleanResult::try_from(payload)
             // Convert ii-stratum error to proxy error
             .map_err(Into::into)
             .and_then(|bool_result| {
-                trace!("Authorize result: {:?}", bool_result; self.proxy_info);
+                trace!("Authorize result: {:?}", bool_result; &self.proxy_info);
                 self.v1_authorized = bool_result.0;
                 if self.v1_authorized {
                     // Subscribe result already received (since extra nonce 1 is present), let's
@@ -687,14 +687,14 @@ impl V2ToV1Translation {
             id,
             self.state,
             payload;
-            self.proxy_info
+            &self.proxy_info
         );
         // Only the first of authorize or subscribe error issues the OpenMiningChannelError message
         if self.state != V2ToV1TranslationState::V1SubscribeOrAuthorizeFail {
             trace!(
                 "Upstream connection init failed, dropping channel: {:?}",
                 payload;
-                self.proxy_info
+                &self.proxy_info
             );
             self.abort_open_channel("Service not ready");
             Err(Error::from(ii_stratum::error::Error::from(
@@ -705,7 +705,7 @@ impl V2ToV1Translation {
                 "Ok, received the second of subscribe/authorize failures, \
                 channel is already closed: {:?}",
                 payload;
-                self.proxy_info
+                &self.proxy_info
             );
             Ok(())
         }
@@ -721,7 +721,7 @@ impl V2ToV1Translation {
             id,
             self.state,
             payload;
-            self.proxy_info
+            &self.proxy_info
         );
         // mining.submit response is expected as a plain boolean answer
         v1::messages::BooleanResult::try_from(payload)
@@ -737,11 +737,11 @@ impl V2ToV1Translation {
                     "Submit result: {:?}, V2 channel: {:?}",
                     bool_result,
                     v2_channel_details;
-                    self.proxy_info
+                    &self.proxy_info
                 );
 
                 if bool_result.0 {
-                    debug!("Share accepted: SESSION {}", self.session_details(); self.proxy_info);
+                    debug!("Share accepted: SESSION {}", self.session_details(); &self.proxy_info);
                     if let Some(metrics) = self.metrics.as_ref() {
                         metrics.account_accepted_share(self.v2_target);
                     }
@@ -751,7 +751,11 @@ impl V2ToV1Translation {
                         self.v2_target.expect("BUG: difficulty missing").low_u64(),
                     )
                 } else {
-                    info!("Share rejected for {}", v2_channel_details.user.to_string(); self.proxy_info);
+                    info!(
+                        "Share rejected for {}",
+                        v2_channel_details.user.to_string();
+                        &self.proxy_info
+                    );
                     self.reject_shares(
                         Self::CHANNEL_ID,
                         SeqNum::V1(*id),
@@ -774,7 +778,7 @@ impl V2ToV1Translation {
             id,
             self.state,
             payload;
-            self.proxy_info
+            &self.proxy_info
         );
         self.reject_shares(
             Self::CHANNEL_ID,
@@ -946,7 +950,7 @@ impl V2ToV1Translation {
         seq_num_variant: SeqNum,
         err_msg: String,
     ) -> Result<()> {
-        trace!("{}", err_msg; self.proxy_info);
+        trace!("{}", err_msg; &self.proxy_info);
         let (seq_num, submit) = match seq_num_variant {
             SeqNum::V1(id) => (self.get_v2_submit_shares_seq_num(&id)?, true),
             SeqNum::V2(value) => (value, self.v2_submit_share_queue.is_empty()),
@@ -1007,7 +1011,7 @@ impl V2ToV1Translation {
             "Registering V2 job ID {:x?} -> V1 job ID {:x?}",
             v2_job.job_id,
             payload.job_id();
-            self.proxy_info
+            &self.proxy_info
         );
         // TODO extract this duplicate code, turn the map into a new type with this
         // custom policy (attempt to insert with the same key is a bug)
@@ -1154,7 +1158,7 @@ impl V2ToV1Translation {
             id,
             self.state,
             &msg;
-            self.proxy_info
+            &self.proxy_info
         );
         self.visit_stratum_result_or_error(&id, V1ResultOrError::Result(&msg))
             .await
@@ -1170,7 +1174,7 @@ impl V2ToV1Translation {
             id,
             self.state,
             msg;
-            self.proxy_info
+            &self.proxy_info
         );
         self.visit_stratum_result_or_error(&id, V1ResultOrError::Error(&msg))
             .await
@@ -1186,7 +1190,7 @@ impl V2ToV1Translation {
             id,
             self.state,
             msg;
-            self.proxy_info
+            &self.proxy_info
         );
         let diff = msg.value() as u32;
         self.v2_target = Some(Self::diff_to_target(diff));
@@ -1219,7 +1223,7 @@ impl V2ToV1Translation {
             id,
             self.state,
             msg;
-            self.proxy_info
+            &self.proxy_info
         );
 
         // Update extranonces.
@@ -1239,7 +1243,7 @@ impl V2ToV1Translation {
             id,
             self.state,
             msg;
-            self.proxy_info
+            &self.proxy_info
         );
 
         // We won't process the job as long as the channel is not operational
@@ -1273,7 +1277,7 @@ impl V2ToV1Translation {
             id,
             self.state,
             msg;
-            self.proxy_info
+            &self.proxy_info
         );
         Ok(())
     }
@@ -1288,7 +1292,7 @@ impl V2ToV1Translation {
             id,
             self.state,
             msg;
-            self.proxy_info
+            &self.proxy_info
         );
         // Propagate the reconnect only if configured so
         if self.options.propagate_reconnect_downstream {
@@ -1302,7 +1306,7 @@ impl V2ToV1Translation {
 
     async fn handle_ping(&mut self, payload: (MessageId, v1::messages::Ping)) -> Result<()> {
         let msg = v1::messages::Pong("pong".into());
-        debug!("Received {:?} message, sending {:?} response", payload, msg; self.proxy_info);
+        debug!("Received {:?} message, sending {:?} response", payload, msg; &self.proxy_info);
         let stratum_result = v1::rpc::ResponsePayload::try_from(msg)
             .expect("BUG: Pong response to ping couldn't be serialized")
             .ok();
@@ -1319,10 +1323,10 @@ impl V2ToV1Translation {
         // the connection
         match parsed_frame {
             Ok(rpc_msg) => {
-                warn!("Unknown stratum v1 message received: {:?}", rpc_msg; self.proxy_info);
+                warn!("Unknown stratum v1 message received: {:?}", rpc_msg; &self.proxy_info);
             }
             Err(e) => {
-                warn!("Broken stratum v1 Rpc frame received: {:?}", e; self.proxy_info);
+                warn!("Broken stratum v1 Rpc frame received: {:?}", e; &self.proxy_info);
             }
         }
         Ok(())
@@ -1332,10 +1336,10 @@ impl V2ToV1Translation {
 #[handler(async try v2::framing::Frame suffix _v2)]
 impl V2ToV1Translation {
     async fn handle_setup_connection(&mut self, msg: v2::messages::SetupConnection) -> Result<()> {
-        trace!("handle_setup_connection(): {:?}", msg; self.proxy_info);
+        trace!("handle_setup_connection(): {:?}", msg; &self.proxy_info);
 
         if self.state != V2ToV1TranslationState::Init {
-            trace!("Cannot setup connection again, received: {:?}", msg; self.proxy_info);
+            trace!("Cannot setup connection again, received: {:?}", msg; &self.proxy_info);
 
             let err_msg = v2::messages::SetupConnectionError {
                 code: "Connection can be setup only once"
@@ -1383,7 +1387,7 @@ impl V2ToV1Translation {
             "handle_open_standard_mining_channel() state={:?} payload:{:?}",
             self.state,
             msg;
-            self.proxy_info
+            &self.proxy_info
         );
         if self.state != V2ToV1TranslationState::ConnectionSetup
             && self.state != V2ToV1TranslationState::V1SubscribeOrAuthorizeFail
@@ -1391,7 +1395,7 @@ impl V2ToV1Translation {
             trace!(
                 "Out of sequence OpenStandardMiningChannel message, received: {:?}",
                 msg;
-                self.proxy_info
+                &self.proxy_info
             );
             let err_msg = v2::messages::OpenMiningChannelError {
                 req_id: msg.req_id,
@@ -1467,7 +1471,7 @@ impl V2ToV1Translation {
             "handle_submit_shares_standard() state={:?} payload:{:02x?}",
             self.state,
             msg;
-            self.proxy_info
+            &self.proxy_info
         );
         self.last_submit = Some(Instant::now());
         // Report invalid channel ID
@@ -1543,7 +1547,7 @@ impl V2ToV1Translation {
         // and processing broken frame should result in closing the connection
         match parsed_frame {
             Ok(v2_frame) => {
-                warn!("Unknown stratum v2 message received: {:?}", v2_frame; self.proxy_info);
+                warn!("Unknown stratum v2 message received: {:?}", v2_frame; &self.proxy_info);
                 Ok(())
             }
             Err(e) => Err(V2ProtocolError::Other(format!(
@@ -352,7 +352,7 @@ async fn test_v2server_full_with_proxy_protocol() {
         .expect("BUG: invalid addresses");
 
     // Dummy pool server
-    tokio::spawn(v1server_task(addr_v1.clone(), Some(proxy_info)));
+    tokio::spawn(v1server_task(addr_v1.clone(), Some(proxy_info.clone())));
 
     let v2server = server::ProxyServer::listen(
         addr_v2.clone(),
@@ -0,0 +1,106 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures::prelude::*;
+use pin_project_lite::pin_project;
+
+use crate::Tripwire;
+
+pin_project! {
+    /// Stream adaptor that stops accepting new items once the `Tripwire` fires but still yields
+    /// items that are immediately available from the inner stream (e.g. those already buffered
+    /// in a channel) before it ends. This is unlike `take_until()` which ends right away.
+    #[must_use = "streams do nothing unless polled"]
+    pub struct DrainOnHalt<S> {
+        #[pin]
+        inner: S,
+        #[pin]
+        tripwire: Tripwire,
+        halted: bool,
+    }
+}
+
+impl<S: Stream> DrainOnHalt<S> {
+    pub fn new(inner: S, tripwire: Tripwire) -> Self {
+        Self {
+            inner,
+            tripwire,
+            halted: false,
+        }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S: Stream> Stream for DrainOnHalt<S> {
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+
+        if !*this.halted {
+            if let Poll::Ready(()) = this.tripwire.poll(cx) {
+                *this.halted = true;
+            }
+        }
+
+        match this.inner.poll_next(cx) {
+            // Once halted, waiting for an item means there's nothing left to drain
+            Poll::Pending if *this.halted => Poll::Ready(None),
+            poll => poll,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use tokio::sync::mpsc;
+    use tokio_stream::wrappers::UnboundedReceiverStream;
+
+    #[tokio::test]
+    async fn drain_on_halt() {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let (trigger, tripwire) = Tripwire::new();
+        let mut stream = DrainOnHalt::new(UnboundedReceiverStream::new(rx), tripwire);
+
+        tx.send(1).expect("BUG: Cannot send item");
+        assert_eq!(stream.next().await, Some(1));
+
+        // Items buffered at the time of halt are still yielded
+        tx.send(2).expect("BUG: Cannot send item");
+        tx.send(3).expect("BUG: Cannot send item");
+        trigger.cancel();
+        assert_eq!(stream.next().await, Some(2));
+        assert_eq!(stream.next().await, Some(3));
+
+        // The stream ends once drained even though the sender is still alive
+        assert_eq!(stream.next().await, None);
+        drop(tx);
+    }
+}
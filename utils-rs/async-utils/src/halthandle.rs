@@ -20,13 +20,16 @@
 // of such proprietary license or if you have any other questions, please
 // contact us at opensource@braiins.com.
 
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::error::Error as StdError;
 use std::fmt;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
 use std::pin::Pin;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use std::task::{Context, Poll};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use futures::prelude::*;
 use tokio::sync::{mpsc, watch, Notify};
@@ -72,15 +75,341 @@ where
 #[cfg(all(not(target_family = "unix"), not(target_family = "windows")))]
 compile_error!("Unsupported OS family");
 
-pub trait Spawnable {
-    fn run(self, tripwire: Tripwire) -> JoinHandle<()>;
+/// Signals that can be handled by `HaltHandle::handle_signals()`. On windows, the console events
+/// are used instead: `Interrupt` is CTRL-C, `Terminate` is CTRL-BREAK and `Hangup` is closing of
+/// the console. `User1` and `User2` are not supported on windows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SignalKind {
+    Interrupt,
+    Terminate,
+    Hangup,
+    User1,
+    User2,
+}
+
+#[cfg(target_family = "unix")]
+fn signal_stream(kind: SignalKind) -> io::Result<stream::BoxStream<'static, SignalKind>> {
+    use tokio::signal::unix;
+
+    let unix_kind = match kind {
+        SignalKind::Interrupt => unix::SignalKind::interrupt(),
+        SignalKind::Terminate => unix::SignalKind::terminate(),
+        SignalKind::Hangup => unix::SignalKind::hangup(),
+        SignalKind::User1 => unix::SignalKind::user_defined1(),
+        SignalKind::User2 => unix::SignalKind::user_defined2(),
+    };
+    let signal = unix::signal(unix_kind)?;
+    Ok(stream::unfold(signal, move |mut signal| async move {
+        signal.recv().await.map(|_| (kind, signal))
+    })
+    .boxed())
+}
+
+#[cfg(target_family = "windows")]
+fn signal_stream(kind: SignalKind) -> io::Result<stream::BoxStream<'static, SignalKind>> {
+    use tokio::signal::windows;
+
+    macro_rules! console_event_stream {
+        ($listener:expr) => {
+            Ok(stream::unfold($listener?, move |mut listener| async move {
+                listener.recv().await.map(|_| (kind, listener))
+            })
+            .boxed())
+        };
+    }
+
+    match kind {
+        SignalKind::Interrupt => console_event_stream!(windows::ctrl_c()),
+        SignalKind::Terminate => console_event_stream!(windows::ctrl_break()),
+        SignalKind::Hangup => console_event_stream!(windows::ctrl_close()),
+        SignalKind::User1 | SignalKind::User2 => Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("Signal {:?} is not supported on windows", kind),
+        )),
+    }
+}
+
+pub trait Spawnable<R = ()> {
+    fn run(self, tripwire: Tripwire<R>) -> JoinHandle<()>;
+}
+
+/// Fallible variant of `Spawnable`, see `HaltHandle::spawn_try_object()`
+pub trait TrySpawnable<R = ()> {
+    type Error: Into<Box<dyn StdError + Send + Sync>> + Send + 'static;
+
+    fn run(self, tripwire: Tripwire<R>) -> JoinHandle<Result<(), Self::Error>>;
+}
+
+/// Reason of a halt that a `Trigger` passes to its tripwires, see `Trigger::cancel_with()` and
+/// `HaltHandle::halt_with()`. The default reason is used when no reason is given.
+pub trait HaltReason: Clone + Default + Send + Sync + 'static {}
+
+impl<R: Clone + Default + Send + Sync + 'static> HaltReason for R {}
+
+/// Error returned by a task spawned with `HaltHandle::spawn_try_object()`,
+/// see `HaltHandle::join_with_errors()`
+#[derive(Debug)]
+pub struct TaskError {
+    /// Name of the task that has failed
+    pub name: String,
+    pub error: Box<dyn StdError + Send + Sync>,
+}
+
+impl fmt::Display for TaskError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Task {} failed: {}", self.name, self.error)
+    }
+}
+
+impl StdError for TaskError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(self.error.as_ref())
+    }
+}
+
+/// State of a task spawned on a `HaltHandle`, see `HaltHandle::tasks_snapshot()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskState {
+    Running,
+    Finished,
+    Panicked,
+    /// The task has been dropped before completion, e.g. by `HaltHandle::halt_then_abort()`
+    Aborted,
+}
+
+/// Point-in-time information about a task spawned on a `HaltHandle`
+#[derive(Debug, Clone)]
+pub struct TaskInfo {
+    /// Name provided to `spawn_named()`, unnamed tasks are named after the order they
+    /// were spawned in (`#0`, `#1`, ...)
+    pub name: String,
+    pub state: TaskState,
+    pub spawned_at: Instant,
+    /// When the task finished, panicked or was aborted, `None` while it's running
+    pub finished_at: Option<Instant>,
+    /// How many times the task has been restarted after a panic, see
+    /// `HaltHandle::spawn_supervised()`
+    pub restarts: usize,
+}
+
+impl TaskInfo {
+    /// How long the task has been running, `None` while it's still running
+    pub fn lifetime(&self) -> Option<Duration> {
+        self.finished_at
+            .map(|finished_at| finished_at.saturating_duration_since(self.spawned_at))
+    }
+}
+
+/// Maximum number of stopped tasks listed by `HaltHandle::tasks_snapshot()`, records of older
+/// stopped tasks are dropped so that a long-running handle doesn't accumulate them
+pub const FINISHED_TASK_RECORDS: usize = 1024;
+
+/// Internal, shared by the `HaltHandle` and the wrapper of the task it describes.
+/// The wrapper updates the state once the task completes.
+#[derive(Debug)]
+struct TaskRecord {
+    /// Order in which the task was spawned
+    id: usize,
+    name: String,
+    /// Whether the state is updated by the wrapper, the state of a task added by
+    /// `HaltHandle::add_task()` is only recorded once the task is joined
+    tracked: bool,
+    spawned_at: Instant,
+    state: Mutex<TaskState>,
+    finished_at: Mutex<Option<Instant>>,
+    restarts: AtomicUsize,
+    /// Notified when the task stops running, see `HaltHandle::join_progress()`
+    notify_stopped: Arc<Notify>,
+}
+
+impl TaskRecord {
+    fn new(id: usize, name: String, tracked: bool, notify_stopped: Arc<Notify>) -> Self {
+        Self {
+            id,
+            name,
+            tracked,
+            spawned_at: Instant::now(),
+            state: Mutex::new(TaskState::Running),
+            finished_at: Mutex::new(None),
+            restarts: AtomicUsize::new(0),
+            notify_stopped,
+        }
+    }
+
+    /// Records that the task has stopped running
+    fn set_state(&self, state: TaskState) {
+        *self
+            .finished_at
+            .lock()
+            .expect("BUG: TaskRecord: Poisoned mutex") = Some(Instant::now());
+        *self.state.lock().expect("BUG: TaskRecord: Poisoned mutex") = state;
+        self.notify_stopped.notify_one();
+    }
+
+    fn is_running(&self) -> bool {
+        *self.state.lock().expect("BUG: TaskRecord: Poisoned mutex") == TaskState::Running
+    }
+
+    fn info(&self) -> TaskInfo {
+        TaskInfo {
+            name: self.name.clone(),
+            state: *self.state.lock().expect("BUG: TaskRecord: Poisoned mutex"),
+            spawned_at: self.spawned_at,
+            finished_at: *self
+                .finished_at
+                .lock()
+                .expect("BUG: TaskRecord: Poisoned mutex"),
+            restarts: self.restarts.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Wraps the task future so that the record is updated when the task finishes, panics or is
+    /// aborted. A panic is propagated further so that it's still reported as a `JoinError` by
+    /// `join()`.
+    async fn track<FT>(self: Arc<Self>, ft: FT)
+    where
+        FT: Future<Output = ()>,
+    {
+        /// Marks the task as aborted if it's dropped while still running
+        struct AbortGuard(Arc<TaskRecord>);
+
+        impl Drop for AbortGuard {
+            fn drop(&mut self) {
+                if self.0.is_running() {
+                    self.0.set_state(TaskState::Aborted);
+                }
+            }
+        }
+
+        let _guard = AbortGuard(self.clone());
+        match AssertUnwindSafe(ft).catch_unwind().await {
+            Ok(()) => self.set_state(TaskState::Finished),
+            Err(panic_payload) => {
+                self.set_state(TaskState::Panicked);
+                panic::resume_unwind(panic_payload);
+            }
+        }
+    }
+}
+
+/// Internal, counts a spawned task as outstanding until the task is dropped, i.e. until it
+/// finishes, panics or is aborted. Unlike `TaskRecord::track()`, this also covers a task that
+/// is aborted before it's first polled.
+#[derive(Debug)]
+struct OutstandingTask(Arc<AtomicUsize>);
+
+impl OutstandingTask {
+    fn new(count: Arc<AtomicUsize>) -> Self {
+        count.fetch_add(1, Ordering::SeqCst);
+        Self(count)
+    }
+}
+
+impl Drop for OutstandingTask {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Exponentially growing delay between restarts of a task spawned by
+/// `HaltHandle::spawn_supervised()`. The delay starts at `initial` and doubles with each restart
+/// up to `max`.
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    next: Duration,
+    max: Duration,
+}
+
+impl Backoff {
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        Self {
+            next: initial.min(max),
+            max,
+        }
+    }
+
+    /// Returns the delay before the next restart and doubles the following one
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = self.next;
+        self.next = (self.next * 2).min(self.max);
+        delay
+    }
+}
+
+impl Default for Backoff {
+    /// Starts at 100 ms and grows up to 30 s
+    fn default() -> Self {
+        Self::new(Duration::from_millis(100), Duration::from_secs(30))
+    }
+}
+
+/// Timestamp of the last activity of a group of tasks, see `HaltHandle::halt_group_on_idle()`.
+/// Clones share the timestamp so that each task of the group can report its activity by calling
+/// `touch()`, e.g. whenever a request is processed.
+#[derive(Debug, Clone)]
+pub struct Activity {
+    start: Instant,
+    /// Nanoseconds since `start`
+    last: Arc<AtomicU64>,
+}
+
+impl Activity {
+    /// Creates the tracker, creation counts as activity
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            last: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Records activity now
+    pub fn touch(&self) {
+        let elapsed = self.start.elapsed().as_nanos() as u64;
+        self.last.fetch_max(elapsed, Ordering::Relaxed);
+    }
+
+    /// How long there hasn't been any activity
+    pub fn idle_for(&self) -> Duration {
+        let last = Duration::from_nanos(self.last.load(Ordering::Relaxed));
+        self.start.elapsed().saturating_sub(last)
+    }
+}
+
+impl Default for Activity {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Internal, allows tracking a task that has been spawned outside of the `HaltHandle` (see
+/// `spawn_try_object()`) by awaiting its `JoinHandle` in a wrapper task. Aborting the wrapper
+/// task aborts the original task, too. Resolves to `None` when the original task has been
+/// cancelled.
+struct AbortOnDrop<T>(JoinHandle<T>);
+
+impl<T> Future for AbortOnDrop<T> {
+    type Output = Option<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        match Pin::new(&mut self.0).poll(cx) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(Err(err)) if err.is_panic() => panic::resume_unwind(err.into_panic()),
+            Poll::Ready(result) => Poll::Ready(result.ok()),
+        }
+    }
+}
+
+impl<T> Drop for AbortOnDrop<T> {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
 }
 
 /// Internal, used to signal termination via `trigger`
 /// and notify `Tasks` when that happens.
 #[derive(Debug)]
-struct Halt {
-    trigger: Trigger,
+struct Halt<R> {
+    trigger: Trigger<R>,
     notify_join: Arc<Notify>,
 }
 
@@ -94,10 +423,42 @@ struct Halt {
 /// `ready()` to send a Ready notification.
 #[derive(Debug)]
 enum TaskMsg {
-    Task(JoinHandle<()>),
+    Task(NamedTask),
     Ready,
 }
 
+/// Internal, join handle of a spawned task along with the task name so that a join error can be
+/// attributed to the task
+#[derive(Debug)]
+struct NamedTask {
+    name: String,
+    handle: JoinHandle<()>,
+    record: Arc<TaskRecord>,
+    /// Set for a task added by `HaltHandle::add_task()`, such a task isn't wrapped so it's
+    /// counted as outstanding until this is dropped
+    _outstanding: Option<OutstandingTask>,
+}
+
+impl NamedTask {
+    /// Records the `result` of awaiting `handle` in case the task isn't tracked by its wrapper
+    fn record(&self, result: &Result<(), JoinError>) {
+        if !self.record.tracked {
+            self.record.set_state(match result {
+                Ok(()) => TaskState::Finished,
+                Err(error) if error.is_panic() => TaskState::Panicked,
+                Err(_) => TaskState::Aborted,
+            });
+        }
+    }
+
+    async fn join(mut self) -> Result<(), HaltError> {
+        let result = (&mut self.handle).await;
+        self.record(&result);
+        let name = self.name;
+        result.map_err(|error| HaltError::Join { name, error })
+    }
+}
+
 /// Internal, used in `HaltHandle::join()`
 /// to wait on signal from `halt()`
 /// and then collect halting tasks' join handles.
@@ -107,20 +468,113 @@ struct Tasks {
     notify_join: Arc<Notify>,
 }
 
+/// Internal, the result of `HaltHandle::join()` fanned out to all callers of
+/// `HaltHandle::join_shared()`
+type SharedJoin = future::Shared<future::BoxFuture<'static, Result<(), Arc<HaltError>>>>;
+
+/// Internal, a group of tasks that can be halted and joined separately from the rest of
+/// the tasks, see `HaltHandle::spawn_in_group()`
+struct Group<R> {
+    trigger: Trigger<R>,
+    /// Tripwire that is cloned into the tasks of the group
+    tripwire: Tripwire<R>,
+    tasks: Vec<NamedTask>,
+}
+
+impl<R: HaltReason> Group<R> {
+    /// Creates an empty group, its tripwire is fired right away with the `halt_reason` if the
+    /// whole handle has already been halted
+    fn new(halt_reason: Option<R>) -> Self {
+        let (trigger, tripwire) = Tripwire::new_with_reason();
+        if let Some(reason) = halt_reason {
+            trigger.fire(reason);
+        }
+        Self {
+            trigger,
+            tripwire,
+            tasks: Vec::new(),
+        }
+    }
+
+    /// Fires the tripwire of the current tasks and arms a new one for tasks spawned into the
+    /// group later on
+    fn recycle(&mut self) {
+        let (trigger, tripwire) = Tripwire::new_with_reason();
+        self.trigger.fire(R::default());
+        self.trigger = trigger;
+        self.tripwire = tripwire;
+    }
+}
+
+/// Summary of `HaltHandle::halt_then_abort()`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct HaltReport {
+    /// Number of tasks that stopped by themselves within the grace period
+    pub cooperative: usize,
+    /// Number of tasks that panicked within the grace period
+    pub panicked: usize,
+    /// Number of tasks that didn't stop within the grace period and had to be aborted
+    pub aborted: usize,
+    /// Total number of restarts of the tasks spawned by `HaltHandle::spawn_supervised()`
+    pub restarts: usize,
+}
+
+/// Event reported by `HaltHandle::join_progress()`
+#[derive(Debug)]
+pub enum JoinProgress {
+    /// Task `name` has stopped running, `remaining` tasks are still running
+    TaskStopped { name: String, remaining: usize },
+    /// The last event, carries the same result as `join()`
+    Done(Result<(), HaltError>),
+}
+
 /// Error type returned by `HaltHandle::join()`.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum HaltError {
-    /// Tasks didn't finish inside the timeout passed to `join()`.
-    Timeout,
-    /// One of the tasks panicked.
-    Join(JoinError),
+    /// Tasks didn't finish inside the timeout passed to `join()`, `pending` are the names of
+    /// the tasks that were still running.
+    Timeout { pending: Vec<String> },
+    /// One of the tasks panicked, `name` is the name of the task as reported by
+    /// `tasks_snapshot()`.
+    Join { name: String, error: JoinError },
+    /// A task spawned with `spawn_try_object()` returned an error.
+    TaskFailed(TaskError),
 }
 
 impl HaltError {
     fn map<'a, T, F: FnOnce(&'a JoinError) -> Option<T>>(&'a self, f: F) -> Option<T> {
         match self {
-            HaltError::Timeout => None,
-            HaltError::Join(err) => f(err),
+            HaltError::Timeout { .. } | HaltError::TaskFailed(_) => None,
+            HaltError::Join { error, .. } => f(error),
+        }
+    }
+
+    /// Describes the error as key-value pairs for structured logging. The `kind` field is
+    /// `timeout`, `join` or `task_failed`. A timeout provides the comma separated names of the
+    /// `pending` tasks. A join error also provides its `cause` (`panic` or `cancelled`), the
+    /// `message` that includes the panic message if it's a string and the `task` name. A task
+    /// failure provides the `message` and the `task` name.
+    pub fn to_log_fields(&self) -> Vec<(&'static str, String)> {
+        match self {
+            HaltError::Timeout { pending } => vec![
+                ("kind", "timeout".to_string()),
+                ("pending", pending.join(", ")),
+            ],
+            HaltError::Join { name, error: err } => vec![
+                ("kind", "join".to_string()),
+                (
+                    "cause",
+                    if err.is_panic() { "panic" } else { "cancelled" }.to_string(),
+                ),
+                ("message", err.to_string()),
+                ("task", name.clone()),
+            ],
+            HaltError::TaskFailed(err) => vec![
+                ("kind", "task_failed".to_string()),
+                ("message", err.error.to_string()),
+                ("task", err.name.clone()),
+            ],
         }
     }
 }
@@ -128,20 +582,32 @@ impl HaltError {
 impl fmt::Display for HaltError {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            HaltError::Timeout => write!(fmt, "Timeout"),
-            HaltError::Join(err) => write!(fmt, "Join error: {}", err),
+            HaltError::Timeout { pending } if pending.is_empty() => write!(fmt, "Timeout"),
+            HaltError::Timeout { pending } => {
+                write!(fmt, "Timeout, pending tasks: {}", pending.join(", "))
+            }
+            HaltError::Join { name, error } => {
+                write!(fmt, "Join error in task {}: {}", name, error)
+            }
+            HaltError::TaskFailed(err) => write!(fmt, "{}", err),
         }
     }
 }
 
 impl StdError for HaltError {
     fn source(&self) -> Option<&(dyn StdError + 'static)> {
-        self.map(JoinError::source)
+        match self {
+            HaltError::TaskFailed(err) => Some(err.error.as_ref()),
+            _ => self.map(JoinError::source),
+        }
     }
 
     #[allow(deprecated)]
     fn cause(&self) -> Option<&dyn StdError> {
-        self.map(JoinError::cause)
+        match self {
+            HaltError::TaskFailed(err) => Some(err.error.as_ref()),
+            _ => self.map(JoinError::cause),
+        }
     }
 }
 
@@ -150,78 +616,161 @@ impl StdError for HaltError {
 ///
 /// NB. This is really just a thin wrapper around `watch::Sender`.
 #[derive(Debug)]
-pub struct Trigger(watch::Sender<bool>);
+pub struct Trigger<R = ()>(watch::Sender<Option<R>>);
 
-impl Trigger {
+impl<R: HaltReason> Trigger<R> {
+    /// Fires the associated tripwires with the default reason
     pub fn cancel(self) {
-        let _ = self.0.send(true);
+        self.fire(R::default());
+    }
+
+    /// Fires the associated tripwires, they resolve to `reason`
+    pub fn cancel_with(self, reason: R) {
+        self.fire(reason);
     }
+
+    /// Only the first reason is kept when fired multiple times
+    fn fire(&self, reason: R) {
+        if self.0.borrow().is_none() {
+            let _ = self.0.send(Some(reason));
+        }
+    }
+}
+
+/// Reason why a strict `Tripwire` has resolved, see `Tripwire::strict()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TripwireCause {
+    /// The associated `Trigger` has fired
+    Cancelled,
+    /// The associated `Trigger` has been dropped without firing
+    TriggerDropped,
 }
 
-type WaitForHaltFuture =
-    Pin<Box<dyn Future<Output = Result<(), watch::error::RecvError>> + Send + Sync>>;
+type WaitForHaltFuture<R> =
+    Pin<Box<dyn Future<Output = Result<R, watch::error::RecvError>> + Send + Sync>>;
 
 /// A synchronization end that tasks can use to wait on
 /// using eg. `take_until()` or `select!()` or similar
-/// to await cancellation.
+/// to await cancellation. The tripwire resolves to the reason the `Trigger` has been fired
+/// with, a `Trigger` dropped without firing resolves it to the default reason.
 ///
 /// NB. This is really just a thin wrapper around `watch::Receiver`.
-pub struct Tripwire {
-    receiver: Option<watch::Receiver<bool>>,
-    wait_for_halt_future: Option<WaitForHaltFuture>,
+pub struct Tripwire<R = ()> {
+    receiver: Option<watch::Receiver<Option<R>>>,
+    wait_for_halt_future: Option<WaitForHaltFuture<R>>,
+    strict: bool,
+    cause: Option<TripwireCause>,
+    /// Reason the tripwire has resolved to
+    reason: Option<R>,
 }
 
 impl Tripwire {
     pub fn new() -> (Trigger, Self) {
-        let (sender, receiver) = watch::channel(false);
+        Self::new_with_reason()
+    }
+}
+
+impl<R: HaltReason> Tripwire<R> {
+    /// Same as `new()` but the tripwire resolves to a reason of type `R`, see
+    /// `Trigger::cancel_with()`
+    pub fn new_with_reason() -> (Trigger<R>, Self) {
+        let (sender, receiver) = watch::channel(None);
         (
             Trigger(sender),
             Tripwire {
                 receiver: Some(receiver),
                 wait_for_halt_future: None,
+                strict: false,
+                cause: None,
+                reason: None,
             },
         )
     }
 
+    /// Switches the tripwire into strict mode in which a `Trigger` dropped without `cancel()`
+    /// is told apart from an explicit cancellation. The tripwire resolves in both cases, the
+    /// cause can be inspected via `cause()` afterwards.
+    pub fn strict(mut self) -> Self {
+        self.strict = true;
+        self
+    }
+
+    /// Returns why the tripwire has resolved. This is only known in strict mode, `None` is
+    /// returned otherwise or when the tripwire hasn't resolved yet.
+    pub fn cause(&self) -> Option<TripwireCause> {
+        self.cause
+    }
+
+    /// Returns whether the associated `Trigger` has fired without waiting. A tripwire that has
+    /// already resolved is always reported as tripped.
+    pub fn is_tripped(&self) -> bool {
+        match &self.receiver {
+            Some(receiver) => receiver.borrow().is_some(),
+            None => true,
+        }
+    }
+
+    /// Returns the reason the associated `Trigger` has fired with without waiting, `None` is
+    /// returned when it hasn't fired yet
+    pub fn reason(&self) -> Option<R> {
+        match &self.receiver {
+            Some(receiver) => receiver.borrow().clone(),
+            None => self.reason.clone(),
+        }
+    }
+
     async fn wait_for_halt(
-        mut receiver: watch::Receiver<bool>,
-    ) -> Result<(), watch::error::RecvError> {
+        mut receiver: watch::Receiver<Option<R>>,
+    ) -> Result<R, watch::error::RecvError> {
         loop {
-            receiver.changed().await?;
-            if *receiver.borrow() {
-                break;
+            if let Some(reason) = receiver.borrow().clone() {
+                return Ok(reason);
             }
+            receiver.changed().await?;
         }
-        Ok(())
     }
 }
 
-impl Clone for Tripwire {
+impl<R: Clone> Clone for Tripwire<R> {
     fn clone(&self) -> Self {
         Self {
             receiver: self.receiver.clone(),
             wait_for_halt_future: None,
+            strict: self.strict,
+            cause: self.cause,
+            reason: self.reason.clone(),
         }
     }
 }
 
-impl Future for Tripwire {
-    type Output = ();
+// The reason is never pinned
+impl<R> Unpin for Tripwire<R> {}
 
-    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+impl<R: HaltReason> Future for Tripwire<R> {
+    type Output = R;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<R> {
         let receiver = match &self.receiver {
             Some(receiver) => receiver.clone(),
-            None => return Poll::Ready(()),
+            None => return Poll::Ready(self.reason.clone().unwrap_or_default()),
         };
         let wait_for_halt_future = self
             .wait_for_halt_future
             .get_or_insert_with(|| Box::pin(Self::wait_for_halt(receiver)));
         match wait_for_halt_future.as_mut().poll(cx) {
             Poll::Pending => Poll::Pending,
-            Poll::Ready(_) => {
+            Poll::Ready(result) => {
+                if self.strict {
+                    self.cause = Some(match result {
+                        Ok(_) => TripwireCause::Cancelled,
+                        Err(_) => TripwireCause::TriggerDropped,
+                    });
+                }
+                let reason = result.unwrap_or_default();
+                self.reason = Some(reason.clone());
                 self.receiver.take();
                 self.wait_for_halt_future.take();
-                Poll::Ready(())
+                Poll::Ready(reason)
             }
         }
     }
@@ -240,15 +789,26 @@ impl Future for Tripwire {
 ///    handler that calls `halt()` on `SIGTERM` & `SIGINT`.
 /// 5. Use `join()` to wait on the tasks to stop (a timeout may be used).
 ///
+/// Servers that want to stop taking new work but let the work in progress finish can use
+/// `drain()` instead of `halt()` in step 4, see `spawn_with_drain()`.
+///
+/// A handle of type `HaltHandle<R>` (created by `HaltHandle::<R>::default()`) can be halted
+/// with a reason of type `R` via `halt_with()`, the tripwires of the tasks then resolve to the
+/// reason, e.g. to tell a shutdown on signal from a shutdown due to a fatal error.
+///
 /// Note that `halt()` or `halt_on_signal()` doesn't necessarily need to be called
 /// after `ready()`. These can be called pretty much anytime and it won't cause
 /// a race condition as long as `ready()` is called in the right moment.
-pub struct HaltHandle {
+pub struct HaltHandle<R = ()> {
     /// Tripwire that is cloned into
     /// 'child' tasks when they are started with this handle.
-    tripwire: Tripwire,
+    tripwire: Tripwire<R>,
     /// Used to trigger the tripwire and then notifies `tasks`.
-    halt: Mutex<Option<Halt>>,
+    halt: Mutex<Option<Halt<R>>>,
+    /// Tripwire that fires when the tasks should stop taking new work, see `drain()`
+    draining: Tripwire,
+    /// Fires `draining`, taken on first use
+    drain_trigger: Mutex<Option<Trigger>>,
     /// Spawned task handles as well as a ready notification are sent here, see `TaskMsg`
     tasks_tx: mpsc::UnboundedSender<TaskMsg>,
     /// Used to receive notification from `halt` and the task handles.
@@ -256,13 +816,31 @@ pub struct HaltHandle {
     /// A flag whether we've already spawned a signal task;
     /// this can only be done once.
     signal_task_spawned: AtomicBool,
+    /// Records of the tasks spawned on this handle, see `tasks_snapshot()`. Only the latest
+    /// `FINISHED_TASK_RECORDS` of the records of stopped tasks are kept.
+    task_records: Mutex<Vec<Arc<TaskRecord>>>,
+    /// Number of tasks spawned so far, used to name unnamed tasks
+    spawned_tasks: AtomicUsize,
+    /// Number of spawned tasks that are still running, see `outstanding_tasks()`
+    outstanding_tasks: Arc<AtomicUsize>,
+    /// The join shared by all callers of `join_shared()`, created by the first caller
+    shared_join: Mutex<Option<SharedJoin>>,
+    /// Notified by task records whenever a task stops running
+    notify_stopped: Arc<Notify>,
+    /// Errors returned by tasks spawned with `spawn_try_object()`
+    task_errors: Arc<Mutex<Vec<TaskError>>>,
+    /// Notified when a task spawned with `spawn_try_object()` fails
+    notify_failed: Arc<Notify>,
+    /// Tasks spawned with `spawn_in_group()` indexed by group
+    groups: Mutex<HashMap<u8, Group<R>>>,
 }
 
-impl Default for HaltHandle {
+impl<R: HaltReason> Default for HaltHandle<R> {
     fn default() -> Self {
-        let (trigger, tripwire) = Tripwire::new();
+        let (trigger, tripwire) = Tripwire::new_with_reason();
         let notify_join = Arc::new(Notify::new());
         let (tasks_tx, tasks_rx) = mpsc::unbounded_channel();
+        let (drain_trigger, draining) = Tripwire::new();
 
         Self {
             tripwire,
@@ -270,12 +848,22 @@ impl Default for HaltHandle {
                 trigger,
                 notify_join: notify_join.clone(),
             })),
+            draining,
+            drain_trigger: Mutex::new(Some(drain_trigger)),
             tasks_tx,
             tasks: Mutex::new(Some(Tasks {
                 tasks_rx: UnboundedReceiverStream::new(tasks_rx),
                 notify_join,
             })),
             signal_task_spawned: AtomicBool::new(false),
+            task_records: Mutex::new(Vec::new()),
+            spawned_tasks: AtomicUsize::new(0),
+            outstanding_tasks: Arc::new(AtomicUsize::new(0)),
+            shared_join: Mutex::new(None),
+            notify_stopped: Arc::new(Notify::new()),
+            task_errors: Arc::new(Mutex::new(Vec::new())),
+            notify_failed: Arc::new(Notify::new()),
+            groups: Mutex::new(HashMap::new()),
         }
     }
 }
@@ -290,7 +878,9 @@ impl HaltHandle {
     pub fn arc() -> Arc<Self> {
         Arc::new(Self::new())
     }
+}
 
+impl<R: HaltReason> HaltHandle<R> {
     /// Spawn a new task. `f` is a function that takes
     /// a `Tripwire` and returns a `Future` to be spawned.
     /// `Tripwire` can be passed to `StreamExt::take_until`
@@ -299,21 +889,229 @@ impl HaltHandle {
     pub fn spawn<FT, FN>(&self, f: FN)
     where
         FT: Future<Output = ()> + Send + 'static,
-        FN: FnOnce(Tripwire) -> FT,
+        FN: FnOnce(Tripwire<R>) -> FT,
+    {
+        let ft = f(self.tripwire());
+        let record = self.register_task(None, true);
+        self.send_task(self.spawn_tracked(record, ft));
+    }
+
+    /// Same as `spawn()`, the task is listed under `name` in `tasks_snapshot()`
+    pub fn spawn_named<FT, FN>(&self, name: impl Into<String>, f: FN)
+    where
+        FT: Future<Output = ()> + Send + 'static,
+        FN: FnOnce(Tripwire<R>) -> FT,
     {
         let ft = f(self.tripwire());
-        self.add_task(tokio::spawn(ft));
+        let record = self.register_task(Some(name.into()), true);
+        self.send_task(self.spawn_tracked(record, ft));
+    }
+
+    /// Same as `spawn()`, but `f` is passed two tripwires. The first one fires once `drain()`
+    /// (or `halt()`) is called and the task should stop taking new work, e.g. stop accepting
+    /// connections. The second one is the regular tripwire that fires when the task has to stop.
+    pub fn spawn_with_drain<FT, FN>(&self, f: FN)
+    where
+        FT: Future<Output = ()> + Send + 'static,
+        FN: FnOnce(Tripwire, Tripwire<R>) -> FT,
+    {
+        let ft = f(self.draining(), self.tripwire());
+        let record = self.register_task(None, true);
+        self.send_task(self.spawn_tracked(record, ft));
     }
 
-    pub fn spawn_object<T: Spawnable>(&self, obj: T) {
+    /// Same as `spawn()`, but the task becomes part of `group`. The tripwire passed to `f` fires
+    /// either when the whole handle is halted or when the group is halted by `halt_group()`.
+    pub fn spawn_in_group<FT, FN>(&self, group: u8, f: FN)
+    where
+        FT: Future<Output = ()> + Send + 'static,
+        FN: FnOnce(Tripwire<R>) -> FT,
+    {
+        let tripwire = self.with_group(group, |group| group.tripwire.clone());
+        let ft = f(tripwire);
+        let record = self.register_task(None, true);
+        let task = self.spawn_tracked(record, ft);
+        self.with_group(group, |group| group.tasks.push(task));
+    }
+
+    /// Same as `spawn_named()`, but the task is restarted whenever it panics. `f` is called to
+    /// create a new instance of the task for each run. Restarts are delayed according to
+    /// `backoff` to prevent a crashing task from hot-looping, halting the handle interrupts the
+    /// delay. The number of restarts is reported in `TaskInfo::restarts` and in
+    /// `HaltReport::restarts`.
+    pub fn spawn_supervised<FT, FN>(&self, name: impl Into<String>, mut backoff: Backoff, mut f: FN)
+    where
+        FT: Future<Output = ()> + Send + 'static,
+        FN: FnMut(Tripwire<R>) -> FT + Send + 'static,
+    {
+        let tripwire = self.tripwire();
+        let record = self.register_task(Some(name.into()), true);
+        let supervised_record = record.clone();
+        self.send_task(self.spawn_tracked(record, async move {
+            loop {
+                // Each run is spawned separately so that its panic can be caught here
+                let mut run = AbortOnDrop(tokio::spawn(f(tripwire.clone())));
+                match (&mut run.0).await {
+                    Err(err) if err.is_panic() => {}
+                    _ => break,
+                }
+                if tripwire.clone().now_or_never().is_some() {
+                    break;
+                }
+                tokio::select! {
+                    _ = time::sleep(backoff.next_delay()) => {}
+                    _ = tripwire.clone() => break,
+                }
+                supervised_record.restarts.fetch_add(1, Ordering::Relaxed);
+            }
+        }));
+    }
+
+    /// Spawns `obj` and tracks the returned task like the other tasks. A `Spawnable` that
+    /// ignores its `Tripwire` is still aborted by `halt_then_abort()`.
+    pub fn spawn_object<T: Spawnable<R>>(&self, obj: T) {
         self.add_task(obj.run(self.tripwire()));
     }
 
-    pub fn tripwire(&self) -> Tripwire {
+    /// Spawns a fallible `obj`. An error returned by the task doesn't panic, it is collected
+    /// and reported by `join()` as `HaltError::TaskFailed`, or by `join_with_errors()`. The
+    /// first error halts the handle so that the rest of the tasks fail fast, this takes effect
+    /// while `join()` is being awaited. The task itself is reported as finished by
+    /// `tasks_snapshot()`.
+    pub fn spawn_try_object<T: TrySpawnable<R>>(&self, obj: T) {
+        let task = AbortOnDrop(obj.run(self.tripwire()));
+        let record = self.register_task(None, true);
+        let name = record.name.clone();
+        let task_errors = self.task_errors.clone();
+        let notify_failed = self.notify_failed.clone();
+        self.send_task(self.spawn_tracked(record, async move {
+            if let Some(Err(error)) = task.await {
+                task_errors
+                    .lock()
+                    .expect("BUG: HaltHandle: Poisoned mutex")
+                    .push(TaskError {
+                        name,
+                        error: error.into(),
+                    });
+                notify_failed.notify_one();
+            }
+        }));
+    }
+
+    pub fn tripwire(&self) -> Tripwire<R> {
         self.tripwire.clone()
     }
 
+    /// Tripwire that fires once `drain()` or `halt()` is called, see `spawn_with_drain()`
+    pub fn draining(&self) -> Tripwire {
+        self.draining.clone()
+    }
+
+    /// Adds a task that has already been spawned. The task isn't wrapped, so
+    /// `tasks_snapshot()` reports it as running until it's joined (or aborted by
+    /// `halt_then_abort()`) and `drain()` doesn't wait for it.
     pub fn add_task(&self, task: JoinHandle<()>) {
+        let record = self.register_task(None, false);
+        let outstanding = OutstandingTask::new(self.outstanding_tasks.clone());
+        self.send_task(NamedTask {
+            name: record.name.clone(),
+            handle: task,
+            record,
+            _outstanding: Some(outstanding),
+        });
+    }
+
+    /// Returns the number of tasks spawned on this handle that are still running. The signal
+    /// handling task isn't counted.
+    pub fn outstanding_tasks(&self) -> usize {
+        self.outstanding_tasks.load(Ordering::SeqCst)
+    }
+
+    /// Returns information about all tasks spawned on this handle so far including the
+    /// already finished ones. Only the latest `FINISHED_TASK_RECORDS` stopped tasks are listed.
+    pub fn tasks_snapshot(&self) -> Vec<TaskInfo> {
+        self.task_records
+            .lock()
+            .expect("BUG: HaltHandle: Poisoned mutex")
+            .iter()
+            .map(|record| record.info())
+            .collect()
+    }
+
+    /// Creates a record of a new task, `tracked` is set for tasks wrapped by
+    /// `TaskRecord::track()`. Records of the oldest stopped tasks are dropped so that at most
+    /// `FINISHED_TASK_RECORDS` of them are kept.
+    fn register_task(&self, name: Option<String>, tracked: bool) -> Arc<TaskRecord> {
+        let id = self.spawned_tasks.fetch_add(1, Ordering::Relaxed);
+        let name = name.unwrap_or_else(|| format!("#{}", id));
+        let record = Arc::new(TaskRecord::new(
+            id,
+            name,
+            tracked,
+            self.notify_stopped.clone(),
+        ));
+
+        let mut task_records = self
+            .task_records
+            .lock()
+            .expect("BUG: HaltHandle: Poisoned mutex");
+        let stopped = task_records
+            .iter()
+            .filter(|record| !record.is_running())
+            .count();
+        let mut excess = stopped.saturating_sub(FINISHED_TASK_RECORDS);
+        if excess > 0 {
+            task_records.retain(|record| {
+                let drop = excess > 0 && !record.is_running();
+                if drop {
+                    excess -= 1;
+                }
+                !drop
+            });
+        }
+        task_records.push(record.clone());
+        record
+    }
+
+    /// Runs `f` on `group`, the group is created on first use
+    fn with_group<T>(&self, group: u8, f: impl FnOnce(&mut Group<R>) -> T) -> T {
+        let mut groups = self.groups.lock().expect("BUG: HaltHandle: Poisoned mutex");
+        let group = groups
+            .entry(group)
+            .or_insert_with(|| Group::new(self.tripwire.reason()));
+        f(group)
+    }
+
+    /// Spawns `ft` wrapped so that `record` tracks its state and the task is counted by
+    /// `outstanding_tasks()` until it's dropped
+    fn spawn_tracked<FT>(&self, record: Arc<TaskRecord>, ft: FT) -> NamedTask
+    where
+        FT: Future<Output = ()> + Send + 'static,
+    {
+        let outstanding = OutstandingTask::new(self.outstanding_tasks.clone());
+        let tracked_record = record.clone();
+        NamedTask {
+            name: record.name.clone(),
+            handle: tokio::spawn(async move {
+                let _outstanding = outstanding;
+                tracked_record.track(ft).await
+            }),
+            record,
+            _outstanding: None,
+        }
+    }
+
+    /// Takes join handles of tasks from all groups
+    fn take_group_tasks(&self) -> Vec<NamedTask> {
+        self.groups
+            .lock()
+            .expect("BUG: HaltHandle: Poisoned mutex")
+            .values_mut()
+            .flat_map(|group| group.tasks.drain(..))
+            .collect()
+    }
+
+    fn send_task(&self, task: NamedTask) {
         // Add the task join handle to tasks_tx (used by join()).
         // Errors are ignored here - send() on an unbounded channel
         // only fails if the receiver is dropped, and in that case
@@ -329,21 +1127,194 @@ impl HaltHandle {
         let _ = self.tasks_tx.send(TaskMsg::Ready);
     }
 
-    /// Tell the handle to halt all the associated tasks.
-    pub fn halt(&self) {
-        if let Some(halt) = self
-            .halt
+    /// Returns whether `halt()` has been called, e.g. for reporting the shutdown state from
+    /// a health check. Doesn't block and doesn't depend on `ready()` having been called.
+    pub fn is_halted(&self) -> bool {
+        self.tripwire.is_tripped()
+    }
+
+    /// Returns a future that resolves to the halt reason once `halt()` is called, e.g. for use
+    /// in `select!()` without managing a `Tripwire` clone
+    pub fn halted(&self) -> impl Future<Output = R> + Send + 'static {
+        self.tripwire()
+    }
+
+    /// Returns whether `drain()` or `halt()` has been called. Doesn't block.
+    pub fn is_draining(&self) -> bool {
+        self.draining.is_tripped()
+    }
+
+    /// Tell the handle to halt the associated tasks gracefully. The draining tripwire fires first
+    /// so that the tasks can finish the work in progress, the tasks are given up to `grace` time
+    /// to stop on their own and only then `halt()` is called.
+    ///
+    /// Returns `true` when all tasks have stopped within `grace`. Tasks added by `add_task()`
+    /// aren't waited for. The `timeout` of `join()` starts
+    /// counting once the grace period is over, so the tasks are joined within
+    /// `grace + timeout` at most.
+    pub async fn drain(&self, grace: Duration) -> bool {
+        self.start_draining();
+
+        let deadline = time::Instant::now() + grace;
+        let mut drained = true;
+        while self
+            .task_records
             .lock()
             .expect("BUG: HaltHandle: Poisoned mutex")
-            .take()
+            .iter()
+            .any(|record| record.tracked && record.is_running())
         {
-            halt.trigger.cancel();
-            halt.notify_join.notify_one();
+            if time::timeout_at(deadline, self.notify_stopped.notified())
+                .await
+                .is_err()
+            {
+                drained = false;
+                break;
+            }
         }
-    }
 
-    pub fn halt_on_signal(self: &Arc<Self>) {
-        Self::handle_signal(self.clone(), |this| async move { this.halt() });
+        self.halt();
+        drained
+    }
+
+    fn start_draining(&self) {
+        if let Some(trigger) = self
+            .drain_trigger
+            .lock()
+            .expect("BUG: HaltHandle: Poisoned mutex")
+            .take()
+        {
+            trigger.cancel();
+        }
+    }
+
+    /// Returns the reason passed to `halt_with()`, the default reason is reported when halted by
+    /// `halt()`. `None` is returned when the handle hasn't been halted yet.
+    pub fn halt_reason(&self) -> Option<R> {
+        self.tripwire.reason()
+    }
+
+    /// Tell the handle to halt all the associated tasks.
+    pub fn halt(&self) {
+        self.halt_with(R::default());
+    }
+
+    /// Same as `halt()`, the tripwires of the tasks resolve to `reason`. Only the reason of the
+    /// first halt is kept.
+    pub fn halt_with(&self, reason: R) {
+        self.start_draining();
+        // The lock is released before locking groups, see `with_group()`
+        let halt = self
+            .halt
+            .lock()
+            .expect("BUG: HaltHandle: Poisoned mutex")
+            .take();
+        if let Some(halt) = halt {
+            halt.trigger.cancel_with(reason.clone());
+            for group in self
+                .groups
+                .lock()
+                .expect("BUG: HaltHandle: Poisoned mutex")
+                .values()
+            {
+                group.trigger.fire(reason.clone());
+            }
+            halt.notify_join.notify_one();
+        }
+    }
+
+    /// Tell the handle to halt tasks of `group` only, the rest of the tasks keep running.
+    /// Tasks spawned into the group afterwards are not affected, so the group can be
+    /// repopulated once its tasks are collected by `join_group()`.
+    pub fn halt_group(&self, group: u8) {
+        if let Some(group) = self
+            .groups
+            .lock()
+            .expect("BUG: HaltHandle: Poisoned mutex")
+            .get_mut(&group)
+        {
+            group.recycle();
+        }
+    }
+
+    /// Watchdog that halts `group` once there hasn't been any `activity` for `idle_timeout`,
+    /// e.g. to free resources of an idle worker group which is respawned on demand later on.
+    /// Resolves to `true` once the group is halted, or to `false` when the whole handle is
+    /// halted first.
+    pub async fn halt_group_on_idle(
+        &self,
+        group: u8,
+        activity: &Activity,
+        idle_timeout: Duration,
+    ) -> bool {
+        let mut tripwire = self.tripwire();
+        loop {
+            let idle_for = activity.idle_for();
+            if idle_for >= idle_timeout {
+                self.halt_group(group);
+                return true;
+            }
+            tokio::select! {
+                _ = time::sleep(idle_timeout - idle_for) => {}
+                _ = &mut tripwire => return false,
+            }
+        }
+    }
+
+    /// Wait for tasks of `group` to finish, usually after `halt_group()` has been called.
+    /// An optional `timeout` is the maximum time to wait.
+    ///
+    /// Returns the same errors as `join()`. The tasks that haven't been joined when an error
+    /// is returned are kept in the group, so that they are collected by another
+    /// `join_group()`, by `join()` or aborted by `halt_then_abort()`.
+    pub async fn join_group(&self, group: u8, timeout: Option<Duration>) -> Result<(), HaltError> {
+        let mut pending: VecDeque<_> = self
+            .with_group(group, |group| std::mem::take(&mut group.tasks))
+            .into();
+        let res = {
+            let handles = async {
+                while let Some(task) = pending.front_mut() {
+                    let result = (&mut task.handle).await;
+                    task.record(&result);
+                    let task = pending.pop_front().expect("BUG: No pending task");
+                    result.map_err(|error| HaltError::Join {
+                        name: task.name,
+                        error,
+                    })?;
+                }
+                Ok(())
+            };
+            match timeout {
+                Some(timeout) => time::timeout(timeout, handles).await.ok(),
+                None => Some(handles.await),
+            }
+        };
+        let res = res.unwrap_or_else(|| {
+            Err(HaltError::Timeout {
+                pending: pending.iter().map(|task| task.name.clone()).collect(),
+            })
+        });
+
+        if !pending.is_empty() {
+            self.with_group(group, |group| {
+                let spawned_since = std::mem::take(&mut group.tasks);
+                group.tasks.extend(pending);
+                group.tasks.extend(spawned_since);
+            });
+        }
+        res
+    }
+
+    /// Awaits all `tasks` and fails fast if there's a join error
+    async fn join_all(tasks: Vec<NamedTask>) -> Result<(), HaltError> {
+        for task in tasks {
+            task.join().await?;
+        }
+        Ok(())
+    }
+
+    pub fn halt_on_signal(self: &Arc<Self>) {
+        Self::handle_signal(self.clone(), |this| async move { this.halt() });
     }
 
     /// Tell the handle to catch `SIGTERM` & `SIGINT` and run
@@ -363,6 +1334,91 @@ impl HaltHandle {
         }
     }
 
+    /// Tell the handle to catch signals of `kinds` and run the future generated by `f` each time
+    /// one of them is received, `f` is passed the received signal. Unlike `handle_signal()`, this
+    /// can be used multiple times, e.g. to reload configuration on `SignalKind::Hangup` next to
+    /// `halt_on_signal()`. The signals are no longer handled once the handle is halted.
+    ///
+    /// Returns an error when listening for any of the signals fails, see `SignalKind` for the
+    /// signals that are not supported on windows.
+    pub fn handle_signals<FT, FN>(
+        self: Arc<Self>,
+        kinds: &[SignalKind],
+        mut f: FN,
+    ) -> io::Result<()>
+    where
+        FT: Future + Send + 'static,
+        FN: FnMut(Arc<Self>, SignalKind) -> FT + Send + 'static,
+    {
+        let signals = kinds
+            .iter()
+            .map(|kind| signal_stream(*kind))
+            .collect::<io::Result<Vec<_>>>()?;
+        let mut signals = stream::select_all(signals).take_until(self.tripwire());
+        tokio::spawn(async move {
+            while let Some(kind) = signals.next().await {
+                f(self.clone(), kind).await;
+            }
+        });
+        Ok(())
+    }
+
+    /// Tell the handle to halt all the associated tasks and give them `grace` time to stop.
+    /// Tasks that are still running afterwards are aborted. This is an alternative to
+    /// `join()`, it only collects tasks that have been spawned before the call and doesn't wait
+    /// for `ready()`.
+    ///
+    /// # Panics
+    /// Panics when called after `join()` or multiple times.
+    pub async fn halt_then_abort(&self, grace: Duration) -> HaltReport {
+        let tasks = self
+            .tasks
+            .lock()
+            .expect("BUG: HaltHandle: Poisoned mutex")
+            .take()
+            .expect("BUG: HaltHandle: join() or halt_then_abort() called multiple times");
+
+        let mut tasks_rx = tasks.tasks_rx.into_inner();
+        let mut tasks = Vec::new();
+        while let Ok(msg) = tasks_rx.try_recv() {
+            if let TaskMsg::Task(task) = msg {
+                tasks.push(task);
+            }
+        }
+        tasks.extend(self.take_group_tasks());
+
+        self.halt();
+
+        let deadline = time::Instant::now() + grace;
+        let mut report = HaltReport::default();
+        let mut aborted_tasks = Vec::new();
+        for mut task in tasks {
+            match time::timeout_at(deadline, &mut task.handle).await {
+                Ok(result) => {
+                    match result {
+                        Ok(()) => report.cooperative += 1,
+                        Err(_) => report.panicked += 1,
+                    }
+                    task.record(&result);
+                    report.restarts += task.record.restarts.load(Ordering::Relaxed);
+                }
+                Err(_) => {
+                    task.handle.abort();
+                    aborted_tasks.push(task);
+                }
+            }
+        }
+        report.aborted = aborted_tasks.len();
+        // Make sure the aborted tasks are gone before returning
+        for mut task in aborted_tasks {
+            let result = (&mut task.handle).await;
+            task.record(&result);
+            report.restarts += task.record.restarts.load(Ordering::Relaxed);
+        }
+
+        report
+    }
+
     /// Wait for all associated tasks to finish.
     /// Call this function once `ready()` was called on the handle.
     /// It will collect task results once they are stopped with `halt()` or once
@@ -374,55 +1430,307 @@ impl HaltHandle {
     /// Returns `Ok(())` when tasks are collected succesfully, or a `HaltError::Timeout`
     /// if tasks tasks didn't stop in time, or a `HaltError::Join` when a task panics.
     /// If multiple tasks panic, the first join error encountered is returned.
+    /// Once all tasks are collected, the error of the first task spawned with
+    /// `spawn_try_object()` that has failed is returned as `HaltError::TaskFailed`.
     ///
     /// # Panics
-    /// `join()` panics if you call it multiple times. It must only be called once.
+    /// `join()` panics if you call it multiple times. It must only be called once,
+    /// use `join_shared()` to await the tasks from multiple places.
     pub async fn join(&self, timeout: Option<Duration>) -> Result<(), HaltError> {
-        let tasks = self
-            .tasks
-            .lock()
-            .expect("BUG: HaltHandle: Poisoned mutex")
-            .take()
-            .expect("BUG: HaltHandle: join() called multiple times");
+        self.join_tasks(timeout).await?;
+        self.take_task_failure()
+    }
 
+    /// Same as `join()`, but the errors of the tasks spawned with `spawn_try_object()` are
+    /// left for the caller to collect
+    async fn join_tasks(&self, timeout: Option<Duration>) -> Result<(), HaltError> {
         let Tasks {
             tasks_rx,
             notify_join,
-        } = tasks;
+        } = self.take_tasks();
 
         // Map the incomming handles stream (up to the Ready mesage) into a future
         // that awaits them and fails fast if there's a join error.
         let handles = tasks_rx
             .take_while(|task_msg| future::ready(!matches!(task_msg, TaskMsg::Ready)))
             .map(|msg| match msg {
-                TaskMsg::Task(handle) => handle,
+                TaskMsg::Task(task) => task,
                 TaskMsg::Ready => unreachable!("BUG: Unexpected Ready message"),
             })
-            .fold(Ok(()), |res, handle| async {
+            .fold(Ok(()), |res, task| async {
                 if res.is_ok() {
-                    handle.await.map_err(HaltError::Join)
+                    task.join().await
                 } else {
                     res
                 }
-            });
+            })
+            // Tasks spawned into groups are collected once the rest of the tasks is done
+            .and_then(|_| Self::join_all(self.take_group_tasks()));
+
+        tokio::select! {
+            res = handles => res,
+            timeout = self.timeout_after_halt(notify_join, timeout) => timeout,
+            res = self.halt_on_task_failure() => res,
+        }
+    }
+
+    /// Same as `join()`, but it can be called repeatedly, e.g. from multiple places that await
+    /// the shutdown. The first call starts the join with its `timeout`, the `timeout` of the
+    /// other calls is ignored. All calls resolve to the same result once the join completes,
+    /// including the calls made after that.
+    ///
+    /// As with `join()`, the join completes only after `ready()` has been called and all tasks
+    /// spawned before `ready()` have finished. Calling `join_shared()` before `ready()` is fine,
+    /// it just doesn't resolve until then.
+    ///
+    /// # Panics
+    /// Panics if `join()` has been called on the handle, the two can't be combined.
+    pub async fn join_shared(
+        self: &Arc<Self>,
+        timeout: Option<Duration>,
+    ) -> Result<(), Arc<HaltError>> {
+        let join = self
+            .shared_join
+            .lock()
+            .expect("BUG: HaltHandle: Poisoned mutex")
+            .get_or_insert_with(|| {
+                let handle = self.clone();
+                async move { handle.join(timeout).await.map_err(Arc::new) }
+                    .boxed()
+                    .shared()
+            })
+            .clone();
+        join.await
+    }
+
+    /// Same as `join()`, but tasks that are still running once the `timeout` expires are
+    /// aborted and awaited before `HaltError::Timeout` is returned, so that no task is left
+    /// running in the background.
+    ///
+    /// # Panics
+    /// Panics under the same conditions as `join()`.
+    pub async fn join_or_abort(&self, timeout: Duration) -> Result<(), HaltError> {
+        let Tasks {
+            mut tasks_rx,
+            notify_join,
+        } = self.take_tasks();
+
+        // Tasks that haven't been joined yet, in the order they are going to be joined
+        let mut pending = VecDeque::new();
+        let res = {
+            let handles = async {
+                while let Some(TaskMsg::Task(task)) = tasks_rx.next().await {
+                    pending.push_back(task);
+                }
+                // Tasks spawned into groups are collected once the rest of the tasks is known
+                pending.extend(self.take_group_tasks());
+                while let Some(task) = pending.front_mut() {
+                    let result = (&mut task.handle).await;
+                    task.record(&result);
+                    if let Err(error) = result {
+                        let task = pending.pop_front().expect("BUG: No pending task");
+                        return Err(HaltError::Join {
+                            name: task.name,
+                            error,
+                        });
+                    }
+                    pending.pop_front();
+                }
+                Ok(())
+            };
 
-        // Waits for notify_join and then starts to apply the timeout, if any
-        let notify = async move {
-            let _ = notify_join.notified().await;
-            // At this point halt() is confirmed to have been called...
-            if let Some(timeout) = timeout {
-                time::sleep(timeout).await;
-                Err(HaltError::Timeout)
-            } else {
-                future::pending().await
+            tokio::select! {
+                res = handles => res,
+                timeout = self.timeout_after_halt(notify_join, Some(timeout)) => timeout,
+                res = self.halt_on_task_failure() => res,
             }
         };
 
-        tokio::select! {
-            res = handles => res,
-            timeout = notify => timeout,
+        if let Err(HaltError::Timeout { .. }) = res {
+            // Include the tasks that haven't been collected before the timeout expired
+            let mut tasks_rx = tasks_rx.into_inner();
+            while let Ok(msg) = tasks_rx.try_recv() {
+                if let TaskMsg::Task(task) = msg {
+                    pending.push_back(task);
+                }
+            }
+            pending.extend(self.take_group_tasks());
+            for task in pending.iter() {
+                task.handle.abort();
+            }
+            // Make sure the aborted tasks are gone before returning
+            for mut task in pending {
+                let result = (&mut task.handle).await;
+                task.record(&result);
+            }
+        }
+        res?;
+        self.take_task_failure()
+    }
+
+    /// Whether the tasks have been taken by `join()` already
+    pub(crate) fn is_joined(&self) -> bool {
+        self.tasks
+            .lock()
+            .expect("BUG: HaltHandle: Poisoned mutex")
+            .is_none()
+    }
+
+    /// Takes the task handles that are to be joined
+    fn take_tasks(&self) -> Tasks {
+        self.tasks
+            .lock()
+            .expect("BUG: HaltHandle: Poisoned mutex")
+            .take()
+            .expect("BUG: HaltHandle: join() called multiple times")
+    }
+
+    /// Returns the names of the tasks that are still running
+    fn running_tasks(&self) -> Vec<String> {
+        self.task_records
+            .lock()
+            .expect("BUG: HaltHandle: Poisoned mutex")
+            .iter()
+            .filter(|record| record.is_running())
+            .map(|record| record.name.clone())
+            .collect()
+    }
+
+    /// Halts the handle once a task spawned with `spawn_try_object()` fails so that the rest of
+    /// the tasks stops too, never resolves
+    async fn halt_on_task_failure(&self) -> Result<(), HaltError> {
+        self.notify_failed.notified().await;
+        self.halt();
+        future::pending().await
+    }
+
+    /// Returns the error of the first task spawned with `spawn_try_object()` that has failed
+    fn take_task_failure(&self) -> Result<(), HaltError> {
+        let mut task_errors = self
+            .task_errors
+            .lock()
+            .expect("BUG: HaltHandle: Poisoned mutex");
+        if task_errors.is_empty() {
+            Ok(())
+        } else {
+            Err(HaltError::TaskFailed(task_errors.remove(0)))
+        }
+    }
+
+    /// Waits for notify_join and then starts to apply the timeout, if any
+    async fn timeout_after_halt(
+        &self,
+        notify_join: Arc<Notify>,
+        timeout: Option<Duration>,
+    ) -> Result<(), HaltError> {
+        let _ = notify_join.notified().await;
+        // At this point halt() is confirmed to have been called...
+        if let Some(timeout) = timeout {
+            time::sleep(timeout).await;
+            Err(HaltError::Timeout {
+                pending: self.running_tasks(),
+            })
+        } else {
+            future::pending().await
+        }
+    }
+
+    /// Same as `join()`, but reports each task that stops running while the tasks are being
+    /// joined. Tasks that have stopped before are reported first. The stream ends with
+    /// `JoinProgress::Done` carrying the result of `join()`.
+    ///
+    /// # Panics
+    /// The stream panics under the same conditions as `join()`.
+    pub fn join_progress(
+        &self,
+        timeout: Option<Duration>,
+    ) -> impl Stream<Item = JoinProgress> + Send + '_ {
+        struct State<'a> {
+            join: Option<future::BoxFuture<'a, Result<(), HaltError>>>,
+            /// Ids of the task records that have already been reported
+            reported: HashSet<usize>,
+            events: VecDeque<JoinProgress>,
+        }
+
+        let state = State {
+            join: Some(self.join(timeout).boxed()),
+            reported: HashSet::new(),
+            events: VecDeque::new(),
+        };
+        stream::unfold(state, move |mut state| async move {
+            loop {
+                if let Some(event) = state.events.pop_front() {
+                    return Some((event, state));
+                }
+                let join = state.join.as_mut()?;
+                tokio::select! {
+                    result = join => {
+                        state.join = None;
+                        self.collect_stopped(&mut state.reported, &mut state.events);
+                        state.events.push_back(JoinProgress::Done(result));
+                    }
+                    _ = self.notify_stopped.notified() => {
+                        self.collect_stopped(&mut state.reported, &mut state.events);
+                    }
+                }
+            }
+        })
+    }
+
+    /// Appends an event for each task that has stopped and hasn't been `reported` yet to
+    /// `events`, in the order the tasks have stopped
+    fn collect_stopped(&self, reported: &mut HashSet<usize>, events: &mut VecDeque<JoinProgress>) {
+        let tasks: Vec<_> = self
+            .task_records
+            .lock()
+            .expect("BUG: HaltHandle: Poisoned mutex")
+            .iter()
+            .map(|record| (record.id, record.info()))
+            .collect();
+        let mut remaining = tasks
+            .iter()
+            .filter(|(_, info)| info.state == TaskState::Running)
+            .count();
+        let mut stopped: Vec<_> = tasks
+            .into_iter()
+            .filter(|(id, info)| info.state != TaskState::Running && reported.insert(*id))
+            .map(|(_, info)| info)
+            .collect();
+        stopped.sort_by_key(|info| info.finished_at);
+        remaining += stopped.len();
+        for info in stopped {
+            remaining -= 1;
+            events.push_back(JoinProgress::TaskStopped {
+                name: info.name,
+                remaining,
+            });
         }
     }
+
+    /// Same as `join()`, but on success returns information about all tasks including the time
+    /// they stopped running. The per-task lifetimes help with spotting the tasks that dominate
+    /// the shutdown time.
+    pub async fn join_with_trace(
+        &self,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<TaskInfo>, HaltError> {
+        self.join(timeout).await?;
+        Ok(self.tasks_snapshot())
+    }
+
+    /// Same as `join()`, but the errors of all tasks spawned with `spawn_try_object()` that
+    /// have failed are returned on success instead of `HaltError::TaskFailed`
+    pub async fn join_with_errors(
+        &self,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<TaskError>, HaltError> {
+        self.join_tasks(timeout).await?;
+        let mut task_errors = self
+            .task_errors
+            .lock()
+            .expect("BUG: HaltHandle: Poisoned mutex");
+        Ok(task_errors.drain(..).collect())
+    }
 }
 
 #[cfg(test)]
@@ -432,6 +1740,7 @@ mod test {
     use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
     use std::sync::Arc;
 
+    use tokio::sync::oneshot;
     use tokio::time;
 
     /// Wait indefinitely on a stream with a `Tripwire` for cancellation.
@@ -444,6 +1753,283 @@ mod test {
         stream.next().await;
     }
 
+    #[tokio::test]
+    async fn strict_tripwire_reports_dropped_trigger() {
+        let (trigger, tripwire) = Tripwire::new();
+        let mut tripwire = tripwire.strict();
+        assert_eq!(tripwire.cause(), None);
+        drop(trigger);
+        time::timeout(Duration::from_secs(1), &mut tripwire)
+            .await
+            .expect("BUG: Tripwire not resolved after dropping trigger");
+        assert_eq!(tripwire.cause(), Some(TripwireCause::TriggerDropped));
+        assert_eq!(
+            tripwire.clone().cause(),
+            Some(TripwireCause::TriggerDropped)
+        );
+
+        let (trigger, tripwire) = Tripwire::new();
+        let mut tripwire = tripwire.strict();
+        trigger.cancel();
+        (&mut tripwire).await;
+        assert_eq!(tripwire.cause(), Some(TripwireCause::Cancelled));
+
+        // Cause is not tracked in the default mode
+        let (trigger, mut tripwire) = Tripwire::new();
+        drop(trigger);
+        (&mut tripwire).await;
+        assert_eq!(tripwire.cause(), None);
+    }
+
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+    enum ShutdownReason {
+        #[default]
+        Signal,
+        FatalError,
+    }
+
+    #[tokio::test]
+    async fn tripwire_reason() {
+        let (trigger, tripwire) = Tripwire::<ShutdownReason>::new_with_reason();
+        assert_eq!(tripwire.reason(), None);
+        trigger.cancel_with(ShutdownReason::FatalError);
+        assert_eq!(tripwire.reason(), Some(ShutdownReason::FatalError));
+        assert_eq!(tripwire.clone().await, ShutdownReason::FatalError);
+
+        // A dropped trigger resolves the tripwire to the default reason
+        let (trigger, tripwire) = Tripwire::<ShutdownReason>::new_with_reason();
+        drop(trigger);
+        assert_eq!(tripwire.await, ShutdownReason::Signal);
+    }
+
+    #[tokio::test]
+    async fn halthandle_halt_with() {
+        let handle = HaltHandle::<ShutdownReason>::default();
+        let (reason_tx, reason_rx) = oneshot::channel();
+        handle.spawn(move |tripwire| async move {
+            let _ = reason_tx.send(tripwire.await);
+        });
+        handle.spawn_in_group(0, |tripwire| async move {
+            assert_eq!(tripwire.await, ShutdownReason::FatalError);
+        });
+        handle.ready();
+        assert_eq!(handle.halt_reason(), None);
+
+        handle.halt_with(ShutdownReason::FatalError);
+        // Only the first reason is kept
+        handle.halt_with(ShutdownReason::Signal);
+        assert_eq!(handle.halt_reason(), Some(ShutdownReason::FatalError));
+        handle
+            .join(Some(Duration::from_secs(1)))
+            .await
+            .expect("BUG: join() failed");
+        assert_eq!(reason_rx.await.ok(), Some(ShutdownReason::FatalError));
+
+        // Groups created after the halt are halted with the same reason
+        handle.spawn_in_group(1, |tripwire| async move {
+            assert_eq!(tripwire.await, ShutdownReason::FatalError);
+        });
+        handle
+            .join_group(1, Some(Duration::from_secs(1)))
+            .await
+            .expect("BUG: join_group() failed");
+    }
+
+    #[tokio::test]
+    async fn halthandle_spawn_supervised() {
+        let mut backoff = Backoff::new(Duration::from_millis(100), Duration::from_millis(300));
+        let delays: Vec<_> = (0..4).map(|_| backoff.next_delay()).collect();
+        assert_eq!(
+            delays,
+            [100, 200, 300, 300]
+                .iter()
+                .map(|ms| Duration::from_millis(*ms))
+                .collect::<Vec<_>>()
+        );
+
+        let handle = HaltHandle::new();
+        let runs = Arc::new(Mutex::new(Vec::new()));
+        let task_runs = runs.clone();
+        handle.spawn_supervised(
+            "crashing",
+            Backoff::new(Duration::from_millis(100), Duration::from_secs(10)),
+            move |_| {
+                let runs = task_runs.clone();
+                async move {
+                    runs.lock()
+                        .expect("BUG: Poisoned mutex")
+                        .push(Instant::now());
+                    panic!("Task crashed");
+                }
+            },
+        );
+
+        // Runs are expected at ~0, 100 and 300 ms, the next one not before 700 ms
+        while runs.lock().expect("BUG: Poisoned mutex").len() < 3 {
+            time::sleep(Duration::from_millis(10)).await;
+        }
+        let halted_at = Instant::now();
+        let report = handle.halt_then_abort(Duration::from_secs(1)).await;
+        assert!(
+            halted_at.elapsed() < Duration::from_millis(300),
+            "BUG: Halt didn't interrupt the backoff"
+        );
+
+        let runs = runs.lock().expect("BUG: Poisoned mutex");
+        assert!(runs.len() >= 3);
+        for (i, delay) in runs.windows(2).map(|runs| runs[1] - runs[0]).enumerate() {
+            assert!(delay >= Duration::from_millis(100 << i));
+        }
+        assert_eq!(report.cooperative, 1);
+        assert_eq!(report.restarts, runs.len() - 1);
+        let info = &handle.tasks_snapshot()[0];
+        assert_eq!(info.restarts, runs.len() - 1);
+        assert_eq!(info.state, TaskState::Finished);
+    }
+
+    #[tokio::test]
+    async fn halthandle_join_with_errors() {
+        struct Failing;
+
+        impl TrySpawnable for Failing {
+            type Error = String;
+
+            fn run(self, _tripwire: Tripwire) -> JoinHandle<Result<(), String>> {
+                tokio::spawn(async { Err("startup failed".to_string()) })
+            }
+        }
+
+        struct Succeeding;
+
+        impl TrySpawnable for Succeeding {
+            type Error = String;
+
+            fn run(self, tripwire: Tripwire) -> JoinHandle<Result<(), String>> {
+                tokio::spawn(async move {
+                    forever_stream(tripwire).await;
+                    Ok(())
+                })
+            }
+        }
+
+        let handle = HaltHandle::new();
+        handle.spawn_try_object(Failing);
+        handle.spawn_try_object(Succeeding);
+        handle.ready();
+        handle.halt();
+
+        let errors = handle
+            .join_with_errors(Some(Duration::from_secs(1)))
+            .await
+            .expect("BUG: Join failed");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].name, "#0");
+        assert_eq!(errors[0].error.to_string(), "startup failed");
+        assert_eq!(
+            errors[0].source().map(ToString::to_string),
+            Some("startup failed".to_string())
+        );
+        assert!(handle
+            .tasks_snapshot()
+            .iter()
+            .all(|info| info.state == TaskState::Finished));
+    }
+
+    // Verify that a failed task halts the rest of the tasks and join() reports its error
+    #[tokio::test]
+    async fn halthandle_task_failed() {
+        struct Failing;
+
+        impl TrySpawnable for Failing {
+            type Error = String;
+
+            fn run(self, _tripwire: Tripwire) -> JoinHandle<Result<(), String>> {
+                tokio::spawn(async {
+                    time::sleep(Duration::from_millis(50)).await;
+                    Err("connection lost".to_string())
+                })
+            }
+        }
+
+        let handle = HaltHandle::new();
+        handle.spawn(forever_stream);
+        handle.spawn_try_object(Failing);
+        handle.ready();
+
+        let err = handle
+            .join(Some(Duration::from_secs(1)))
+            .await
+            .expect_err("BUG: Task failure not reported");
+        assert!(handle.is_halted());
+        match &err {
+            HaltError::TaskFailed(task_error) => assert_eq!(task_error.name, "#1"),
+            _ => panic!("BUG: Unexpected error: {}", err),
+        }
+        assert_eq!(err.to_string(), "Task #1 failed: connection lost");
+        assert_eq!(
+            err.source().map(ToString::to_string),
+            Some("connection lost".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn halthandle_join_with_trace() {
+        let handle = HaltHandle::new();
+        let delays = [
+            ("short", Duration::from_millis(50)),
+            ("long", Duration::from_millis(200)),
+        ];
+        for (name, delay) in delays.iter() {
+            let delay = *delay;
+            handle.spawn_named(*name, move |_| time::sleep(delay));
+        }
+        handle.ready();
+
+        let trace = handle
+            .join_with_trace(None)
+            .await
+            .expect("BUG: Join failed");
+        assert_eq!(trace.len(), delays.len());
+        for (info, (name, delay)) in trace.iter().zip(delays.iter()) {
+            assert_eq!(info.name, *name);
+            assert_eq!(info.state, TaskState::Finished);
+            let finished_at = info.finished_at.expect("BUG: Missing finish time");
+            assert!(finished_at >= info.spawned_at);
+            let lifetime = info.lifetime().expect("BUG: Missing lifetime");
+            assert!(lifetime >= *delay, "BUG: {} finished too early", name);
+            assert!(
+                lifetime < *delay + Duration::from_secs(1),
+                "BUG: {} finished too late",
+                name
+            );
+        }
+        assert!(trace[0].finished_at < trace[1].finished_at);
+    }
+
+    #[tokio::test]
+    async fn halthandle_join_progress() {
+        let handle = HaltHandle::new();
+        for (name, delay) in [("first", 30), ("second", 80), ("third", 130)].iter() {
+            let delay = Duration::from_millis(*delay);
+            handle.spawn_named(*name, move |_| time::sleep(delay));
+        }
+        handle.ready();
+
+        let events: Vec<_> = handle.join_progress(None).collect().await;
+        let stopped: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                JoinProgress::TaskStopped { name, remaining } => Some((name.as_str(), *remaining)),
+                JoinProgress::Done(_) => None,
+            })
+            .collect();
+        assert_eq!(stopped, vec![("first", 2), ("second", 1), ("third", 0)]);
+        match events.last() {
+            Some(JoinProgress::Done(Ok(()))) => (),
+            event => panic!("BUG: Unexpected last event: {:?}", event),
+        }
+    }
+
     // Basic functional test
     #[tokio::test]
     async fn halthandle_basic() {
@@ -460,6 +2046,81 @@ mod test {
         handle.join(None).await.expect("BUG: join() failed");
     }
 
+    #[tokio::test]
+    async fn halthandle_is_halted() {
+        let handle = HaltHandle::new();
+        handle.spawn(|tripwire| forever_stream(tripwire));
+        handle.ready();
+        assert!(!handle.is_halted());
+        assert!(!handle.tripwire().is_tripped());
+
+        handle.halt();
+        assert!(handle.is_halted());
+        assert!(handle.tripwire().is_tripped());
+        handle.join(None).await.expect("BUG: join() failed");
+        assert!(handle.is_halted());
+    }
+
+    #[tokio::test]
+    async fn halthandle_halted() {
+        let handle = HaltHandle::new();
+        let halted = handle.halted();
+        assert!(handle.halted().now_or_never().is_none());
+
+        // Halting before ready() is reported as well
+        handle.halt();
+        assert!(handle.is_halted());
+        tokio::select! {
+            _ = halted => {}
+            _ = time::sleep(Duration::from_secs(1)) => panic!("BUG: halted() not resolved"),
+        }
+        assert!(handle.halted().now_or_never().is_some());
+
+        handle.ready();
+        handle.join(None).await.expect("BUG: join() failed");
+    }
+
+    #[tokio::test]
+    async fn halthandle_drain() {
+        let handle = HaltHandle::new();
+        let in_flight_done = Arc::new(AtomicBool::new(false));
+
+        // The task finishes its work in progress once draining starts
+        let in_flight_done2 = in_flight_done.clone();
+        handle.spawn_with_drain(move |draining, tripwire| async move {
+            draining.await;
+            assert!(!tripwire.is_tripped());
+            time::sleep(Duration::from_millis(50)).await;
+            in_flight_done2.store(true, Ordering::SeqCst);
+        });
+        handle.ready();
+        assert!(!handle.is_draining());
+
+        assert!(handle.drain(Duration::from_secs(5)).await);
+        assert!(in_flight_done.load(Ordering::SeqCst));
+        assert!(handle.is_draining());
+        assert!(handle.is_halted());
+        handle
+            .join(Some(Duration::from_secs(1)))
+            .await
+            .expect("BUG: join() failed");
+    }
+
+    #[tokio::test]
+    async fn halthandle_drain_timeout() {
+        let handle = HaltHandle::new();
+        // Ignores draining, stops only once halted
+        handle.spawn_with_drain(|_draining, tripwire| forever_stream(tripwire));
+        handle.ready();
+
+        assert!(!handle.drain(Duration::from_millis(50)).await);
+        assert!(handle.is_halted());
+        handle
+            .join(Some(Duration::from_secs(1)))
+            .await
+            .expect("BUG: join() failed");
+    }
+
     // Test that Tripwire won't abort a task right away
     // without halt() being called (this was a bug).
     #[tokio::test]
@@ -569,7 +2230,7 @@ mod test {
 
         // Verify we've got a timeout
         match &res {
-            Err(HaltError::Timeout) => (),
+            Err(HaltError::Timeout { .. }) => (),
             _ => panic!(
                 "BUG: join result was supposed to be HaltError::Timeout but was instead: {:?}",
                 res
@@ -609,11 +2270,499 @@ mod test {
 
         // Verify we've got a join error
         match &res {
-            Err(HaltError::Join(_)) => (),
+            Err(HaltError::Join { .. }) => (),
             _ => panic!(
                 "BUG: join result was supposed to be HaltError::Join but was instead: {:?}",
                 res
             ),
         }
     }
+
+    #[tokio::test]
+    async fn halt_error_log_fields() {
+        let err = HaltError::Timeout {
+            pending: vec!["listener".to_string(), "#1".to_string()],
+        };
+        assert_eq!(
+            err.to_log_fields(),
+            vec![
+                ("kind", "timeout".to_string()),
+                ("pending", "listener, #1".to_string())
+            ]
+        );
+        assert_eq!(err.to_string(), "Timeout, pending tasks: listener, #1");
+
+        let handle = HaltHandle::new();
+        handle.spawn(|_| async {
+            panic!("Things aren't going well");
+        });
+        handle.ready();
+        handle.halt();
+        let err = handle
+            .join(Some(Duration::from_millis(100)))
+            .await
+            .expect_err("BUG: Panic not reported");
+        let fields = err.to_log_fields();
+        assert_eq!(fields[0], ("kind", "join".to_string()));
+        assert_eq!(fields[1], ("cause", "panic".to_string()));
+        assert_eq!(fields[2].0, "message");
+        assert!(
+            fields[2].1.contains("Things aren't going well"),
+            "BUG: Missing panic message: {}",
+            fields[2].1
+        );
+        assert_eq!(fields[3], ("task", "#0".to_string()));
+    }
+
+    #[tokio::test]
+    async fn halthandle_panic_task_name() {
+        let handle = HaltHandle::new();
+        handle.spawn_named("healthy", forever_stream);
+        handle.spawn_named("crashing", |_| async {
+            panic!("Things aren't going well");
+        });
+        handle.spawn_in_group(1, forever_stream);
+        handle.ready();
+        handle.halt();
+
+        match handle.join(Some(Duration::from_secs(1))).await {
+            Err(err @ HaltError::Join { .. }) => {
+                assert!(
+                    err.to_string().contains("crashing"),
+                    "BUG: Missing task name: {}",
+                    err
+                );
+                match err {
+                    HaltError::Join { name, error } => {
+                        assert_eq!(name, "crashing");
+                        assert!(error.is_panic());
+                    }
+                    _ => unreachable!(),
+                }
+            }
+            res => panic!("BUG: Unexpected join result: {:?}", res),
+        }
+    }
+
+    // Verify that all callers of join_shared() get the result of the join
+    #[tokio::test]
+    async fn halthandle_join_shared() {
+        let handle = HaltHandle::arc();
+        handle.spawn(forever_stream);
+        handle.spawn_named("panicking", |tripwire| async move {
+            tripwire.await;
+            panic!("Panic on halt");
+        });
+
+        // Callers may start waiting before ready() is called
+        let waiters: Vec<_> = (0..2)
+            .map(|_| {
+                let handle = handle.clone();
+                tokio::spawn(async move { handle.join_shared(None).await })
+            })
+            .collect();
+        handle.ready();
+        handle.halt();
+
+        for waiter in waiters {
+            let err = waiter
+                .await
+                .expect("BUG: Waiter failed")
+                .expect_err("BUG: Join error not reported");
+            assert!(matches!(&*err, HaltError::Join { name, .. } if name == "panicking"));
+        }
+        // The result is also available once the join is done
+        let err = handle
+            .join_shared(None)
+            .await
+            .expect_err("BUG: Join error not reported");
+        assert!(matches!(*err, HaltError::Join { .. }));
+    }
+
+    // Verify that the outstanding tasks count drops as the tasks are halted one by one
+    #[tokio::test]
+    async fn halthandle_outstanding_tasks() {
+        const N: u8 = 4;
+        let handle = HaltHandle::new();
+        assert_eq!(handle.outstanding_tasks(), 0);
+
+        for group in 0..N {
+            handle.spawn_in_group(group, forever_stream);
+        }
+        handle.spawn(forever_stream);
+        handle.ready();
+        assert_eq!(handle.outstanding_tasks(), N as usize + 1);
+
+        for group in 0..N {
+            handle.halt_group(group);
+            handle
+                .join_group(group, Some(Duration::from_secs(1)))
+                .await
+                .expect("BUG: join_group() failed");
+            assert_eq!(handle.outstanding_tasks(), (N - group) as usize);
+        }
+
+        handle.halt();
+        handle
+            .join(Some(Duration::from_secs(1)))
+            .await
+            .expect("BUG: join() failed");
+        assert_eq!(handle.outstanding_tasks(), 0);
+    }
+
+    // Verify that halting a group leaves tasks of other groups running
+    #[tokio::test]
+    async fn halthandle_halt_group() {
+        let handle = HaltHandle::new();
+        let finished = Arc::new(AtomicUsize::new(0));
+
+        for group in &[1, 1, 2] {
+            let finished = finished.clone();
+            handle.spawn_in_group(*group, move |tripwire| async move {
+                forever_stream(tripwire).await;
+                finished.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+
+        handle.halt_group(1);
+        handle
+            .join_group(1, Some(Duration::from_secs(1)))
+            .await
+            .expect("BUG: join_group() failed");
+        assert_eq!(finished.load(Ordering::SeqCst), 2);
+
+        // Tasks of the other group keep running
+        time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(finished.load(Ordering::SeqCst), 2);
+        assert_eq!(
+            handle
+                .tasks_snapshot()
+                .iter()
+                .filter(|info| info.state == TaskState::Running)
+                .count(),
+            1
+        );
+
+        // Tasks respawned into the halted group are not affected by the previous halt
+        handle.spawn_in_group(1, forever_stream);
+        handle.ready();
+        handle.halt();
+        handle.join(None).await.expect("BUG: join() failed");
+    }
+
+    // Verify that the tasks of a group that time out are kept in the group
+    #[tokio::test]
+    async fn halthandle_join_group_timeout() {
+        let handle = HaltHandle::new();
+
+        // The group isn't halted, so its task doesn't stop
+        handle.spawn_in_group(1, forever_stream);
+        let res = handle.join_group(1, Some(Duration::from_millis(50))).await;
+        assert!(matches!(res, Err(HaltError::Timeout { .. })));
+        assert_eq!(handle.outstanding_tasks(), 1);
+
+        handle.halt_group(1);
+        handle
+            .join_group(1, Some(Duration::from_secs(1)))
+            .await
+            .expect("BUG: join_group() failed");
+        assert_eq!(handle.outstanding_tasks(), 0);
+    }
+
+    #[tokio::test]
+    async fn halthandle_halt_group_on_idle() {
+        const IDLE_TIMEOUT: Duration = Duration::from_millis(100);
+
+        let handle = HaltHandle::arc();
+        let finished = Arc::new(AtomicUsize::new(0));
+        for group in &[1, 2] {
+            let finished = finished.clone();
+            handle.spawn_in_group(*group, move |tripwire| async move {
+                forever_stream(tripwire).await;
+                finished.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+
+        // Group 2 is kept busy
+        let active = Activity::new();
+        let touching = active.clone();
+        handle.spawn_in_group(2, move |tripwire| async move {
+            let mut ticks =
+                tokio_stream::wrappers::IntervalStream::new(time::interval(IDLE_TIMEOUT / 5))
+                    .take_until(tripwire);
+            while ticks.next().await.is_some() {
+                touching.touch();
+            }
+        });
+        let active_watchdog = {
+            let handle = handle.clone();
+            tokio::spawn(async move { handle.halt_group_on_idle(2, &active, IDLE_TIMEOUT).await })
+        };
+
+        let idle = Activity::new();
+        let idle_watchdog = handle.halt_group_on_idle(1, &idle, IDLE_TIMEOUT);
+        assert!(time::timeout(Duration::from_secs(1), idle_watchdog)
+            .await
+            .expect("BUG: Idle group not halted"));
+        assert!(idle.idle_for() >= IDLE_TIMEOUT);
+        handle
+            .join_group(1, Some(Duration::from_secs(1)))
+            .await
+            .expect("BUG: join_group() failed");
+        assert_eq!(finished.load(Ordering::SeqCst), 1);
+
+        // The active group keeps running until the whole handle is halted
+        time::sleep(IDLE_TIMEOUT * 2).await;
+        assert_eq!(finished.load(Ordering::SeqCst), 1);
+        handle.ready();
+        handle.halt();
+        assert!(!active_watchdog.await.expect("BUG: Watchdog panicked"));
+        handle
+            .join(Some(Duration::from_secs(1)))
+            .await
+            .expect("BUG: join() failed");
+        assert_eq!(finished.load(Ordering::SeqCst), 2);
+    }
+
+    /// Sends real signals to the whole test process, which may affect other tests running in
+    /// parallel, run it separately with `cargo test -- --ignored`.
+    #[cfg(target_family = "unix")]
+    #[tokio::test]
+    #[ignore]
+    async fn halthandle_handle_signals() {
+        let send_signal = |signal: &str| {
+            let status = std::process::Command::new("kill")
+                .args([signal, &std::process::id().to_string()])
+                .status()
+                .expect("BUG: Cannot run kill");
+            assert!(status.success());
+        };
+
+        let handle = HaltHandle::arc();
+        let (signal_tx, mut signal_rx) = mpsc::unbounded_channel();
+        handle
+            .clone()
+            .handle_signals(
+                &[SignalKind::Hangup, SignalKind::User1],
+                move |_handle, kind| {
+                    let _ = signal_tx.send(kind);
+                    future::ready(())
+                },
+            )
+            .expect("BUG: Cannot handle signals");
+
+        for (signal, kind) in &[
+            ("-HUP", SignalKind::Hangup),
+            ("-USR1", SignalKind::User1),
+            ("-HUP", SignalKind::Hangup),
+        ] {
+            send_signal(signal);
+            let received = time::timeout(Duration::from_secs(5), signal_rx.recv())
+                .await
+                .expect("BUG: Signal not received");
+            assert_eq!(received, Some(*kind));
+        }
+
+        // The handler is dropped once the handle halts
+        handle.ready();
+        handle.halt();
+        handle.join(None).await.expect("BUG: join() failed");
+        let received = time::timeout(Duration::from_secs(5), signal_rx.recv())
+            .await
+            .expect("BUG: Signal handler not dropped");
+        assert_eq!(received, None);
+    }
+
+    // Verify that tasks ignoring the tripwire are aborted after the grace period
+    #[tokio::test]
+    async fn halthandle_halt_then_abort() {
+        let handle = HaltHandle::new();
+
+        handle.spawn_named("well-behaved", forever_stream);
+        handle.spawn_named("ignoring-tripwire", |_tripwire| future::pending());
+
+        let report = handle.halt_then_abort(Duration::from_millis(100)).await;
+        assert_eq!(
+            report,
+            HaltReport {
+                cooperative: 1,
+                panicked: 0,
+                aborted: 1,
+                restarts: 0,
+            }
+        );
+        let states = handle
+            .tasks_snapshot()
+            .into_iter()
+            .map(|info| info.state)
+            .collect::<Vec<_>>();
+        assert_eq!(states, vec![TaskState::Finished, TaskState::Aborted]);
+    }
+
+    #[tokio::test]
+    async fn halthandle_abort_spawn_object() {
+        /// Sets the flag when dropped, i.e. when the task is aborted
+        struct DropFlag(Arc<AtomicBool>);
+
+        impl Drop for DropFlag {
+            fn drop(&mut self) {
+                self.0.store(true, Ordering::SeqCst);
+            }
+        }
+
+        struct Stubborn(Arc<AtomicBool>);
+
+        impl Spawnable for Stubborn {
+            fn run(self, _tripwire: Tripwire) -> JoinHandle<()> {
+                tokio::spawn(async move {
+                    let _flag = DropFlag(self.0);
+                    future::pending::<()>().await;
+                })
+            }
+        }
+
+        let handle = HaltHandle::new();
+        let dropped = Arc::new(AtomicBool::new(false));
+        handle.spawn_object(Stubborn(dropped.clone()));
+
+        let report = handle.halt_then_abort(Duration::from_millis(50)).await;
+        assert_eq!(report.aborted, 1);
+        assert_eq!(handle.tasks_snapshot()[0].state, TaskState::Aborted);
+
+        // Aborting the original task takes effect asynchronously
+        time::timeout(Duration::from_secs(1), async {
+            while !dropped.load(Ordering::SeqCst) {
+                time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("BUG: Spawnable not aborted");
+    }
+
+    #[tokio::test]
+    async fn halthandle_join_or_abort() {
+        /// Sets the flag when dropped, i.e. when the task is aborted
+        struct DropFlag(Arc<AtomicBool>);
+
+        impl Drop for DropFlag {
+            fn drop(&mut self) {
+                self.0.store(true, Ordering::SeqCst);
+            }
+        }
+
+        let handle = HaltHandle::new();
+        let dropped = Arc::new(AtomicBool::new(false));
+        let dropped_in_task = dropped.clone();
+        handle.spawn(|tripwire| tripwire.map(|_| ()));
+        handle.spawn(|_tripwire| async move {
+            let _flag = DropFlag(dropped_in_task);
+            time::sleep(Duration::from_secs(9000)).await;
+        });
+        handle.ready();
+        handle.halt();
+
+        let res = handle.join_or_abort(Duration::from_millis(50)).await;
+        match res {
+            Err(HaltError::Timeout { pending }) => assert_eq!(pending, vec!["#1".to_string()]),
+            _ => panic!("BUG: Unexpected join result: {:?}", res),
+        }
+        // The stubborn task has been aborted and awaited by the time join_or_abort() returns
+        assert!(dropped.load(Ordering::SeqCst));
+        let states: Vec<_> = handle
+            .tasks_snapshot()
+            .into_iter()
+            .map(|info| info.state)
+            .collect();
+        assert_eq!(states, vec![TaskState::Finished, TaskState::Aborted]);
+    }
+
+    // Verify that join_or_abort() also aborts tasks it hasn't collected before the timeout,
+    // i.e. group tasks and tasks of a handle that never got ready()
+    #[tokio::test]
+    async fn halthandle_join_or_abort_uncollected() {
+        let handle = HaltHandle::new();
+        handle.spawn(|_tripwire| time::sleep(Duration::from_secs(9000)));
+        handle.spawn_in_group(1, |_tripwire| time::sleep(Duration::from_secs(9000)));
+        handle.halt();
+
+        let res = handle.join_or_abort(Duration::from_millis(50)).await;
+        assert!(matches!(res, Err(HaltError::Timeout { .. })));
+        assert_eq!(handle.outstanding_tasks(), 0);
+        assert!(handle
+            .tasks_snapshot()
+            .iter()
+            .all(|info| info.state == TaskState::Aborted));
+    }
+
+    // Verify that the snapshot reflects states of finished, panicked and running tasks
+    #[tokio::test]
+    async fn halthandle_tasks_snapshot() {
+        let handle = HaltHandle::new();
+
+        handle.spawn_named("finishing", |_| future::ready(()));
+        handle.spawn_named("panicking", |_| async {
+            panic!("Things aren't going well");
+        });
+        handle.spawn_named("forever", forever_stream);
+        let (trigger, tripwire) = Tripwire::new();
+        handle.add_task(tokio::spawn(forever_stream(tripwire)));
+
+        // Give the short-lived tasks time to complete
+        time::sleep(Duration::from_millis(100)).await;
+
+        let states = |handle: &HaltHandle| {
+            handle
+                .tasks_snapshot()
+                .into_iter()
+                .map(|info| (info.name, info.state))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            states(&handle),
+            vec![
+                ("finishing".to_string(), TaskState::Finished),
+                ("panicking".to_string(), TaskState::Panicked),
+                ("forever".to_string(), TaskState::Running),
+                ("#3".to_string(), TaskState::Running),
+            ]
+        );
+
+        trigger.cancel();
+        // Unlike join(), halt_then_abort() doesn't stop at the panicked task, the state of the
+        // added task is recorded once it's awaited
+        handle.halt_then_abort(Duration::from_secs(1)).await;
+        assert!(handle
+            .tasks_snapshot()
+            .iter()
+            .all(|info| info.state != TaskState::Running));
+    }
+
+    // Verify that only the latest records of stopped tasks are kept and that unnamed tasks
+    // keep being numbered in the order they were spawned
+    #[tokio::test]
+    async fn halthandle_finished_task_records() {
+        let handle = HaltHandle::new();
+
+        handle.spawn_named("forever", forever_stream);
+        for _ in 0..FINISHED_TASK_RECORDS + 10 {
+            handle.spawn(|_| future::ready(()));
+        }
+        time::sleep(Duration::from_millis(100)).await;
+        // Records are pruned when a task is spawned
+        handle.spawn(|_| future::ready(()));
+        time::sleep(Duration::from_millis(100)).await;
+
+        let tasks = handle.tasks_snapshot();
+        assert_eq!(tasks.len(), FINISHED_TASK_RECORDS + 2);
+        assert_eq!(tasks[0].name, "forever");
+        assert_eq!(tasks[0].state, TaskState::Running);
+        assert_eq!(
+            tasks.last().expect("BUG: No task").name,
+            format!("#{}", FINISHED_TASK_RECORDS + 11)
+        );
+
+        handle.ready();
+        handle.halt();
+        handle.join(None).await.expect("BUG: join failed");
+    }
 }
@@ -28,6 +28,12 @@ pub use halthandle::*;
 mod maybe_future;
 pub use maybe_future::MaybeFuture;
 
+mod drain_on_halt;
+pub use drain_on_halt::DrainOnHalt;
+
+mod scoped_halt;
+pub use scoped_halt::ScopedHalt;
+
 use std::panic::{self, PanicInfo};
 use std::pin::Pin;
 use std::process;
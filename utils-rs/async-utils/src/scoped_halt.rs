@@ -0,0 +1,174 @@
+// Copyright (C) 2020  Braiins Systems s.r.o.
+//
+// This file is part of Braiins Open-Source Initiative (BOSI).
+//
+// BOSI is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+//
+// Please, keep in mind that we may also license BOSI or any part thereof
+// under a proprietary license. For more information on the terms and conditions
+// of such proprietary license or if you have any other questions, please
+// contact us at opensource@braiins.com.
+
+use std::ops::Deref;
+use std::time::Duration;
+
+use ii_logging::macros::*;
+use tokio::{runtime, task};
+
+use crate::HaltHandle;
+
+/// Guard that halts the tasks spawned through it when it goes out of scope, so that a function
+/// can spawn helper tasks that are guaranteed to be told to stop when the function returns.
+/// Tasks are spawned via the `HaltHandle` methods that the guard dereferences to.
+///
+/// By default the tasks are only halted and the drop doesn't wait for them, see
+/// `join_on_drop()`.
+#[derive(Default)]
+pub struct ScopedHalt {
+    handle: HaltHandle,
+    join_timeout: Option<Duration>,
+}
+
+impl ScopedHalt {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes the drop also wait up to `timeout` for the tasks to stop.
+    ///
+    /// There's no async drop, the wait blocks the dropping thread via
+    /// `tokio::task::block_in_place()`. This is only supported by the multi-threaded runtime,
+    /// the drop only halts the tasks (and logs a warning) when dropped on the current thread
+    /// runtime or outside of any runtime. The wait is skipped when the tasks have already been
+    /// joined through the guard.
+    pub fn join_on_drop(mut self, timeout: Duration) -> Self {
+        self.join_timeout = Some(timeout);
+        self
+    }
+}
+
+impl Deref for ScopedHalt {
+    type Target = HaltHandle;
+
+    fn deref(&self) -> &Self::Target {
+        &self.handle
+    }
+}
+
+impl Drop for ScopedHalt {
+    fn drop(&mut self) {
+        self.handle.halt();
+        let timeout = match self.join_timeout {
+            Some(timeout) if !self.handle.is_joined() => timeout,
+            _ => return,
+        };
+        // Panicking here would abort the process when the guard is dropped during unwinding
+        let runtime = match runtime::Handle::try_current() {
+            Ok(runtime) if runtime.runtime_flavor() == runtime::RuntimeFlavor::MultiThread => {
+                runtime
+            }
+            _ => {
+                warn!("ScopedHalt: Cannot wait for the tasks outside of a multi-threaded runtime, the tasks are only halted");
+                return;
+            }
+        };
+        let handle = &self.handle;
+        handle.ready();
+        // There's no way to report a join error or a timeout from drop
+        let _ = task::block_in_place(|| runtime.block_on(handle.join(Some(timeout))));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    use crate::FutureExt;
+
+    fn spawn_helper(scope: &ScopedHalt, stopped: Arc<AtomicBool>) {
+        scope.spawn(|tripwire| async move {
+            tripwire.await;
+            stopped.store(true, Ordering::SeqCst);
+        });
+    }
+
+    #[tokio::test]
+    async fn scoped_halt() {
+        let stopped = Arc::new(AtomicBool::new(false));
+        let scope = ScopedHalt::new();
+        spawn_helper(&scope, stopped.clone());
+        assert!(!stopped.load(Ordering::SeqCst));
+
+        drop(scope);
+        async {
+            while !stopped.load(Ordering::SeqCst) {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        }
+        .timeout(Duration::from_secs(1))
+        .await
+        .expect("BUG: Task not halted by dropping the scope");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn scoped_halt_join_on_drop() {
+        let stopped = Arc::new(AtomicBool::new(false));
+        let scope = ScopedHalt::new().join_on_drop(Duration::from_secs(1));
+        spawn_helper(&scope, stopped.clone());
+
+        drop(scope);
+        // The task has been joined by the drop
+        assert!(stopped.load(Ordering::SeqCst));
+
+        // Already joined tasks are not joined again
+        let stopped = Arc::new(AtomicBool::new(false));
+        let scope = ScopedHalt::new().join_on_drop(Duration::from_secs(1));
+        spawn_helper(&scope, stopped.clone());
+        scope.ready();
+        scope.halt();
+        scope
+            .join(Some(Duration::from_secs(1)))
+            .await
+            .expect("BUG: Cannot join tasks");
+        drop(scope);
+        assert!(stopped.load(Ordering::SeqCst));
+    }
+
+    /// The current thread runtime can't block in place, the tasks are only halted
+    #[tokio::test]
+    async fn scoped_halt_join_on_drop_current_thread() {
+        let stopped = Arc::new(AtomicBool::new(false));
+        let scope = ScopedHalt::new().join_on_drop(Duration::from_secs(1));
+        spawn_helper(&scope, stopped.clone());
+
+        drop(scope);
+        async {
+            while !stopped.load(Ordering::SeqCst) {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        }
+        .timeout(Duration::from_secs(1))
+        .await
+        .expect("BUG: Task not halted by dropping the scope");
+    }
+
+    #[test]
+    fn scoped_halt_join_on_drop_outside_runtime() {
+        let scope = ScopedHalt::new().join_on_drop(Duration::from_secs(1));
+        drop(scope);
+    }
+}